@@ -0,0 +1,497 @@
+//! What roleman keeps in its cache directory, for `roleman cache list|show|clear`.
+//!
+//! Entry files have hashed names; the readable key each one stores next to its data is what
+//! lets these commands say which identity, account and role an entry belongs to. Entries
+//! written before roleman stored keys are still listed, just without those columns.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use tracing::debug;
+
+use crate::config::{Config, DEFAULT_ROLES_CACHE_TTL, SsoIdentity};
+use crate::credentials_cache::{self, StoredCredentials};
+use crate::desktop::PERMISSIONS_CACHE_FILE;
+use crate::error::{Error, Result};
+use crate::history::format_timestamp;
+use crate::model::RoleChoice;
+use crate::provider;
+use crate::roles_cache::{self, CachedRoles, roleman_cache_dir};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheKind {
+    /// Minted role credentials.
+    Credentials,
+    /// Account/role lists fetched for the selector.
+    Roles,
+    /// Remembered OS automation permissions (macOS).
+    DesktopPermissions,
+}
+
+impl CacheKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CacheKind::Credentials => "credentials",
+            CacheKind::Roles => "roles",
+            CacheKind::DesktopPermissions => "desktop",
+        }
+    }
+}
+
+/// One file in the cache directory, described without its secrets.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheEntry {
+    pub kind: CacheKind,
+    /// File name inside the cache directory; `roleman cache show` accepts it (or a prefix).
+    pub file: String,
+    /// Configured identity the entry belongs to, when its namespace matches one.
+    pub identity: Option<String>,
+    /// Provider cache namespace, for entries whose identity is no longer configured.
+    pub namespace: Option<String>,
+    pub account_id: Option<String>,
+    pub role_name: Option<String>,
+    /// `full` or `readonly`.
+    pub scope: Option<String>,
+    pub region: Option<String>,
+    /// Credentials: when they expire. Roles: when the list stops being reused.
+    pub expires_at: Option<String>,
+    pub expires_in_seconds: Option<u64>,
+    pub expired: bool,
+    /// Roles: how long ago the list was fetched.
+    pub age_seconds: Option<u64>,
+    pub role_count: Option<usize>,
+    /// Credentials: whether the payload is encrypted at rest.
+    pub encrypted: bool,
+}
+
+/// A [`CacheEntry`] with the non-secret details `roleman cache show` prints.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheEntryDetails {
+    #[serde(flatten)]
+    pub entry: CacheEntry,
+    pub path: String,
+    /// Credentials: when roleman minted them.
+    pub issued_at: Option<String>,
+    /// Roles: the cached list.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<RoleChoice>,
+    /// Desktop permissions: the stored settings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<serde_json::Value>,
+}
+
+/// Which entries `list` and `clear` operate on; unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct CacheFilter {
+    pub kind: Option<CacheKind>,
+    pub identity: Option<String>,
+    pub account_id: Option<String>,
+    pub role_name: Option<String>,
+    pub expired_only: bool,
+}
+
+impl CacheFilter {
+    fn matches(&self, entry: &CacheEntry) -> bool {
+        let field = |wanted: &Option<String>, actual: &Option<String>| {
+            wanted.is_none() || wanted.as_deref() == actual.as_deref()
+        };
+        self.kind.is_none_or(|kind| kind == entry.kind)
+            && field(&self.identity, &entry.identity)
+            && field(&self.account_id, &entry.account_id)
+            && field(&self.role_name, &entry.role_name)
+            && (!self.expired_only || entry.expired)
+    }
+}
+
+/// Every cache entry matching `filter`, sorted by kind, identity, account and role.
+pub(crate) fn list(config: &Config, filter: &CacheFilter, now_ms: u64) -> Result<Vec<CacheEntry>> {
+    let identities = identities_by_namespace(config);
+    let mut entries: Vec<CacheEntry> = scan(&identities, now_ms)?
+        .into_iter()
+        .map(|mut entry| {
+            entry.identity = entry
+                .namespace
+                .as_ref()
+                .and_then(|namespace| identities.get(namespace))
+                .map(|identity| identity.name.clone());
+            entry
+        })
+        .filter(|entry| filter.matches(entry))
+        .collect();
+    entries.sort_by(|left, right| {
+        (
+            left.kind as u8,
+            &left.identity,
+            &left.account_id,
+            &left.role_name,
+            &left.file,
+        )
+            .cmp(&(
+                right.kind as u8,
+                &right.identity,
+                &right.account_id,
+                &right.role_name,
+                &right.file,
+            ))
+    });
+    Ok(entries)
+}
+
+/// Delete the entries matching `filter` (or only report them, for `dry_run`).
+pub(crate) fn clear(
+    config: &Config,
+    filter: &CacheFilter,
+    dry_run: bool,
+    now_ms: u64,
+) -> Result<Vec<CacheEntry>> {
+    let entries = list(config, filter, now_ms)?;
+    if dry_run {
+        return Ok(entries);
+    }
+    let dir = roleman_cache_dir()?;
+    for entry in &entries {
+        let path = dir.join(&entry.file);
+        fs::remove_file(&path)
+            .map_err(|err| Error::Cache(format!("failed to remove {}: {err}", path.display())))?;
+        debug!(path = %path.display(), "removed cache entry");
+    }
+    Ok(entries)
+}
+
+/// Details of the one entry whose file name is `name` or starts with it.
+pub(crate) fn show(config: &Config, name: &str, now_ms: u64) -> Result<CacheEntryDetails> {
+    let entries = list(config, &CacheFilter::default(), now_ms)?;
+    let mut matching = entries
+        .into_iter()
+        .filter(|entry| entry.file == name || entry.file.starts_with(name));
+    let entry = match (matching.next(), matching.next()) {
+        (Some(entry), None) => entry,
+        (None, _) => return Err(Error::Cache(format!("no cache entry matches `{name}`"))),
+        (Some(_), Some(_)) => {
+            return Err(Error::Cache(format!(
+                "`{name}` matches several cache entries; use more of the file name"
+            )));
+        }
+    };
+    let path = roleman_cache_dir()?.join(&entry.file);
+    let mut details = CacheEntryDetails {
+        entry,
+        path: path.display().to_string(),
+        issued_at: None,
+        roles: Vec::new(),
+        settings: None,
+    };
+    match details.entry.kind {
+        CacheKind::Credentials => {
+            details.issued_at = StoredCredentials::read(&path)
+                .and_then(|stored| stored.issued_at_ms)
+                .map(|issued_at_ms| format_timestamp((issued_at_ms / 1000) as i64));
+        }
+        CacheKind::Roles => {
+            details.roles = CachedRoles::read(&path)
+                .map(|cached| cached.choices())
+                .unwrap_or_default();
+        }
+        CacheKind::DesktopPermissions => {
+            details.settings = fs::read_to_string(&path)
+                .ok()
+                .and_then(|data| serde_json::from_str(&data).ok());
+        }
+    }
+    Ok(details)
+}
+
+fn scan(identities: &HashMap<String, &SsoIdentity>, now_ms: u64) -> Result<Vec<CacheEntry>> {
+    let dir = roleman_cache_dir()?;
+    let read_dir = match fs::read_dir(&dir) {
+        Ok(read_dir) => read_dir,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(Error::Cache(format!(
+                "failed to read {}: {err}",
+                dir.display()
+            )));
+        }
+    };
+    let mut scanned = Vec::new();
+    for dir_entry in read_dir.flatten() {
+        let file = dir_entry.file_name().to_string_lossy().into_owned();
+        let path = dir_entry.path();
+        let entry = if file.starts_with(credentials_cache::FILE_PREFIX) {
+            credentials_entry(&file, &path, now_ms)
+        } else if file.starts_with(roles_cache::FILE_PREFIX) {
+            roles_entry(&file, &path, identities, now_ms)
+        } else if file == PERMISSIONS_CACHE_FILE {
+            Some(empty_entry(CacheKind::DesktopPermissions, file))
+        } else {
+            None
+        };
+        scanned.extend(entry);
+    }
+    Ok(scanned)
+}
+
+fn credentials_entry(file: &str, path: &Path, now_ms: u64) -> Option<CacheEntry> {
+    let stored = StoredCredentials::read(path)?;
+    let mut entry = empty_entry(CacheKind::Credentials, file.to_string());
+    if let Some(key) = stored.key {
+        entry.namespace = Some(key.namespace);
+        entry.account_id = Some(key.account_id);
+        entry.role_name = Some(key.role_name);
+        entry.scope = Some(key.scope);
+        entry.region = key.region;
+    }
+    entry.expires_at = Some(format_timestamp((stored.expiration_ms / 1000) as i64));
+    entry.expires_in_seconds = Some(stored.expiration_ms.saturating_sub(now_ms) / 1000);
+    entry.expired = stored.expiration_ms <= now_ms;
+    entry.encrypted = stored.encryption.is_some();
+    Some(entry)
+}
+
+fn roles_entry(
+    file: &str,
+    path: &Path,
+    identities: &HashMap<String, &SsoIdentity>,
+    now_ms: u64,
+) -> Option<CacheEntry> {
+    let cached = CachedRoles::read(path)?;
+    let mut entry = empty_entry(CacheKind::Roles, file.to_string());
+    let now = now_ms / 1000;
+    let ttl = cached
+        .namespace
+        .as_ref()
+        .and_then(|namespace| identities.get(namespace))
+        .map_or(DEFAULT_ROLES_CACHE_TTL, |identity| {
+            identity.roles_cache_ttl()
+        });
+    let stale_at = cached.fetched_at + ttl.as_secs();
+    entry.namespace = cached.namespace.clone();
+    entry.expires_at = Some(format_timestamp(stale_at as i64));
+    entry.expires_in_seconds = Some(stale_at.saturating_sub(now));
+    entry.expired = stale_at < now;
+    entry.age_seconds = Some(now.saturating_sub(cached.fetched_at));
+    entry.role_count = Some(cached.choices().len());
+    Some(entry)
+}
+
+fn empty_entry(kind: CacheKind, file: String) -> CacheEntry {
+    CacheEntry {
+        kind,
+        file,
+        identity: None,
+        namespace: None,
+        account_id: None,
+        role_name: None,
+        scope: None,
+        region: None,
+        expires_at: None,
+        expires_in_seconds: None,
+        expired: false,
+        age_seconds: None,
+        role_count: None,
+        encrypted: false,
+    }
+}
+
+/// Configured identities keyed by their providers' cache namespaces.
+fn identities_by_namespace(config: &Config) -> HashMap<String, &SsoIdentity> {
+    config
+        .identities
+        .iter()
+        .filter_map(|identity| {
+            let provider = provider::for_identity(identity).ok()?;
+            Some((provider.cache_namespace(), identity))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CredentialCacheBackend, SsoIdentity};
+    use crate::credentials_cache::CredentialsCache;
+    use crate::provider::AccessScope;
+    use tempfile::TempDir;
+
+    const NOW_MS: u64 = 1_700_000_000_000;
+
+    fn config() -> Config {
+        let identity: SsoIdentity = toml::from_str(
+            r#"
+                name = "work"
+                start_url = "https://acme.awsapps.com/start"
+                sso_region = "us-east-1"
+            "#,
+        )
+        .unwrap();
+        Config {
+            identities: vec![identity],
+            ..Config::default()
+        }
+    }
+
+    fn seed(config: &Config) {
+        let namespace = provider::for_identity(&config.identities[0])
+            .unwrap()
+            .cache_namespace();
+        let cache =
+            CredentialsCache::open(CredentialCacheBackend::File, namespace.clone()).unwrap();
+        cache
+            .save(
+                "1111",
+                "Admin",
+                AccessScope::Full,
+                None,
+                NOW_MS + 3_600_000,
+                "{\"secret\":\"live\"}",
+            )
+            .unwrap();
+        cache
+            .save(
+                "2222",
+                "ReadOnly",
+                AccessScope::ReadOnly,
+                Some("eu-west-1"),
+                NOW_MS - 1_000,
+                "{\"secret\":\"old\"}",
+            )
+            .unwrap();
+        roles_cache::save_cached_roles(
+            &namespace,
+            &[RoleChoice {
+                account_id: "1111".into(),
+                account_name: "Platform".into(),
+                role_name: "Admin".into(),
+            }],
+        )
+        .unwrap();
+        fs::write(
+            roleman_cache_dir().unwrap().join(PERMISSIONS_CACHE_FILE),
+            r#"{"macos_close_auth_tab_authorized":true}"#,
+        )
+        .unwrap();
+    }
+
+    fn with_cache_home(test: impl FnOnce()) {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("XDG_CACHE_HOME").ok();
+        unsafe {
+            std::env::set_var("XDG_CACHE_HOME", temp.path());
+        }
+        test();
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("XDG_CACHE_HOME", value);
+            } else {
+                std::env::remove_var("XDG_CACHE_HOME");
+            }
+        }
+    }
+
+    #[test]
+    fn lists_entries_with_readable_keys() {
+        with_cache_home(|| {
+            let config = config();
+            seed(&config);
+            let entries = list(&config, &CacheFilter::default(), NOW_MS).unwrap();
+            let kinds: Vec<_> = entries.iter().map(|entry| entry.kind).collect();
+            assert_eq!(
+                kinds,
+                [
+                    CacheKind::Credentials,
+                    CacheKind::Credentials,
+                    CacheKind::Roles,
+                    CacheKind::DesktopPermissions
+                ]
+            );
+
+            let live = &entries[0];
+            assert_eq!(live.identity.as_deref(), Some("work"));
+            assert_eq!(live.account_id.as_deref(), Some("1111"));
+            assert_eq!(live.scope.as_deref(), Some("full"));
+            assert_eq!(live.expires_in_seconds, Some(3600));
+            assert!(!live.expired);
+            let old = &entries[1];
+            assert_eq!(old.region.as_deref(), Some("eu-west-1"));
+            assert!(old.expired);
+            assert_eq!(entries[2].role_count, Some(1));
+
+            // Listings describe entries but never carry their secrets.
+            let json = serde_json::to_string(&entries).unwrap();
+            assert!(!json.contains("secret"));
+
+            let filter = CacheFilter {
+                account_id: Some("1111".into()),
+                ..CacheFilter::default()
+            };
+            let only = list(&config, &filter, NOW_MS).unwrap();
+            assert_eq!(only.len(), 1);
+            assert_eq!(only[0].role_name.as_deref(), Some("Admin"));
+
+            // Unknown identities match nothing rather than everything.
+            let filter = CacheFilter {
+                identity: Some("personal".into()),
+                ..CacheFilter::default()
+            };
+            assert!(list(&config, &filter, NOW_MS).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn clears_only_matching_entries() {
+        with_cache_home(|| {
+            let config = config();
+            seed(&config);
+            let expired = CacheFilter {
+                expired_only: true,
+                ..CacheFilter::default()
+            };
+            let planned = clear(&config, &expired, true, NOW_MS).unwrap();
+            assert_eq!(planned.len(), 1);
+            assert_eq!(
+                list(&config, &CacheFilter::default(), NOW_MS)
+                    .unwrap()
+                    .len(),
+                4
+            );
+
+            let removed = clear(&config, &expired, false, NOW_MS).unwrap();
+            assert_eq!(removed[0].account_id.as_deref(), Some("2222"));
+            let remaining = list(&config, &CacheFilter::default(), NOW_MS).unwrap();
+            assert_eq!(remaining.len(), 3);
+            assert!(remaining.iter().all(|entry| !entry.expired));
+
+            clear(&config, &CacheFilter::default(), false, NOW_MS).unwrap();
+            assert!(
+                list(&config, &CacheFilter::default(), NOW_MS)
+                    .unwrap()
+                    .is_empty()
+            );
+        });
+    }
+
+    #[test]
+    fn shows_entries_by_unique_prefix() {
+        with_cache_home(|| {
+            let config = config();
+            seed(&config);
+            let roles = show(&config, roles_cache::FILE_PREFIX, NOW_MS).unwrap();
+            assert_eq!(roles.roles.len(), 1);
+            assert_eq!(roles.roles[0].account_name, "Platform");
+
+            let desktop = show(&config, PERMISSIONS_CACHE_FILE, NOW_MS).unwrap();
+            assert_eq!(
+                desktop.settings,
+                Some(serde_json::json!({"macos_close_auth_tab_authorized": true}))
+            );
+
+            let err = show(&config, credentials_cache::FILE_PREFIX, NOW_MS).unwrap_err();
+            assert!(err.to_string().contains("several"));
+            assert!(show(&config, "nope", NOW_MS).is_err());
+        });
+    }
+}
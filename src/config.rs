@@ -1,10 +1,24 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
 use crate::error::{Error, Result};
 
+/// Default for `min_credential_lifetime_seconds`.
+pub const DEFAULT_MIN_CREDENTIAL_LIFETIME: Duration = Duration::from_secs(60);
+/// Default for `credential_refresh_fraction`.
+pub const DEFAULT_CREDENTIAL_REFRESH_FRACTION: f64 = 0.75;
+/// Default for an identity's `roles_cache_ttl_seconds`.
+pub const DEFAULT_ROLES_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Default for an identity's `list_concurrency`.
+pub const DEFAULT_LIST_CONCURRENCY: usize = 10;
+/// Default for an identity's `max_requests_per_second`.
+pub const DEFAULT_MAX_REQUESTS_PER_SECOND: u32 = 20;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
     #[serde(default)]
@@ -20,6 +34,18 @@ pub struct Config {
     /// When true, `--readonly` creates the roleman-owned read-only IAM role without prompting.
     /// Creation is always announced regardless.
     pub auto_create_readonly_roles: Option<bool>,
+    /// Template `roleman prompt` renders (placeholders such as `{account}`, `{role}`,
+    /// `{expires_in}`); defaults to [`crate::status::DEFAULT_PROMPT_TEMPLATE`].
+    pub prompt_template: Option<String>,
+    /// Cached credentials with less than this left are re-minted instead of exported
+    /// (default 60). Identities can override it.
+    pub min_credential_lifetime_seconds: Option<u64>,
+    /// Once a cached credential has used this fraction of its lifetime, `set` still exports it
+    /// but re-mints it in the background (default 0.75; 1 disables).
+    pub credential_refresh_fraction: Option<f64>,
+    /// How cached role credentials are stored at rest.
+    #[serde(default)]
+    pub credential_cache: CredentialCacheBackend,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -38,6 +64,17 @@ pub enum SelectorSortMode {
     Alphabetical,
 }
 
+/// Where minted role credentials are cached between runs.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum CredentialCacheBackend {
+    /// Plain JSON files only you can read (0600 in a 0700 directory).
+    #[default]
+    File,
+    /// The same files, with the credentials encrypted under a key kept in the OS keyring.
+    Keyring,
+}
+
 /// Which cloud provider an identity authenticates against.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[serde(rename_all = "kebab-case")]
@@ -54,20 +91,22 @@ impl ProviderKind {
     }
 }
 
-/// How `--readonly` drops write access for an identity (AWS).
+/// How `--readonly` drops write access for an identity.
 ///
-/// Defaults to the AWS-managed `ReadOnlyAccess` policy when unset.
+/// Defaults to the AWS-managed `ReadOnlyAccess` policy for AWS. GCP has no default: read-only
+/// needs an inline Credential Access Boundary.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum ReadonlyPolicy {
-    /// Managed policy ARN(s) applied as STS session policies.
+    /// Managed policy ARN(s) applied as STS session policies (AWS only).
     PolicyArns(Vec<String>),
-    /// Inline session policy document (JSON).
+    /// Inline session policy document (AWS) or Credential Access Boundary (GCP), as JSON.
     Inline(String),
 }
 
 impl Config {
     pub fn load(path: Option<&Path>) -> Result<(Self, PathBuf)> {
+        let explicit = path.is_some();
         let path = match path {
             Some(path) => path.to_path_buf(),
             None => default_config_path()?,
@@ -78,10 +117,65 @@ impl Config {
         }
 
         let contents = fs::read_to_string(&path).map_err(|err| Error::Config(err.to_string()))?;
-        let config = toml::from_str(&contents).map_err(|err| Error::Config(err.to_string()))?;
+        let mut config: Config =
+            toml::from_str(&contents).map_err(|err| Error::Config(err.to_string()))?;
+        if let Some(fraction) = config.credential_refresh_fraction
+            && !(fraction > 0.0 && fraction <= 1.0)
+        {
+            return Err(Error::Config(format!(
+                "credential_refresh_fraction must be in (0, 1], got {fraction}"
+            )));
+        }
+        for identity in &config.identities {
+            if identity.list_concurrency == Some(0) {
+                return Err(Error::Config(format!(
+                    "list_concurrency for identity `{}` must be at least 1",
+                    identity.name
+                )));
+            }
+            if identity.max_requests_per_second == Some(0) {
+                return Err(Error::Config(format!(
+                    "max_requests_per_second for identity `{}` must be at least 1",
+                    identity.name
+                )));
+            }
+            if identity.shared_credentials_file && !identity.provider.is_aws() {
+                return Err(Error::Config(format!(
+                    "shared_credentials_file is only supported for AWS identities, not `{}`",
+                    identity.name
+                )));
+            }
+            if identity.isolated_aws_config && !identity.provider.is_aws() {
+                return Err(Error::Config(format!(
+                    "isolated_aws_config is only supported for AWS identities, not `{}`",
+                    identity.name
+                )));
+            }
+        }
+        if explicit {
+            let absolute = std::path::absolute(&path).unwrap_or_else(|_| path.clone());
+            for identity in &mut config.identities {
+                identity.config_file = Some(absolute.clone());
+            }
+        }
         Ok((config, path))
     }
 
+    /// Minimum remaining lifetime for reusing cached credentials of `identity`.
+    pub fn min_credential_lifetime(&self, identity: &SsoIdentity) -> Duration {
+        identity
+            .min_credential_lifetime_seconds
+            .or(self.min_credential_lifetime_seconds)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_MIN_CREDENTIAL_LIFETIME)
+    }
+
+    /// Fraction of its lifetime after which a cached credential is refreshed in the background.
+    pub fn credential_refresh_fraction(&self) -> f64 {
+        self.credential_refresh_fraction
+            .unwrap_or(DEFAULT_CREDENTIAL_REFRESH_FRACTION)
+    }
+
     pub fn save(&self, path: &Path) -> Result<()> {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent).map_err(|err| Error::Config(err.to_string()))?;
@@ -95,7 +189,11 @@ impl Config {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SsoIdentity {
     pub name: String,
+    /// AWS SSO start URL; unused by GCP identities.
+    #[serde(default)]
     pub start_url: String,
+    /// AWS SSO region; unused by GCP identities.
+    #[serde(default)]
     pub sso_region: String,
     /// Cloud provider for this identity. Defaults to AWS so existing configs load unchanged.
     #[serde(default, skip_serializing_if = "ProviderKind::is_aws")]
@@ -107,6 +205,148 @@ pub struct SsoIdentity {
     /// Policy used to drop write access for `--readonly`. `None` uses the provider default.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub readonly_policy: Option<ReadonlyPolicy>,
+    /// Write `credential_process = roleman credential-process ...` into generated AWS
+    /// profiles instead of `sso_*` keys, so tools refresh credentials through roleman.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub credential_process: bool,
+    /// Also write minted credentials into a roleman-managed section of the shared credentials
+    /// file (`~/.aws/credentials`), for tools that can't use SSO profiles.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shared_credentials_file: bool,
+    /// Write this identity's `sso-session` and profiles into roleman's own AWS config file
+    /// (exported as `AWS_CONFIG_FILE`) instead of `~/.aws/config`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub isolated_aws_config: bool,
+    /// The config file this identity was loaded from when `--config` named one; generated
+    /// `credential_process` commands pass it on.
+    #[serde(skip)]
+    pub config_file: Option<PathBuf>,
+    /// Named targets that `roleman set <name>` resolves without listing accounts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shortcuts: Vec<Shortcut>,
+    /// IAM roles reached by assuming them from an SSO role, listed alongside SSO roles (AWS).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chained_roles: Vec<ChainedRole>,
+    /// Overrides the global `min_credential_lifetime_seconds` for this identity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_credential_lifetime_seconds: Option<u64>,
+    /// How long the cached account/role list is shown without refreshing it (default 24h).
+    /// Older lists are still shown, while a fresh list loads behind the selector.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roles_cache_ttl_seconds: Option<u64>,
+    /// How many accounts' roles are listed at once (default 10).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list_concurrency: Option<usize>,
+    /// Ceiling for the adaptive request rate when listing (default 20); the rate drops
+    /// whenever the provider throttles and recovers as requests succeed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_requests_per_second: Option<u32>,
+}
+
+impl SsoIdentity {
+    /// Age after which the cached role list is refreshed.
+    pub fn roles_cache_ttl(&self) -> Duration {
+        self.roles_cache_ttl_seconds
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_ROLES_CACHE_TTL)
+    }
+
+    /// How many accounts' roles to list concurrently.
+    pub fn list_concurrency(&self) -> usize {
+        self.list_concurrency
+            .unwrap_or(DEFAULT_LIST_CONCURRENCY)
+            .max(1)
+    }
+
+    /// Upper bound for the listing request rate.
+    pub fn max_requests_per_second(&self) -> u32 {
+        self.max_requests_per_second
+            .unwrap_or(DEFAULT_MAX_REQUESTS_PER_SECOND)
+            .max(1)
+    }
+
+    /// The shortcut with this name, if the identity defines one.
+    pub fn shortcut(&self, name: &str) -> Option<&Shortcut> {
+        self.shortcuts.iter().find(|shortcut| shortcut.name == name)
+    }
+
+    /// The chained role shown as `role_name` in `account_id`, if one is configured.
+    pub fn chained_role(&self, account_id: &str, role_name: &str) -> Option<&ChainedRole> {
+        self.chained_roles
+            .iter()
+            .find(|role| role.name == role_name && role.account_id() == Some(account_id))
+    }
+
+    /// Role name that keys the credentials cache for `role_name` in `account_id`. Chained roles
+    /// get a `chained:` prefix, which SSO role names can't contain, so their credentials never
+    /// share an entry with an SSO role of the same name.
+    pub fn credentials_cache_role<'a>(&self, account_id: &str, role_name: &'a str) -> Cow<'a, str> {
+        match self.chained_role(account_id, role_name) {
+            Some(_) => Cow::Owned(format!("chained:{role_name}")),
+            None => Cow::Borrowed(role_name),
+        }
+    }
+
+    /// Whether an account rule marks `account_id` as needing confirmation (`confirm = true`).
+    pub fn requires_confirmation(&self, account_id: &str) -> bool {
+        self.accounts
+            .iter()
+            .any(|rule| rule.account_id == account_id && rule.confirm)
+    }
+
+    /// Configured default region for a role: the account rule's per-role entry, then the
+    /// account-wide one. `None` means the provider default (the SSO region).
+    pub fn region_for(&self, account_id: &str, role_name: &str) -> Option<&str> {
+        let rule = self
+            .accounts
+            .iter()
+            .find(|rule| rule.account_id == account_id)?;
+        rule.role_regions
+            .get(role_name)
+            .or(rule.region.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// A favorite account/role pair, selectable by name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Shortcut {
+    pub name: String,
+    pub account_id: String,
+    pub role: String,
+    /// Mint read-only credentials, as if `--readonly` was passed.
+    #[serde(default)]
+    pub readonly: bool,
+    /// Default region for the shortcut; overrides the account rule's region.
+    #[serde(default)]
+    pub region: Option<String>,
+}
+
+/// A downstream IAM role assumed with `sts:AssumeRole` from an SSO role (role chaining).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChainedRole {
+    /// Role name shown in the selector, under the target role's account.
+    pub name: String,
+    /// Account of the SSO role that assumes the target role.
+    pub source_account_id: String,
+    /// SSO role (permission set) that assumes the target role.
+    pub source_role: String,
+    pub role_arn: String,
+    #[serde(default)]
+    pub external_id: Option<String>,
+    /// Requested session length; AWS caps chained sessions at one hour.
+    #[serde(default)]
+    pub duration_seconds: Option<i32>,
+}
+
+impl ChainedRole {
+    /// Account ID from `role_arn` (`arn:aws:iam::<account>:role/...`).
+    pub fn account_id(&self) -> Option<&str> {
+        self.role_arn
+            .split(':')
+            .nth(4)
+            .filter(|account| !account.is_empty())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -119,6 +359,16 @@ pub struct AccountRule {
     pub ignored_roles: Vec<String>,
     #[serde(default)]
     pub precedence: Option<i32>,
+    /// Default region exported for this account's roles instead of the SSO region.
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Per-role region overrides (role name to region), taking precedence over `region`.
+    #[serde(default)]
+    pub role_regions: BTreeMap<String, String>,
+    /// Production guardrail: highlight the account in the selector and require typing its
+    /// alias before exporting full-scope credentials (Enter switches to `--readonly`).
+    #[serde(default)]
+    pub confirm: bool,
 }
 
 fn default_config_path() -> Result<PathBuf> {
@@ -148,12 +398,25 @@ mod tests {
                 sso_region: "us-east-1".into(),
                 provider: ProviderKind::Aws,
                 readonly_policy: None,
+                credential_process: false,
+                shared_credentials_file: false,
+                isolated_aws_config: false,
+                config_file: None,
+                shortcuts: Vec::new(),
+                chained_roles: Vec::new(),
+                min_credential_lifetime_seconds: None,
+                roles_cache_ttl_seconds: None,
+                list_concurrency: None,
+                max_requests_per_second: None,
                 accounts: vec![AccountRule {
                     account_id: "1234".into(),
                     alias: Some("Main".into()),
                     ignored: false,
                     ignored_roles: vec!["Admin".into()],
                     precedence: Some(10),
+                    region: Some("eu-west-1".into()),
+                    role_regions: BTreeMap::from([("Admin".into(), "eu-central-1".into())]),
+                    confirm: false,
                 }],
                 ignore_roles: vec!["ReadOnly".into()],
             }],
@@ -165,10 +428,19 @@ mod tests {
             hook_prompt: None,
             selector_sort: SelectorSortMode::Alphabetical,
             auto_create_readonly_roles: None,
+            prompt_template: None,
+            min_credential_lifetime_seconds: None,
+            credential_refresh_fraction: None,
+            credential_cache: CredentialCacheBackend::File,
         };
 
         config.save(&path).unwrap();
-        let (loaded, _) = Config::load(Some(&path)).unwrap();
+        let (mut loaded, _) = Config::load(Some(&path)).unwrap();
+        // Identities remember an explicit config file, for `credential_process` commands.
+        assert_eq!(
+            loaded.identities[0].config_file.take().as_deref(),
+            Some(path.as_path())
+        );
         assert_eq!(loaded.identities, config.identities);
         assert_eq!(loaded.default_identity, config.default_identity);
         assert_eq!(loaded.refresh_seconds, config.refresh_seconds);
@@ -194,6 +466,267 @@ mod tests {
         assert_eq!(config.identities[0].readonly_policy, None);
     }
 
+    #[test]
+    fn resolves_credential_lifetime_settings() {
+        let toml = r#"
+            min_credential_lifetime_seconds = 300
+
+            [[identities]]
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+
+            [[identities]]
+            name = "ci"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+            min_credential_lifetime_seconds = 900
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.min_credential_lifetime(&config.identities[0]),
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            config.min_credential_lifetime(&config.identities[1]),
+            Duration::from_secs(900)
+        );
+        assert_eq!(
+            Config::default().min_credential_lifetime(&config.identities[0]),
+            DEFAULT_MIN_CREDENTIAL_LIFETIME
+        );
+        assert_eq!(
+            config.credential_refresh_fraction(),
+            DEFAULT_CREDENTIAL_REFRESH_FRACTION
+        );
+    }
+
+    #[test]
+    fn resolves_roles_cache_ttl() {
+        let toml = r#"
+            [[identities]]
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+            roles_cache_ttl_seconds = 3600
+
+            [[identities]]
+            name = "personal"
+            start_url = "https://personal.awsapps.com/start"
+            sso_region = "us-east-1"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.identities[0].roles_cache_ttl(),
+            Duration::from_secs(3600)
+        );
+        assert_eq!(
+            config.identities[1].roles_cache_ttl(),
+            DEFAULT_ROLES_CACHE_TTL
+        );
+    }
+
+    #[test]
+    fn validates_listing_limits() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        let identity = r#"
+            [[identities]]
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+        "#;
+
+        fs::write(
+            &path,
+            format!("{identity}list_concurrency = 4\nmax_requests_per_second = 5\n"),
+        )
+        .unwrap();
+        let (config, _) = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.identities[0].list_concurrency(), 4);
+        assert_eq!(config.identities[0].max_requests_per_second(), 5);
+
+        fs::write(&path, identity).unwrap();
+        let (config, _) = Config::load(Some(&path)).unwrap();
+        assert_eq!(
+            config.identities[0].list_concurrency(),
+            DEFAULT_LIST_CONCURRENCY
+        );
+
+        fs::write(&path, format!("{identity}list_concurrency = 0\n")).unwrap();
+        assert!(Config::load(Some(&path)).is_err());
+        fs::write(&path, format!("{identity}max_requests_per_second = 0\n")).unwrap();
+        assert!(Config::load(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn shared_credentials_file_is_aws_only() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        fs::write(
+            &path,
+            r#"
+            [[identities]]
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+            shared_credentials_file = true
+            "#,
+        )
+        .unwrap();
+        let (config, _) = Config::load(Some(&path)).unwrap();
+        assert!(config.identities[0].shared_credentials_file);
+
+        fs::write(
+            &path,
+            r#"
+            [[identities]]
+            name = "gcp"
+            provider = "gcp"
+            shared_credentials_file = true
+            "#,
+        )
+        .unwrap();
+        assert!(Config::load(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn isolated_aws_config_is_aws_only() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        fs::write(
+            &path,
+            r#"
+            [[identities]]
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+            isolated_aws_config = true
+            "#,
+        )
+        .unwrap();
+        let (config, _) = Config::load(Some(&path)).unwrap();
+        assert!(config.identities[0].isolated_aws_config);
+
+        fs::write(
+            &path,
+            "[[identities]]\nname = \"gcp\"\nprovider = \"gcp\"\nisolated_aws_config = true\n",
+        )
+        .unwrap();
+        assert!(Config::load(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn parses_credential_cache_backend() {
+        let config: Config = toml::from_str("credential_cache = \"keyring\"").unwrap();
+        assert_eq!(config.credential_cache, CredentialCacheBackend::Keyring);
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.credential_cache, CredentialCacheBackend::File);
+        assert!(toml::from_str::<Config>("credential_cache = \"plaintext\"").is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_refresh_fraction() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        fs::write(&path, "credential_refresh_fraction = 1.5\n").unwrap();
+        let err = Config::load(Some(&path)).unwrap_err();
+        assert!(err.to_string().contains("credential_refresh_fraction"));
+    }
+
+    #[test]
+    fn parses_confirm_guardrail() {
+        let toml = r#"
+            [[identities]]
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+            accounts = [
+              { account_id = "111111111111", alias = "Production" },
+              { account_id = "111111111111", confirm = true },
+              { account_id = "222222222222", alias = "Sandbox" },
+            ]
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let identity = &config.identities[0];
+        assert!(identity.requires_confirmation("111111111111"));
+        assert!(!identity.requires_confirmation("222222222222"));
+        assert!(!identity.requires_confirmation("333333333333"));
+    }
+
+    #[test]
+    fn parses_identity_shortcuts() {
+        let toml = r#"
+            [[identities]]
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+
+            [[identities.shortcuts]]
+            name = "prod-ro"
+            account_id = "123456789012"
+            role = "ReadOnly"
+            readonly = true
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let shortcut = config.identities[0].shortcut("prod-ro").unwrap();
+        assert_eq!(shortcut.account_id, "123456789012");
+        assert_eq!(shortcut.role, "ReadOnly");
+        assert!(shortcut.readonly);
+        assert!(config.identities[0].shortcut("missing").is_none());
+    }
+
+    #[test]
+    fn parses_chained_roles() {
+        let toml = r#"
+            [[identities]]
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+
+            [[identities.chained_roles]]
+            name = "deployer"
+            source_account_id = "111111111111"
+            source_role = "Admin"
+            role_arn = "arn:aws:iam::222222222222:role/Deployer"
+            external_id = "acme"
+            duration_seconds = 900
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let identity = &config.identities[0];
+        let chained = identity.chained_role("222222222222", "deployer").unwrap();
+        assert_eq!(chained.account_id(), Some("222222222222"));
+        assert_eq!(chained.external_id.as_deref(), Some("acme"));
+        assert_eq!(chained.duration_seconds, Some(900));
+        assert!(identity.chained_role("111111111111", "deployer").is_none());
+        assert_eq!(
+            identity.credentials_cache_role("222222222222", "deployer"),
+            "chained:deployer"
+        );
+        assert_eq!(
+            identity.credentials_cache_role("111111111111", "deployer"),
+            "deployer"
+        );
+    }
+
+    #[test]
+    fn role_region_overrides_account_region() {
+        let toml = r#"
+            [[identities]]
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+            accounts = [
+              { account_id = "1234", region = "eu-west-1", role_regions = { Admin = "eu-central-1" } },
+            ]
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let identity = &config.identities[0];
+        assert_eq!(identity.region_for("1234", "Admin"), Some("eu-central-1"));
+        assert_eq!(identity.region_for("1234", "ReadOnly"), Some("eu-west-1"));
+        assert_eq!(identity.region_for("9999", "Admin"), None);
+    }
+
     #[test]
     fn aws_provider_is_not_serialized() {
         // Default provider is skipped so saved configs don't sprout `provider = "aws"`.
@@ -203,6 +736,16 @@ mod tests {
             sso_region: "us-east-1".into(),
             provider: ProviderKind::Aws,
             readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
             accounts: Vec::new(),
             ignore_roles: Vec::new(),
         };
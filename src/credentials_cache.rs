@@ -1,15 +1,27 @@
+//! Role credentials roleman minted, kept between runs.
+//!
+//! Each entry is a small JSON envelope in the roleman cache directory, written 0600 inside a
+//! 0700 directory. Expiry and issue time stay readable so status checks never need a key; the
+//! credential payload itself goes through the configured [`CacheBackend`].
+
 use std::fs;
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
 use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
+use tracing::debug;
 
+use crate::config::CredentialCacheBackend;
 use crate::error::{Error, Result};
 use crate::provider::AccessScope;
-use crate::roles_cache::roleman_cache_dir;
+use crate::roles_cache::{create_private_dir, roleman_cache_dir, write_private};
 
-const EXPIRY_SAFETY_SECS: u64 = 60;
+const KEYRING_SERVICE: &str = "roleman";
+const KEYRING_USER: &str = "credential-cache-key";
+const NONCE_LEN: usize = 12;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CachedCredentialsStatus {
@@ -18,83 +30,279 @@ pub enum CachedCredentialsStatus {
     Missing,
 }
 
-/// On-disk envelope: an expiration we can check without parsing the opaque payload,
-/// plus the provider-specific credential JSON it produced.
+/// How credential payloads are stored at rest.
+pub(crate) trait CacheBackend: Send + Sync {
+    /// Recorded in each entry, so entries another backend wrote read as misses. `None` is
+    /// plaintext, which is also what entries from older versions carry.
+    fn encryption(&self) -> Option<&'static str>;
+    fn seal(&self, payload: &str) -> Result<String>;
+    fn open(&self, sealed: &str) -> Result<String>;
+}
+
+/// Payloads stored as-is; the file permissions are the only protection.
+struct PlainBackend;
+
+impl CacheBackend for PlainBackend {
+    fn encryption(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn seal(&self, payload: &str) -> Result<String> {
+        Ok(payload.to_string())
+    }
+
+    fn open(&self, sealed: &str) -> Result<String> {
+        Ok(sealed.to_string())
+    }
+}
+
+/// Payloads encrypted with ChaCha20-Poly1305 under a random key kept in the OS keyring
+/// (macOS Keychain, Secret Service, Windows Credential Manager).
+struct KeyringBackend {
+    cipher: ChaCha20Poly1305,
+}
+
+impl KeyringBackend {
+    /// Fetch the cache key from the keyring, creating it on first use.
+    fn load() -> Result<Self> {
+        let keyring_error = |err: keyring::Error| {
+            Error::CredentialCache(format!(
+                "the OS keyring is unavailable ({err}); set credential_cache = \"file\" instead"
+            ))
+        };
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(keyring_error)?;
+        let key = match entry.get_password() {
+            Ok(hex) => decode_hex(&hex)
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .ok_or_else(|| {
+                    Error::CredentialCache("the cache key in the OS keyring is malformed".into())
+                })?,
+            Err(keyring::Error::NoEntry) => {
+                let key = random_bytes::<32>()?;
+                entry
+                    .set_password(&encode_hex(&key))
+                    .map_err(keyring_error)?;
+                debug!("created credential cache key in the OS keyring");
+                key
+            }
+            Err(err) => return Err(keyring_error(err)),
+        };
+        Ok(Self::with_key(key))
+    }
+
+    fn with_key(key: [u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+        }
+    }
+}
+
+impl CacheBackend for KeyringBackend {
+    fn encryption(&self) -> Option<&'static str> {
+        Some("chacha20poly1305-keyring")
+    }
+
+    fn seal(&self, payload: &str) -> Result<String> {
+        let nonce = random_bytes::<NONCE_LEN>()?;
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), payload.as_bytes())
+            .map_err(|_| Error::CredentialCache("failed to encrypt credentials".into()))?;
+        Ok(encode_hex(&[nonce.as_slice(), &ciphertext].concat()))
+    }
+
+    fn open(&self, sealed: &str) -> Result<String> {
+        let invalid = || Error::CredentialCache("failed to decrypt cached credentials".into());
+        let bytes = decode_hex(sealed).ok_or_else(invalid)?;
+        if bytes.len() < NONCE_LEN {
+            return Err(invalid());
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| invalid())?;
+        String::from_utf8(plaintext).map_err(|_| invalid())
+    }
+}
+
+/// File names of credential entries start with this; the rest is a hash of [`EntryKey`].
+pub(crate) const FILE_PREFIX: &str = "creds-";
+
+/// What a credentials entry is for, stored in the clear so `roleman cache` can describe
+/// the hashed file names.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct EntryKey {
+    pub namespace: String,
+    pub account_id: String,
+    pub role_name: String,
+    pub scope: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+/// On-disk envelope: an expiration (and issue time) we can check without opening the payload,
+/// plus the provider-specific credential JSON it produced, as sealed by the backend.
 #[derive(Debug, Serialize, Deserialize)]
-struct CachedCredentials {
-    expiration_ms: u64,
+pub(crate) struct StoredCredentials {
+    /// Absent in entries written by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<EntryKey>,
+    pub expiration_ms: u64,
+    /// When roleman minted the credentials; absent in entries written by older versions.
+    #[serde(default)]
+    pub issued_at_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<String>,
     payload: String,
 }
 
-/// Load the cached credential payload for a target, or `None` if missing/expired.
-pub fn load_cached_payload(
-    namespace: &str,
-    account_id: &str,
-    role_name: &str,
-    scope: AccessScope,
-) -> Result<Option<String>> {
-    let Some(cached) = read(namespace, account_id, role_name, scope)? else {
-        return Ok(None);
-    };
-    if is_expired(cached.expiration_ms)? {
-        return Ok(None);
+impl StoredCredentials {
+    /// Read an entry file's envelope, or `None` if it isn't one.
+    pub(crate) fn read(path: &Path) -> Option<Self> {
+        let data = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+}
+
+/// A cached credential with its payload opened.
+#[derive(Debug, Clone)]
+pub struct CachedCredentials {
+    pub expiration_ms: u64,
+    pub issued_at_ms: Option<u64>,
+    pub payload: String,
+}
+
+impl CachedCredentials {
+    /// Whether the credentials have used up `fraction` of their lifetime, so they should be
+    /// re-minted before they get close to `min_lifetime`. Unknown issue times never qualify.
+    pub fn past_refresh_point(&self, fraction: f64, now_ms: u64) -> bool {
+        let Some(issued_at_ms) = self.issued_at_ms else {
+            return false;
+        };
+        let lifetime_ms = self.expiration_ms.saturating_sub(issued_at_ms) as f64;
+        now_ms >= issued_at_ms + (lifetime_ms * fraction) as u64
+    }
+}
+
+/// The credentials cache of one provider namespace, through the configured backend.
+pub struct CredentialsCache {
+    namespace: String,
+    backend: Box<dyn CacheBackend>,
+}
+
+impl CredentialsCache {
+    pub fn open(kind: CredentialCacheBackend, namespace: String) -> Result<Self> {
+        let backend: Box<dyn CacheBackend> = match kind {
+            CredentialCacheBackend::File => Box::new(PlainBackend),
+            CredentialCacheBackend::Keyring => Box::new(KeyringBackend::load()?),
+        };
+        Ok(Self::new(namespace, backend))
+    }
+
+    fn new(namespace: String, backend: Box<dyn CacheBackend>) -> Self {
+        Self { namespace, backend }
+    }
+
+    /// Load the cached credentials for a target, or `None` if missing, written by another
+    /// backend, or with less than `min_lifetime` left.
+    ///
+    /// `region` is the target's region override; `None` keys the provider-default region.
+    pub fn load(
+        &self,
+        account_id: &str,
+        role_name: &str,
+        scope: AccessScope,
+        region: Option<&str>,
+        min_lifetime: Duration,
+    ) -> Result<Option<CachedCredentials>> {
+        let Some(stored) = read(&self.namespace, account_id, role_name, scope, region)? else {
+            return Ok(None);
+        };
+        if expires_within(stored.expiration_ms, min_lifetime)
+            || stored.encryption.as_deref() != self.backend.encryption()
+        {
+            return Ok(None);
+        }
+        let payload = match self.backend.open(&stored.payload) {
+            Ok(payload) => payload,
+            Err(err) => {
+                debug!(error = %err, "ignoring unreadable cached credentials");
+                return Ok(None);
+            }
+        };
+        Ok(Some(CachedCredentials {
+            expiration_ms: stored.expiration_ms,
+            issued_at_ms: stored.issued_at_ms,
+            payload,
+        }))
+    }
+
+    pub fn save(
+        &self,
+        account_id: &str,
+        role_name: &str,
+        scope: AccessScope,
+        region: Option<&str>,
+        expiration_ms: u64,
+        payload: &str,
+    ) -> Result<()> {
+        let path = cache_path(&self.namespace, account_id, role_name, scope, region)?;
+        if let Some(parent) = path.parent() {
+            create_private_dir(parent).map_err(|_| Error::MissingCache)?;
+        }
+        let stored = StoredCredentials {
+            key: Some(EntryKey {
+                namespace: self.namespace.clone(),
+                account_id: account_id.to_string(),
+                role_name: role_name.to_string(),
+                scope: scope.cache_tag().to_string(),
+                region: region.map(str::to_string),
+            }),
+            expiration_ms,
+            issued_at_ms: Some(now_ms()),
+            encryption: self.backend.encryption().map(str::to_string),
+            payload: self.backend.seal(payload)?,
+        };
+        let data =
+            serde_json::to_string(&stored).map_err(|_| Error::CacheParse { path: path.clone() })?;
+        write_private(&path, data.as_bytes()).map_err(|_| Error::CacheParse { path })?;
+        Ok(())
     }
-    Ok(Some(cached.payload))
 }
 
+/// Whether a target has cached credentials that outlast `min_lifetime`, judged by the envelope
+/// alone (no key needed).
 pub fn cached_credentials_status(
     namespace: &str,
     account_id: &str,
     role_name: &str,
     scope: AccessScope,
+    region: Option<&str>,
+    min_lifetime: Duration,
 ) -> Result<CachedCredentialsStatus> {
-    let Some(cached) = read(namespace, account_id, role_name, scope)? else {
+    let Some(stored) = read(namespace, account_id, role_name, scope, region)? else {
         return Ok(CachedCredentialsStatus::Missing);
     };
-    if is_expired(cached.expiration_ms)? {
+    if expires_within(stored.expiration_ms, min_lifetime) {
         Ok(CachedCredentialsStatus::Expired)
     } else {
         Ok(CachedCredentialsStatus::Valid)
     }
 }
 
-pub fn save_cached_payload(
-    namespace: &str,
-    account_id: &str,
-    role_name: &str,
-    scope: AccessScope,
-    expiration_ms: u64,
-    payload: &str,
-) -> Result<()> {
-    let path = cache_path(namespace, account_id, role_name, scope)?;
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|_| Error::MissingCache)?;
-    }
-    let cached = CachedCredentials {
-        expiration_ms,
-        payload: payload.to_string(),
-    };
-    let data =
-        serde_json::to_string(&cached).map_err(|_| Error::CacheParse { path: path.clone() })?;
-    fs::write(&path, data).map_err(|_| Error::CacheParse { path })?;
-    Ok(())
-}
-
 fn read(
     namespace: &str,
     account_id: &str,
     role_name: &str,
     scope: AccessScope,
-) -> Result<Option<CachedCredentials>> {
-    let path = cache_path(namespace, account_id, role_name, scope)?;
+    region: Option<&str>,
+) -> Result<Option<StoredCredentials>> {
+    let path = cache_path(namespace, account_id, role_name, scope, region)?;
     if !path.exists() {
         return Ok(None);
     }
-    let data = match fs::read_to_string(&path) {
-        Ok(data) => data,
-        Err(_) => return Ok(None),
-    };
-    Ok(serde_json::from_str(&data).ok())
+    Ok(StoredCredentials::read(&path))
 }
 
 fn cache_path(
@@ -102,9 +310,12 @@ fn cache_path(
     account_id: &str,
     role_name: &str,
     scope: AccessScope,
+    region: Option<&str>,
 ) -> Result<PathBuf> {
     let cache_dir = roleman_cache_dir()?;
-    Ok(cache_dir.join(cache_filename(namespace, account_id, role_name, scope)))
+    Ok(cache_dir.join(cache_filename(
+        namespace, account_id, role_name, scope, region,
+    )))
 }
 
 fn cache_filename(
@@ -112,30 +323,64 @@ fn cache_filename(
     account_id: &str,
     role_name: &str,
     scope: AccessScope,
+    region: Option<&str>,
 ) -> String {
     let mut hasher = Sha1::new();
     hasher.update(namespace.as_bytes());
     hasher.update(account_id.as_bytes());
     hasher.update(role_name.as_bytes());
     hasher.update(scope.cache_tag().as_bytes());
+    // Only overrides feed the key, so entries for the default region keep their old names.
+    if let Some(region) = region {
+        hasher.update(b"region:");
+        hasher.update(region.as_bytes());
+    }
     let digest = hasher.finalize();
-    format!("creds-{:x}.json", digest)
+    format!("{FILE_PREFIX}{digest:x}.json")
 }
 
-fn is_expired(expiration_ms: u64) -> Result<bool> {
-    let now_ms = SystemTime::now()
+fn expires_within(expiration_ms: u64, min_lifetime: Duration) -> bool {
+    now_ms() + min_lifetime.as_millis() as u64 >= expiration_ms
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
-        .as_millis() as u64;
-    let safety_ms = EXPIRY_SAFETY_SECS * 1000;
-    Ok(now_ms + safety_ms >= expiration_ms)
+        .as_millis() as u64
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes)
+        .map_err(|err| Error::CredentialCache(format!("failed to generate random bytes: {err}")))?;
+    Ok(bytes)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::DEFAULT_MIN_CREDENTIAL_LIFETIME;
     use tempfile::TempDir;
 
+    fn plain() -> CredentialsCache {
+        CredentialsCache::new("work".to_string(), Box::new(PlainBackend))
+    }
+
     fn current_time_ms() -> u64 {
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -152,16 +397,26 @@ mod tests {
             std::env::set_var("XDG_CACHE_HOME", temp.path());
         }
 
-        save_cached_payload(
-            "work",
-            "1234",
-            "Admin",
-            AccessScope::Full,
-            current_time_ms() + 120_000,
-            "{\"token\":\"abc\"}",
-        )
-        .unwrap();
-        let loaded = load_cached_payload("work", "1234", "Admin", AccessScope::Full).unwrap();
+        plain()
+            .save(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                current_time_ms() + 120_000,
+                "{\"token\":\"abc\"}",
+            )
+            .unwrap();
+        let loaded = plain()
+            .load(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                DEFAULT_MIN_CREDENTIAL_LIFETIME,
+            )
+            .unwrap()
+            .map(|cached| cached.payload);
         assert_eq!(loaded.as_deref(), Some("{\"token\":\"abc\"}"));
 
         unsafe {
@@ -182,17 +437,27 @@ mod tests {
             std::env::set_var("XDG_CACHE_HOME", temp.path());
         }
 
-        save_cached_payload(
-            "work",
-            "1234",
-            "Admin",
-            AccessScope::Full,
-            current_time_ms() + 120_000,
-            "full",
-        )
-        .unwrap();
+        plain()
+            .save(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                current_time_ms() + 120_000,
+                "full",
+            )
+            .unwrap();
         // ReadOnly scope must not see the Full-scope entry.
-        let readonly = load_cached_payload("work", "1234", "Admin", AccessScope::ReadOnly).unwrap();
+        let readonly = plain()
+            .load(
+                "1234",
+                "Admin",
+                AccessScope::ReadOnly,
+                None,
+                DEFAULT_MIN_CREDENTIAL_LIFETIME,
+            )
+            .unwrap()
+            .map(|cached| cached.payload);
         assert!(readonly.is_none());
 
         unsafe {
@@ -204,6 +469,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn regions_cache_separately() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("XDG_CACHE_HOME").ok();
+        unsafe {
+            std::env::set_var("XDG_CACHE_HOME", temp.path());
+        }
+
+        plain()
+            .save(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                Some("eu-west-1"),
+                current_time_ms() + 120_000,
+                "eu",
+            )
+            .unwrap();
+        let default_region = plain()
+            .load(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                DEFAULT_MIN_CREDENTIAL_LIFETIME,
+            )
+            .unwrap()
+            .map(|cached| cached.payload);
+        assert!(default_region.is_none());
+        let eu = plain()
+            .load(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                Some("eu-west-1"),
+                DEFAULT_MIN_CREDENTIAL_LIFETIME,
+            )
+            .unwrap()
+            .map(|cached| cached.payload);
+        assert_eq!(eu.as_deref(), Some("eu"));
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("XDG_CACHE_HOME", value);
+            } else {
+                std::env::remove_var("XDG_CACHE_HOME");
+            }
+        }
+    }
+
     #[test]
     fn expired_payload_is_ignored() {
         let _lock = crate::test_support::lock_env();
@@ -213,18 +529,36 @@ mod tests {
             std::env::set_var("XDG_CACHE_HOME", temp.path());
         }
 
-        save_cached_payload(
+        plain()
+            .save(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                current_time_ms().saturating_sub(120_000),
+                "stale",
+            )
+            .unwrap();
+        let loaded = plain()
+            .load(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                DEFAULT_MIN_CREDENTIAL_LIFETIME,
+            )
+            .unwrap()
+            .map(|cached| cached.payload);
+        assert!(loaded.is_none());
+        let status = cached_credentials_status(
             "work",
             "1234",
             "Admin",
             AccessScope::Full,
-            current_time_ms().saturating_sub(120_000),
-            "stale",
+            None,
+            DEFAULT_MIN_CREDENTIAL_LIFETIME,
         )
         .unwrap();
-        let loaded = load_cached_payload("work", "1234", "Admin", AccessScope::Full).unwrap();
-        assert!(loaded.is_none());
-        let status = cached_credentials_status("work", "1234", "Admin", AccessScope::Full).unwrap();
         assert_eq!(status, CachedCredentialsStatus::Expired);
 
         unsafe {
@@ -235,4 +569,204 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn honors_min_lifetime_and_records_issue_time() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("XDG_CACHE_HOME").ok();
+        unsafe {
+            std::env::set_var("XDG_CACHE_HOME", temp.path());
+        }
+
+        let before = current_time_ms();
+        plain()
+            .save(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                before + 120_000,
+                "short",
+            )
+            .unwrap();
+        let load = |min_lifetime| {
+            plain()
+                .load("1234", "Admin", AccessScope::Full, None, min_lifetime)
+                .unwrap()
+        };
+        let cached = load(DEFAULT_MIN_CREDENTIAL_LIFETIME).unwrap();
+        assert!(cached.issued_at_ms.is_some_and(|issued| issued >= before));
+        // Two minutes left is not enough when five are required.
+        assert!(load(Duration::from_secs(5 * 60)).is_none());
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("XDG_CACHE_HOME", value);
+            } else {
+                std::env::remove_var("XDG_CACHE_HOME");
+            }
+        }
+    }
+
+    #[test]
+    fn refresh_point_is_a_fraction_of_the_lifetime() {
+        let cached = CachedCredentials {
+            expiration_ms: 1_000_000 + 3_600_000,
+            issued_at_ms: Some(1_000_000),
+            payload: String::new(),
+        };
+        assert!(!cached.past_refresh_point(0.75, 1_000_000 + 2_600_000));
+        assert!(cached.past_refresh_point(0.75, 1_000_000 + 2_700_000));
+        assert!(!cached.past_refresh_point(1.0, 1_000_000 + 3_599_000));
+
+        // Entries written before issue times were recorded are never refreshed early.
+        let legacy = CachedCredentials {
+            issued_at_ms: None,
+            ..cached
+        };
+        assert!(!legacy.past_refresh_point(0.1, 1_000_000 + 3_000_000));
+    }
+
+    #[test]
+    fn keyring_backend_encrypts_payloads() {
+        let backend = KeyringBackend::with_key([7; 32]);
+        let sealed = backend.seal("{\"secret\":\"abc\"}").unwrap();
+        assert!(!sealed.contains("secret"));
+        assert_eq!(backend.open(&sealed).unwrap(), "{\"secret\":\"abc\"}");
+        // Nonces are random, so the same payload never seals the same way twice.
+        assert_ne!(backend.seal("{\"secret\":\"abc\"}").unwrap(), sealed);
+
+        let other_key = KeyringBackend::with_key([8; 32]);
+        assert!(other_key.open(&sealed).is_err());
+        assert!(backend.open("not hex").is_err());
+    }
+
+    #[test]
+    fn entries_from_another_backend_are_misses() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("XDG_CACHE_HOME").ok();
+        unsafe {
+            std::env::set_var("XDG_CACHE_HOME", temp.path());
+        }
+
+        let encrypted = CredentialsCache::new(
+            "work".to_string(),
+            Box::new(KeyringBackend::with_key([7; 32])),
+        );
+        let expiration = current_time_ms() + 3_600_000;
+        encrypted
+            .save(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                expiration,
+                "sealed",
+            )
+            .unwrap();
+        let load = |cache: &CredentialsCache| {
+            cache
+                .load(
+                    "1234",
+                    "Admin",
+                    AccessScope::Full,
+                    None,
+                    DEFAULT_MIN_CREDENTIAL_LIFETIME,
+                )
+                .unwrap()
+                .map(|cached| cached.payload)
+        };
+        assert_eq!(load(&encrypted).as_deref(), Some("sealed"));
+        assert!(load(&plain()).is_none());
+        // The envelope stays readable without the key.
+        assert_eq!(
+            cached_credentials_status(
+                "work",
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                DEFAULT_MIN_CREDENTIAL_LIFETIME,
+            )
+            .unwrap(),
+            CachedCredentialsStatus::Valid
+        );
+        // An hour left is stale for identities that want two.
+        assert_eq!(
+            cached_credentials_status(
+                "work",
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                Duration::from_secs(2 * 3600),
+            )
+            .unwrap(),
+            CachedCredentialsStatus::Expired
+        );
+
+        // Plaintext entries written by older versions still load with the file backend.
+        let path = cache_path("work", "1234", "Admin", AccessScope::Full, None).unwrap();
+        fs::write(
+            &path,
+            format!(r#"{{"expiration_ms":{expiration},"payload":"legacy"}}"#),
+        )
+        .unwrap();
+        assert_eq!(load(&plain()).as_deref(), Some("legacy"));
+        assert!(load(&encrypted).is_none());
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("XDG_CACHE_HOME", value);
+            } else {
+                std::env::remove_var("XDG_CACHE_HOME");
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cache_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("XDG_CACHE_HOME").ok();
+        unsafe {
+            std::env::set_var("XDG_CACHE_HOME", temp.path());
+        }
+
+        // An older version left a world-readable directory and entry behind.
+        let dir = roleman_cache_dir().unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        let path = cache_path("work", "1234", "Admin", AccessScope::Full, None).unwrap();
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        plain()
+            .save(
+                "1234",
+                "Admin",
+                AccessScope::Full,
+                None,
+                current_time_ms() + 120_000,
+                "secret",
+            )
+            .unwrap();
+        let mode =
+            |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&path), 0o600);
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("XDG_CACHE_HOME", value);
+            } else {
+                std::env::remove_var("XDG_CACHE_HOME");
+            }
+        }
+    }
 }
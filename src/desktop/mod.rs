@@ -13,6 +13,9 @@ mod windows;
 
 use crate::error::{Error, Result};
 
+/// Remembered OS automation permissions, in the roleman cache directory (macOS only).
+pub(crate) const PERMISSIONS_CACHE_FILE: &str = "desktop-permissions.json";
+
 #[derive(Debug, Clone, Copy, Default)]
 pub(crate) struct PermissionRequirements {
     pub close_auth_browser_tab: bool,
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use crate::error::{Error, Result};
 use crate::roles_cache::roleman_cache_dir;
 
-const PERMISSIONS_CACHE_FILE: &str = "desktop-permissions.json";
+use super::PERMISSIONS_CACHE_FILE;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 struct DesktopPermissions {
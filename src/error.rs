@@ -8,14 +8,24 @@ pub enum Error {
     ExpiredCache,
     #[error("failed to parse cache file: {path}")]
     CacheParse { path: PathBuf },
+    #[error("credential cache error: {0}")]
+    CredentialCache(String),
+    #[error("{0}")]
+    Cache(String),
     #[error("aws sdk error: {0}")]
     AwsSdk(String),
+    #[error("gcp api error: {0}")]
+    Gcp(String),
     #[error("failed to drop permissions: {0}")]
     PermissionDrop(String),
     #[error("{0}")]
     NeedsResourceCreation(String),
+    #[error("{0}")]
+    ConfirmationRequired(String),
     #[error("tui error: {0}")]
     Tui(String),
+    #[error("{0}")]
+    RoleSelection(String),
     #[error("no role selection was made")]
     NoSelection,
     #[error("HOME is not set")]
@@ -30,6 +40,12 @@ pub enum Error {
     OpenBrowser(String),
     #[error("config error: {0}")]
     Config(String),
+    #[error("failed to run command: {0}")]
+    Command(String),
+    #[error("command exited with status {0}")]
+    CommandExit(i32),
+    #[error("refusing to export environment variable: {0}")]
+    InvalidEnvVar(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
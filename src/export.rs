@@ -0,0 +1,265 @@
+//! Render exported environment variables for tools other than a POSIX shell.
+//!
+//! `set` prints export commands for the hook's shell by default. `--format` swaps in one
+//! of the renderers below so the same credentials can feed a dotenv file, a JSON consumer or a
+//! non-POSIX shell. Every renderer quotes values for its own syntax; none of them relies on the
+//! values being free of quotes, whitespace or newlines.
+
+use crate::error::Result;
+use crate::provider::EnvVar;
+use crate::shell::{self, check_env_var, is_plain_word, posix_quote_always};
+
+/// How `set` renders the exported variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// Export commands for the hook's shell; POSIX `export NAME=value` outside the hook.
+    #[default]
+    Shell,
+    /// `NAME=value` lines for dotenv loaders and compose `env_file`. Not for
+    /// `docker --env-file`, which keeps quotes and escapes as part of the value.
+    Dotenv,
+    /// A single JSON object mapping names to values.
+    Json,
+    /// PowerShell `$env:NAME = 'value'` assignments.
+    Powershell,
+    /// A nushell `load-env { ... }` call.
+    Nushell,
+    /// fish `set -gx NAME 'value'` commands.
+    Fish,
+    /// An `.envrc` snippet for direnv.
+    Direnv,
+}
+
+impl ExportFormat {
+    /// Whether the output is meant for the shell hook, which also needs roleman's own
+    /// `_ROLEMAN_*` renewal variables and the hook env file.
+    pub fn is_hook_format(self) -> bool {
+        matches!(self, Self::Shell)
+    }
+}
+
+/// Render `vars` in `format`, without a trailing newline.
+///
+/// The shell format follows the hook's shell (see [`shell::hook_shell`]), so `--print` under
+/// the fish hook prints fish commands.
+pub fn render(format: ExportFormat, vars: &[EnvVar]) -> Result<String> {
+    match format {
+        ExportFormat::Shell => shell::hook_shell().render_exports(vars),
+        ExportFormat::Fish => shell::fish().render_exports(vars),
+        ExportFormat::Dotenv => lines(vars, |var| {
+            format!("{}={}", var.name, dotenv_quote(&var.value))
+        }),
+        ExportFormat::Json => {
+            vars.iter().try_for_each(check_env_var)?;
+            Ok(json_object(vars))
+        }
+        ExportFormat::Powershell => lines(vars, |var| {
+            format!("$env:{} = {}", var.name, powershell_quote(&var.value))
+        }),
+        ExportFormat::Nushell => {
+            vars.iter().try_for_each(check_env_var)?;
+            Ok(nushell_load_env(vars))
+        }
+        ExportFormat::Direnv => Ok(format!(
+            "# Written by roleman; these credentials expire, so re-run `roleman set --format direnv`.\n{}",
+            lines(vars, |var| {
+                format!("export {}={}", var.name, posix_quote_always(&var.value))
+            })?
+        )),
+    }
+}
+
+/// One line per variable, after checking every name and value.
+fn lines(vars: &[EnvVar], line: impl Fn(&EnvVar) -> String) -> Result<String> {
+    let lines = vars
+        .iter()
+        .map(|var| check_env_var(var).map(|()| line(var)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(lines.join("\n"))
+}
+
+/// Single-quote for PowerShell, doubling any quote inside. PowerShell also closes a
+/// single-quoted string on the typographic quotes U+2018 to U+201B, so those are doubled too.
+pub(crate) fn powershell_quote(value: &str) -> String {
+    let mut out = String::from("'");
+    for ch in value.chars() {
+        if matches!(ch, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            out.push(ch);
+        }
+        out.push(ch);
+    }
+    out.push('\'');
+    out
+}
+
+/// Leave plain values bare; otherwise double-quote with the escapes dotenv loaders
+/// understand.
+fn dotenv_quote(value: &str) -> String {
+    if is_plain_word(value) {
+        return value.to_string();
+    }
+    let mut out = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '\\' => out.push_str(r"\\"),
+            '"' => out.push_str("\\\""),
+            '$' => out.push_str(r"\$"),
+            '\n' => out.push_str(r"\n"),
+            '\r' => out.push_str(r"\r"),
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A pretty-printed JSON object that keeps the variables in export order.
+fn json_object(vars: &[EnvVar]) -> String {
+    if vars.is_empty() {
+        return "{}".to_string();
+    }
+    let entries = vars
+        .iter()
+        .map(|var| {
+            format!(
+                "  {}: {}",
+                serde_json::Value::from(var.name.as_str()),
+                serde_json::Value::from(var.value.as_str())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    format!("{{\n{entries}\n}}")
+}
+
+/// A nushell `load-env` call taking a record of double-quoted strings.
+fn nushell_load_env(vars: &[EnvVar]) -> String {
+    let entries = vars
+        .iter()
+        .map(|var| {
+            format!(
+                "    {}: {}",
+                nushell_quote(&var.name),
+                nushell_quote(&var.value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("load-env {{\n{entries}\n}}")
+}
+
+/// Double-quote for nushell, escaping everything its string parser treats specially.
+pub(crate) fn nushell_quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '\\' => out.push_str(r"\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str(r"\n"),
+            '\r' => out.push_str(r"\r"),
+            '\t' => out.push_str(r"\t"),
+            ch if ch.is_control() => out.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> Vec<EnvVar> {
+        vec![
+            EnvVar::new("AWS_ACCESS_KEY_ID", "ASIAEXAMPLE"),
+            EnvVar::new("AWS_SESSION_TOKEN", "IQo/b3JpZ2lu+ZWM="),
+            EnvVar::new("AWS_PROFILE", "it's \"prod\" $HOME\\x\nnext"),
+        ]
+    }
+
+    #[test]
+    fn dotenv_leaves_plain_values_bare() {
+        assert_eq!(
+            render(ExportFormat::Dotenv, &vars()).unwrap(),
+            "AWS_ACCESS_KEY_ID=ASIAEXAMPLE\n\
+             AWS_SESSION_TOKEN=IQo/b3JpZ2lu+ZWM=\n\
+             AWS_PROFILE=\"it's \\\"prod\\\" \\$HOME\\\\x\\nnext\""
+        );
+    }
+
+    #[test]
+    fn json_roundtrips_in_order() {
+        let rendered = render(ExportFormat::Json, &vars()).unwrap();
+        let parsed: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&rendered).unwrap();
+        for var in vars() {
+            assert_eq!(parsed[&var.name], var.value.as_str());
+        }
+        let names: Vec<_> = rendered
+            .lines()
+            .filter_map(|line| line.trim().split_once(':').map(|(name, _)| name))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "\"AWS_ACCESS_KEY_ID\"",
+                "\"AWS_SESSION_TOKEN\"",
+                "\"AWS_PROFILE\""
+            ]
+        );
+        assert_eq!(render(ExportFormat::Json, &[]).unwrap(), "{}");
+    }
+
+    #[test]
+    fn powershell_doubles_single_quotes() {
+        assert_eq!(
+            render(ExportFormat::Powershell, &vars()[2..]).unwrap(),
+            "$env:AWS_PROFILE = 'it''s \"prod\" $HOME\\x\nnext'"
+        );
+        assert_eq!(powershell_quote("it’s ‘a’ ‚b‛"), "'it’’s ‘‘a’’ ‚‚b‛‛'");
+    }
+
+    #[test]
+    fn fish_escapes_backslashes_and_quotes() {
+        assert_eq!(
+            render(ExportFormat::Fish, &vars()[2..]).unwrap(),
+            "set -gx AWS_PROFILE 'it\\'s \"prod\" $HOME\\\\x\nnext'"
+        );
+    }
+
+    #[test]
+    fn nushell_builds_a_record() {
+        assert_eq!(
+            render(ExportFormat::Nushell, &vars()[..1]).unwrap(),
+            "load-env {\n    \"AWS_ACCESS_KEY_ID\": \"ASIAEXAMPLE\"\n}"
+        );
+        assert_eq!(
+            nushell_quote("it's \"prod\"\\\n\u{1b}"),
+            "\"it's \\\"prod\\\"\\\\\\n\\u{1b}\""
+        );
+    }
+
+    #[test]
+    fn direnv_always_quotes() {
+        let rendered = render(ExportFormat::Direnv, &vars()[..1]).unwrap();
+        assert!(rendered.starts_with("# Written by roleman"));
+        assert!(rendered.ends_with("\nexport AWS_ACCESS_KEY_ID='ASIAEXAMPLE'"));
+    }
+
+    #[test]
+    fn every_format_rejects_unsafe_names() {
+        let hostile = [EnvVar::new("AWS_PROFILE=x; rm -rf ~ #", "prod")];
+        for format in [
+            ExportFormat::Shell,
+            ExportFormat::Dotenv,
+            ExportFormat::Json,
+            ExportFormat::Powershell,
+            ExportFormat::Nushell,
+            ExportFormat::Fish,
+            ExportFormat::Direnv,
+        ] {
+            assert!(render(format, &hostile).is_err(), "{format:?}");
+        }
+    }
+}
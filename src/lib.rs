@@ -1,20 +1,37 @@
+pub mod cache;
 pub mod config;
 mod credentials_cache;
 mod desktop;
 mod error;
+pub mod export;
 pub mod history;
 mod model;
 pub mod provider;
+mod renew;
+mod role_filter;
 mod roles_cache;
+mod serve;
+pub mod shell;
+pub mod status;
+mod subshell;
 mod tui;
 pub mod ui;
+mod util;
 
+pub use crate::cache::{CacheEntry, CacheEntryDetails, CacheFilter, CacheKind};
 pub use crate::config::Config;
-use crate::config::{SelectorSortMode, SsoIdentity};
+use crate::config::{ProviderKind, SelectorSortMode, Shortcut, SsoIdentity};
+use crate::credentials_cache::CredentialsCache;
 pub use crate::error::{Error, Result};
-pub use crate::model::RoleChoice;
+pub use crate::export::ExportFormat;
+pub use crate::model::{GeneratedProfile, ListingFailure, RoleChoice, RoleListing};
 pub use crate::provider::AccessScope;
-use crate::provider::{CloudProvider, PostLoginActions, ProviderCredentials, ProviderSession};
+use crate::provider::{
+    CloudProvider, EnvVar, PostLoginActions, ProviderCredentials, ProviderSession,
+};
+pub use crate::role_filter::RoleFilter;
+pub use crate::status::ActiveStatus;
+pub use crate::util::format_duration;
 use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 use tracing::debug;
@@ -30,6 +47,11 @@ pub enum AppAction {
     Open,
     Login,
     List,
+    CredentialProcess,
+    Serve,
+    Exec,
+    Shell,
+    Renew,
 }
 
 #[derive(Debug, Default)]
@@ -46,11 +68,21 @@ pub struct AppOptions {
     pub account: Option<String>,
     pub show_all: bool,
     pub initial_query: Option<String>,
+    /// Pick the role matching these criteria instead of opening the selector.
+    pub role_filter: RoleFilter,
     pub selector_sort: Option<SelectorSortMode>,
     pub action: AppAction,
     pub scope: AccessScope,
     /// Skip the interactive confirmation before roleman creates a cloud resource.
     pub assume_yes: bool,
+    /// Loopback port for `serve` (0 or unset picks a free port).
+    pub serve_port: Option<u16>,
+    /// Region to export with the credentials, overriding any configured default.
+    pub region: Option<String>,
+    /// Program and arguments for `exec`.
+    pub command: Vec<String>,
+    /// How `set` renders the credentials; anything but the shell format skips the hook env file.
+    pub export_format: ExportFormat,
 }
 
 impl App {
@@ -64,11 +96,315 @@ impl App {
         let identity = resolve_identity(&self.options, &mut config, &config_path, config_exists)?;
         let provider = provider::for_identity(&identity)?;
         Ok(self
-            .prepare_visible_roles(provider.as_ref(), &identity)
+            .prepare_visible_roles(provider.as_ref(), &identity, false)
             .await?
             .visible)
     }
 
+    /// Mint (or reuse cached) credentials for one account/role without the selector and
+    /// render them for an external credential hook such as AWS `credential_process`.
+    ///
+    /// Everything except the returned document goes to stderr, since callers parse stdout.
+    pub async fn credential_process(&self, account_id: &str, role_name: &str) -> Result<String> {
+        let (mut config, config_path) = Config::load(self.options.config_path.as_deref())?;
+        let config_exists = config_path.exists();
+        let identity = resolve_identity(&self.options, &mut config, &config_path, config_exists)?;
+        let provider = provider::for_identity(&identity)?;
+        let scope = self.options.scope;
+        require_unattended_consent(
+            &identity,
+            account_id,
+            scope,
+            self.options.assume_yes,
+            "credential-process",
+        )?;
+        let region = self
+            .options
+            .region
+            .as_deref()
+            .or_else(|| identity.region_for(account_id, role_name));
+        let namespace = provider.cache_namespace();
+        let cache = CredentialsCache::open(config.credential_cache, namespace.clone())?;
+        let cache_role = identity.credentials_cache_role(account_id, role_name);
+
+        if !self.options.ignore_cache
+            && let Some(cached) = cache.load(
+                account_id,
+                &cache_role,
+                scope,
+                region,
+                config.min_credential_lifetime(&identity),
+            )?
+        {
+            tracing::debug!("using cached role credentials for credential_process");
+            return provider
+                .credentials_from_cache_json(&cached.payload)?
+                .credential_process_json();
+        }
+
+        let cached_roles = roles_cache::load_cached_roles_with_age(&namespace)?
+            .map(|(choices, _)| choices)
+            .unwrap_or_default();
+        let choice = RoleChoice {
+            account_id: account_id.to_string(),
+            account_name: cached_account_name(&cached_roles, account_id),
+            role_name: role_name.to_string(),
+        };
+        let post_login_actions = resolve_post_login_actions(&self.options, &config);
+        let session = provider
+            .ensure_session(self.options.ignore_cache, post_login_actions)
+            .await?;
+        let may_create =
+            config.auto_create_readonly_roles.unwrap_or(false) || self.options.assume_yes;
+        let creds = fetch_with_consent(
+            provider.as_ref(),
+            session.as_ref(),
+            &choice,
+            scope,
+            region,
+            may_create,
+        )
+        .await?;
+        cache.save(
+            account_id,
+            &cache_role,
+            scope,
+            region,
+            creds.expiration_ms(),
+            &creds.to_cache_json()?,
+        )?;
+        creds.credential_process_json()
+    }
+
+    /// Re-mint the credentials named by the shell hook's `_ROLEMAN_TARGET` shortly before they
+    /// expire, for the hook to source at the next prompt.
+    ///
+    /// Never opens the selector or a sign-in flow: when the provider session is gone (or the
+    /// shell switched to another profile since), renewal stops until the next `set`. Other
+    /// failures are retried a minute later.
+    pub async fn renew(&self) -> Result<()> {
+        let target = match std::env::var(renew::TARGET_VAR) {
+            Ok(value) if !value.is_empty() => renew::RenewTarget::parse(&value)?,
+            _ => return self.write_exports(&[renew::disable_var()]),
+        };
+        let vars = match self.renew_credentials(&target).await {
+            Ok(Some(vars)) => {
+                eprintln!(
+                    "{}",
+                    ui::success(&format!(
+                        "Renewed credentials for {}.",
+                        target.choice().label()
+                    ))
+                );
+                vars
+            }
+            Ok(None) => vec![renew::disable_var()],
+            Err(err) => {
+                eprintln!(
+                    "{}",
+                    ui::warn(&format!(
+                        "Failed to renew credentials for {}: {err}",
+                        target.choice().label()
+                    ))
+                );
+                vec![renew::retry_var(now_ms())]
+            }
+        };
+        self.write_exports(&vars)
+    }
+
+    /// Fresh exports for a renewal target, or `None` (after saying why) when renewal should stop.
+    async fn renew_credentials(&self, target: &renew::RenewTarget) -> Result<Option<Vec<EnvVar>>> {
+        let options = AppOptions {
+            account: Some(target.identity.clone()),
+            config_path: self.options.config_path.clone(),
+            action: AppAction::Renew,
+            ..AppOptions::default()
+        };
+        let (mut config, config_path) = Config::load(options.config_path.as_deref())?;
+        let identity = resolve_identity(&options, &mut config, &config_path, true)?;
+        let provider = provider::for_identity(&identity)?;
+        let choice = target.choice();
+        let region = target.region.as_deref();
+
+        if let Err(err) = require_unattended_consent(
+            &identity,
+            &choice.account_id,
+            target.scope,
+            self.options.assume_yes,
+            "the shell hook",
+        ) {
+            eprintln!(
+                "{}",
+                ui::warn(&format!(
+                    "Credentials for {} are about to expire: {err}. Run `roleman` to confirm again.",
+                    choice.label()
+                ))
+            );
+            return Ok(None);
+        }
+        let Some(session) = provider.resume_session().await? else {
+            eprintln!(
+                "{}",
+                ui::warn(&format!(
+                    "Credentials for {} are about to expire and the {} session has ended; run `roleman` to sign in again.",
+                    choice.label(),
+                    identity.name
+                ))
+            );
+            return Ok(None);
+        };
+        let binding = provider.ensure_profile(
+            session.as_ref(),
+            &choice,
+            target.scope,
+            region,
+            target.omit_role_name,
+        )?;
+        if identity.provider == ProviderKind::Aws
+            && let Ok(active) = std::env::var("AWS_PROFILE")
+            && active != binding.profile_name
+        {
+            debug!(%active, expected = %binding.profile_name, "AWS_PROFILE changed; not renewing");
+            return Ok(None);
+        }
+
+        let cache = CredentialsCache::open(config.credential_cache, provider.cache_namespace())?;
+        let now = now_ms();
+        let cached = match cache.load(
+            &choice.account_id,
+            &provider.cache_role_name(&choice),
+            target.scope,
+            region,
+            config.min_credential_lifetime(&identity),
+        )? {
+            Some(cached) => Some(provider.credentials_from_cache_json(&cached.payload)?),
+            None => None,
+        };
+        let creds = match cached.filter(|creds| renew::outlasts_margin(creds.expiration_ms(), now))
+        {
+            Some(creds) => creds,
+            None => {
+                let may_create = config.auto_create_readonly_roles.unwrap_or(false);
+                fetch_and_cache(
+                    provider.as_ref(),
+                    session.as_ref(),
+                    &cache,
+                    &choice,
+                    target.scope,
+                    region,
+                    may_create,
+                )
+                .await?
+            }
+        };
+        update_shared_credentials(&identity, creds.as_ref(), &binding.profile_name)?;
+        let mut vars = creds.env_vars(&binding);
+        vars.extend(renew::renew_vars(target, creds.expiration_ms(), now));
+        Ok(Some(vars))
+    }
+
+    /// Re-mint a target's cached credentials ahead of time; runs detached, started by `set`
+    /// when it exported credentials past `credential_refresh_fraction` of their lifetime.
+    ///
+    /// Like `renew`, never prompts or signs in: without a resumable session it does nothing.
+    pub async fn refresh_cached_credentials(&self, encoded_target: &str) -> Result<()> {
+        let target = renew::RenewTarget::parse(encoded_target)?;
+        let options = AppOptions {
+            account: Some(target.identity.clone()),
+            config_path: self.options.config_path.clone(),
+            action: AppAction::Renew,
+            ..AppOptions::default()
+        };
+        let (mut config, config_path) = Config::load(options.config_path.as_deref())?;
+        let identity = resolve_identity(&options, &mut config, &config_path, true)?;
+        let provider = provider::for_identity(&identity)?;
+        let choice = target.choice();
+        let region = target.region.as_deref();
+
+        // Several `set`s in a row each start a refresh; only the first one has work to do.
+        let cache = CredentialsCache::open(config.credential_cache, provider.cache_namespace())?;
+        let cached = cache.load(
+            &choice.account_id,
+            &provider.cache_role_name(&choice),
+            target.scope,
+            region,
+            config.min_credential_lifetime(&identity),
+        )?;
+        if cached.is_some_and(|cached| {
+            !cached.past_refresh_point(config.credential_refresh_fraction(), now_ms())
+        }) {
+            debug!("cached credentials already refreshed");
+            return Ok(());
+        }
+        let Some(session) = provider.resume_session().await? else {
+            debug!("no resumable session; skipping background refresh");
+            return Ok(());
+        };
+        let may_create = config.auto_create_readonly_roles.unwrap_or(false);
+        fetch_and_cache(
+            provider.as_ref(),
+            session.as_ref(),
+            &cache,
+            &choice,
+            target.scope,
+            region,
+            may_create,
+        )
+        .await?;
+        debug!(target = %choice.label(), "refreshed cached credentials");
+        Ok(())
+    }
+
+    /// Write export lines to the hook env file when there is one, else print them.
+    fn write_exports(&self, vars: &[EnvVar]) -> Result<()> {
+        let lines = export::render(ExportFormat::Shell, vars)?;
+        match env_file_path(&self.options) {
+            Some(path) => write_env_file(&path, &lines),
+            None => {
+                println!("{lines}");
+                Ok(())
+            }
+        }
+    }
+
+    /// Report the role the current shell is using, from its environment and local caches.
+    ///
+    /// `include_session` also reports the remaining sign-in lifetime; the prompt skips that
+    /// read unless its template asks for it.
+    pub fn status(&self, include_session: bool) -> Result<ActiveStatus> {
+        let (config, _) = Config::load(self.options.config_path.as_deref())?;
+        Ok(status::collect(&config, include_session, now_ms()))
+    }
+
+    /// Describe the cache entries matching `filter`, without their secrets.
+    pub fn cache_entries(&self, filter: &CacheFilter) -> Result<Vec<CacheEntry>> {
+        let (config, _) = Config::load(self.options.config_path.as_deref())?;
+        cache::list(&config, filter, now_ms())
+    }
+
+    /// Details of the cache entry whose file name is (or starts with) `name`.
+    pub fn cache_entry(&self, name: &str) -> Result<CacheEntryDetails> {
+        let (config, _) = Config::load(self.options.config_path.as_deref())?;
+        cache::show(&config, name, now_ms())
+    }
+
+    /// Delete the cache entries matching `filter` and return them; `dry_run` only returns them.
+    pub fn clear_cache(&self, filter: &CacheFilter, dry_run: bool) -> Result<Vec<CacheEntry>> {
+        let (config, _) = Config::load(self.options.config_path.as_deref())?;
+        cache::clear(&config, filter, dry_run, now_ms())
+    }
+
+    /// Render the prompt segment for the current shell, from `template` or the configured one.
+    pub fn prompt(&self, template: Option<&str>) -> Result<String> {
+        let (config, _) = Config::load(self.options.config_path.as_deref())?;
+        let template = template
+            .or(config.prompt_template.as_deref())
+            .unwrap_or(status::DEFAULT_PROMPT_TEMPLATE);
+        let status = status::collect(&config, template.contains("{session_expires_in}"), now_ms());
+        Ok(status.render(template))
+    }
+
     /// Scan for roleman-created cloud resources and remove them.
     ///
     /// By default uses the ambient credentials (whatever is active in the shell) and operates
@@ -143,6 +479,112 @@ impl App {
         Ok(())
     }
 
+    /// Write a profile for every visible role, as `set` would when the role is selected, so
+    /// tools that enumerate profiles see all of them. Returns the profile names; with `dry_run`
+    /// nothing is written.
+    pub async fn sync_profiles(&self, dry_run: bool) -> Result<Vec<String>> {
+        let (mut config, config_path) = Config::load(self.options.config_path.as_deref())?;
+        let config_exists = config_path.exists();
+        let identity = resolve_identity(&self.options, &mut config, &config_path, config_exists)?;
+        let provider = provider::for_identity(&identity)?;
+        // Fail before listing roles when the provider keeps no profile config.
+        provider.generated_profiles()?;
+        let context = self
+            .prepare_visible_roles(provider.as_ref(), &identity, false)
+            .await?;
+        let visible = &context.visible;
+        visible
+            .iter()
+            .map(|choice| {
+                let omit_role_name = has_single_role_for_account(visible, &choice.account_id);
+                if dry_run {
+                    return Ok(provider.profile_name(choice, AccessScope::Full, omit_role_name));
+                }
+                let region = self
+                    .options
+                    .region
+                    .as_deref()
+                    .or_else(|| identity.region_for(&choice.account_id, &choice.role_name));
+                let binding = provider.ensure_profile(
+                    context.session.as_ref(),
+                    choice,
+                    AccessScope::Full,
+                    region,
+                    omit_role_name,
+                )?;
+                Ok(binding.profile_name)
+            })
+            .collect()
+    }
+
+    /// The profiles roleman generated for the identity, each flagged stale when no role in
+    /// the current role list maps to it.
+    pub async fn list_profiles(&self) -> Result<Vec<GeneratedProfile>> {
+        Ok(self.classify_profiles().await?.1)
+    }
+
+    /// Remove the generated profiles [`App::list_profiles`] flags stale and return their
+    /// names; with `dry_run` nothing is removed. Refuses while any account fails to list,
+    /// since its profiles can't be told apart from stale ones.
+    pub async fn prune_profiles(&self, dry_run: bool) -> Result<Vec<String>> {
+        let (provider, profiles, failures) = self.classify_profiles().await?;
+        require_complete_listing(&failures)?;
+        let stale = profiles
+            .into_iter()
+            .filter(|profile| profile.stale)
+            .map(|profile| profile.name)
+            .collect::<Vec<_>>();
+        if !dry_run && !stale.is_empty() {
+            provider.remove_generated_profiles(&stale)?;
+        }
+        Ok(stale)
+    }
+
+    /// The generated profiles flagged stale or current, plus the accounts that failed to list.
+    async fn classify_profiles(
+        &self,
+    ) -> Result<(
+        Box<dyn CloudProvider>,
+        Vec<GeneratedProfile>,
+        Vec<ListingFailure>,
+    )> {
+        let (mut config, config_path) = Config::load(self.options.config_path.as_deref())?;
+        let config_exists = config_path.exists();
+        let identity = resolve_identity(&self.options, &mut config, &config_path, config_exists)?;
+        let provider = provider::for_identity(&identity)?;
+        let generated = provider.generated_profiles()?;
+        let post_login_actions = resolve_post_login_actions(&self.options, &config);
+        let (_, listing, _) = fetch_choices_with_cache(
+            provider.as_ref(),
+            &identity,
+            self.options.ignore_cache,
+            false,
+            post_login_actions,
+        )
+        .await?;
+        let RoleListing {
+            mut choices,
+            failures,
+        } = listing;
+        add_chained_choices(&mut choices, &identity);
+        // An empty list (say, every account failed to list) would mark everything stale.
+        if choices.is_empty() {
+            return Err(Error::Config(format!(
+                "no roles are listed for `{}`, so stale profiles can't be told apart",
+                identity.name
+            )));
+        }
+        let current = current_profile_names(provider.as_ref(), &identity, choices);
+        let profiles = generated
+            .into_iter()
+            .map(|name| GeneratedProfile {
+                stale: !current.contains(&name),
+                name,
+            })
+            .collect();
+        Ok((provider, profiles, failures))
+    }
+
     async fn cleanup_roles_all(
         &self,
         provider: &dyn CloudProvider,
@@ -256,24 +698,86 @@ impl App {
             return Ok(());
         }
 
-        let context = self
-            .prepare_visible_roles(provider.as_ref(), &identity)
+        if let Some(shortcut) = requested_shortcut(&self.options, &identity) {
+            // Shortcuts name their target up front, so skip listing accounts entirely.
+            let session = provider
+                .ensure_session(self.options.ignore_cache, post_login_actions)
+                .await?;
+            let mut target =
+                shortcut_target(&identity, &provider.cache_namespace(), shortcut, scope)?;
+            if let Some(region) = &self.options.region {
+                target.region = Some(region.clone());
+            }
+            eprintln!(
+                "{}",
+                ui::info(&format!(
+                    "Using {} (shortcut {}).",
+                    target.choice.label(),
+                    shortcut.name
+                ))
+            );
+            if let Err(err) = history::record_selection(&identity.name, &target.choice) {
+                debug!(error = %err, "failed to record history selection");
+            }
+            return self
+                .act_on_target(
+                    provider.as_ref(),
+                    session.as_ref(),
+                    &identity,
+                    &target,
+                    &config,
+                    post_login_actions,
+                )
+                .await;
+        }
+
+        let interactive = self.options.role_filter.is_empty();
+        let mut context = self
+            .prepare_visible_roles(provider.as_ref(), &identity, interactive)
             .await?;
 
         let prompt = match self.options.action {
             AppAction::Set => "roleman> ",
             AppAction::Open => "roleman open> ",
+            AppAction::Serve => "roleman serve> ",
+            AppAction::Exec => "roleman exec> ",
+            AppAction::Shell => "roleman shell> ",
+            AppAction::Renew => unreachable!("renew is handled by App::renew"),
             AppAction::Login => unreachable!("login exits before role selection"),
             AppAction::List => unreachable!("list is handled by App::list_roles"),
+            AppAction::CredentialProcess => {
+                unreachable!("credential-process is handled by App::credential_process")
+            }
+        };
+        let selected = if interactive && context.stale {
+            let (selected, refreshed) = self
+                .select_while_refreshing(provider.as_ref(), &identity, &config, &context, prompt)
+                .await?;
+            if let Some(refreshed) = refreshed {
+                context.visible = refreshed;
+            }
+            selected
+        } else if interactive {
+            select_role_async(
+                prompt,
+                &context.visible,
+                provider.active_markers(
+                    &context.visible,
+                    scope,
+                    config.min_credential_lifetime(&identity),
+                ),
+                guarded_flags(&identity, &context.visible),
+                self.options.initial_query.as_deref(),
+                None,
+            )
+            .await?
+        } else {
+            Some(tui::TuiSelection {
+                choice: self.options.role_filter.resolve(&context.visible)?,
+                open_in_browser: false,
+                auto_selected: true,
+            })
         };
-        let markers = provider.active_markers(&context.visible, scope);
-        let selected = select_role_async(
-            prompt,
-            &context.visible,
-            markers,
-            self.options.initial_query.as_deref(),
-        )
-        .await?;
         if let Some(selection) = selected {
             if selection.auto_selected {
                 eprintln!(
@@ -297,149 +801,605 @@ impl App {
                 open_in_browser(&url)?;
                 return Ok(());
             }
-            match self.options.action {
-                AppAction::Set => {
-                    let namespace = provider.cache_namespace();
-                    let cached = if self.options.ignore_cache {
-                        None
-                    } else if let Some(json) = credentials_cache::load_cached_payload(
-                        &namespace,
-                        &choice.account_id,
-                        &choice.role_name,
-                        scope,
-                    )? {
-                        Some(provider.credentials_from_cache_json(&json)?)
-                    } else {
-                        None
-                    };
-                    let creds = if let Some(creds) = cached {
-                        tracing::debug!("using cached role credentials");
-                        eprintln!("{}", ui::info("Using cached role credentials."));
-                        creds
-                    } else {
-                        tracing::debug!("fetching role credentials");
-                        let may_create = config.auto_create_readonly_roles.unwrap_or(false)
-                            || self.options.assume_yes;
-                        let fresh = fetch_with_consent(
-                            provider.as_ref(),
-                            context.session.as_ref(),
-                            &choice,
-                            scope,
-                            may_create,
-                        )
-                        .await?;
-                        credentials_cache::save_cached_payload(
-                            &namespace,
-                            &choice.account_id,
-                            &choice.role_name,
-                            scope,
-                            fresh.expiration_ms(),
-                            &fresh.to_cache_json()?,
-                        )?;
-                        tracing::debug!("role credentials received");
-                        fresh
+            let target = Target {
+                omit_role_name: has_single_role_for_account(&context.visible, &choice.account_id),
+                region: self.options.region.clone().or_else(|| {
+                    identity
+                        .region_for(&choice.account_id, &choice.role_name)
+                        .map(str::to_string)
+                }),
+                choice,
+                scope,
+            };
+            self.act_on_target(
+                provider.as_ref(),
+                context.session.as_ref(),
+                &identity,
+                &target,
+                &config,
+                post_login_actions,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Carry out the requested action (export, open, serve) for a resolved target.
+    async fn act_on_target(
+        &self,
+        provider: &dyn CloudProvider,
+        session: &dyn ProviderSession,
+        identity: &SsoIdentity,
+        target: &Target,
+        config: &Config,
+        post_login_actions: PostLoginActions,
+    ) -> Result<()> {
+        let target = &self.confirm_guarded_target(provider, identity, target)?;
+        let choice = &target.choice;
+        match self.options.action {
+            AppAction::Set => {
+                let (creds, refresh_due) = self
+                    .load_or_fetch_credentials(provider, session, identity, target, config)
+                    .await?;
+                let binding = provider.ensure_profile(
+                    session,
+                    choice,
+                    target.scope,
+                    target.region.as_deref(),
+                    target.omit_role_name,
+                )?;
+                update_shared_credentials(identity, creds.as_ref(), &binding.profile_name)?;
+                let mut vars = creds.env_vars(&binding);
+                let format = self.options.export_format;
+                // Renewal looks the identity up by name, so ad-hoc `--sso-start-url` runs opt out.
+                if config.identities.iter().any(|i| i.name == identity.name) {
+                    let renew_target = renew::RenewTarget {
+                        identity: identity.name.clone(),
+                        account_id: choice.account_id.clone(),
+                        account_name: choice.account_name.clone(),
+                        role_name: choice.role_name.clone(),
+                        scope: target.scope,
+                        region: target.region.clone(),
+                        omit_role_name: target.omit_role_name,
                     };
-                    let omit_role_name =
-                        has_single_role_for_account(&context.visible, &choice.account_id);
-                    let binding = provider.ensure_profile(
-                        context.session.as_ref(),
-                        &choice,
-                        scope,
-                        omit_role_name,
-                    )?;
-                    let lines = provider::export_lines(&creds.env_vars(&binding));
-                    if let Some(path) = env_file_path(&self.options) {
-                        tracing::debug!(path = %path.display(), "writing env file");
-                        write_env_file(&path, &lines)?;
+                    // Only the hook acts on the renewal variables.
+                    if format.is_hook_format() {
+                        vars.extend(renew::renew_vars(
+                            &renew_target,
+                            creds.expiration_ms(),
+                            now_ms(),
+                        ));
                     }
-                    let should_print =
-                        self.options.print_env || env_file_path(&self.options).is_none();
-                    if should_print {
-                        println!("{}", lines);
+                    if refresh_due
+                        && let Err(err) = renew::spawn_background_refresh(
+                            &renew_target,
+                            self.options.config_path.as_deref(),
+                        )
+                    {
+                        debug!(error = %err, "failed to start background refresh");
                     }
                 }
-                AppAction::Open => {
-                    let url = provider.console_url(&choice);
-                    eprintln!("{}", ui::action(&format!("Opening {url}")));
-                    open_in_browser(&url)?;
+                let mut lines = export::render(format, &vars)?;
+                if format.is_hook_format()
+                    && let Some(unset) = unset_leftover_vars(&vars)
+                {
+                    lines = format!("{unset}\n{lines}");
+                }
+                // Other formats feed files and tools, never the hook that sourced the env file.
+                let env_file = env_file_path(&self.options).filter(|_| format.is_hook_format());
+                if let Some(path) = &env_file {
+                    tracing::debug!(path = %path.display(), "writing env file");
+                    write_env_file(path, &lines)?;
+                }
+                if self.options.print_env || env_file.is_none() {
+                    println!("{}", lines);
+                }
+            }
+            AppAction::Open => {
+                let url = provider.console_url(choice);
+                eprintln!("{}", ui::action(&format!("Opening {url}")));
+                open_in_browser(&url)?;
+            }
+            AppAction::Serve => {
+                self.serve(
+                    provider,
+                    session,
+                    identity,
+                    target,
+                    config,
+                    post_login_actions,
+                )
+                .await?;
+            }
+            AppAction::Exec | AppAction::Shell => {
+                let (creds, _) = self
+                    .load_or_fetch_credentials(provider, session, identity, target, config)
+                    .await?;
+                let binding = provider.ensure_profile(
+                    session,
+                    choice,
+                    target.scope,
+                    target.region.as_deref(),
+                    target.omit_role_name,
+                )?;
+                let mut vars = creds.env_vars(&binding);
+                vars.push(EnvVar::new(
+                    subshell::ACTIVE_MARKER_VAR,
+                    binding.profile_name.clone(),
+                ));
+                let command = if matches!(self.options.action, AppAction::Shell) {
+                    let shell = subshell::default_shell();
+                    eprintln!(
+                        "{}",
+                        ui::info(&format!(
+                            "Starting {shell} as {}; exit to drop the credentials.",
+                            choice.label()
+                        ))
+                    );
+                    vec![shell]
+                } else {
+                    self.options.command.clone()
+                };
+                let code = subshell::run(&command, &vars).await?;
+                if code != 0 {
+                    return Err(Error::CommandExit(code));
                 }
-                AppAction::Login => unreachable!("login exits before role selection"),
-                AppAction::List => unreachable!("list is handled by App::list_roles"),
             }
+            AppAction::Login => unreachable!("login exits before role selection"),
+            AppAction::List => unreachable!("list is handled by App::list_roles"),
+            AppAction::CredentialProcess => {
+                unreachable!("credential-process is handled by App::credential_process")
+            }
+            AppAction::Renew => unreachable!("renew is handled by App::renew"),
         }
-
         Ok(())
     }
 
+    /// Production guardrail for accounts marked `confirm = true`: exporting full-scope
+    /// credentials requires typing the account name back, and pressing Enter switches to
+    /// read-only instead. `--yes` skips the prompt; other non-interactive runs are refused.
+    fn confirm_guarded_target(
+        &self,
+        provider: &dyn CloudProvider,
+        identity: &SsoIdentity,
+        target: &Target,
+    ) -> Result<Target> {
+        let mut target = target.clone();
+        let exports_credentials = matches!(
+            self.options.action,
+            AppAction::Set | AppAction::Serve | AppAction::Exec | AppAction::Shell
+        );
+        if !exports_credentials
+            || target.scope != AccessScope::Full
+            || !identity.requires_confirmation(&target.choice.account_id)
+            || self.options.assume_yes
+        {
+            return Ok(target);
+        }
+        let label = target.choice.label();
+        if !std::io::stdin().is_terminal() {
+            return Err(Error::ConfirmationRequired(format!(
+                "{label} requires confirmation for full access. Re-run interactively, \
+                 pass --readonly, or pass --yes."
+            )));
+        }
+        let expected = if target.choice.account_name.trim().is_empty() {
+            target.choice.account_id.clone()
+        } else {
+            target.choice.account_name.clone()
+        };
+        let offer_readonly = provider.supports_scope(AccessScope::ReadOnly);
+        eprintln!("{}", ui::warn(&format!("{label} is a protected account.")));
+        let prompt = if offer_readonly {
+            format!("Type `{expected}` for full access, or press Enter for read-only: ")
+        } else {
+            format!("Type `{expected}` to continue with full access: ")
+        };
+        let input = prompt_input(&prompt)?;
+        match input.trim() {
+            typed if typed == expected => Ok(target),
+            "" if offer_readonly => {
+                eprintln!("{}", ui::info("Using read-only credentials."));
+                target.scope = AccessScope::ReadOnly;
+                Ok(target)
+            }
+            _ => Err(Error::ConfirmationRequired(format!(
+                "confirmation did not match `{expected}`; no credentials were exported"
+            ))),
+        }
+    }
+
+    /// Reuse cached credentials that outlast the identity's minimum lifetime, or mint and cache
+    /// fresh ones. The flag says whether reused credentials are due for a background refresh.
+    async fn load_or_fetch_credentials(
+        &self,
+        provider: &dyn CloudProvider,
+        session: &dyn ProviderSession,
+        identity: &SsoIdentity,
+        target: &Target,
+        config: &Config,
+    ) -> Result<(Box<dyn ProviderCredentials>, bool)> {
+        let (choice, scope, region) = (&target.choice, target.scope, target.region.as_deref());
+        let cache = CredentialsCache::open(config.credential_cache, provider.cache_namespace())?;
+        if !self.options.ignore_cache
+            && let Some(cached) = cache.load(
+                &choice.account_id,
+                &provider.cache_role_name(choice),
+                scope,
+                region,
+                config.min_credential_lifetime(identity),
+            )?
+        {
+            tracing::debug!("using cached role credentials");
+            eprintln!("{}", ui::info("Using cached role credentials."));
+            let refresh_due =
+                cached.past_refresh_point(config.credential_refresh_fraction(), now_ms());
+            return Ok((
+                provider.credentials_from_cache_json(&cached.payload)?,
+                refresh_due,
+            ));
+        }
+
+        tracing::debug!("fetching role credentials");
+        let may_create =
+            config.auto_create_readonly_roles.unwrap_or(false) || self.options.assume_yes;
+        let fresh =
+            fetch_with_consent(provider, session, choice, scope, region, may_create).await?;
+        cache.save(
+            &choice.account_id,
+            &provider.cache_role_name(choice),
+            scope,
+            region,
+            fresh.expiration_ms(),
+            &fresh.to_cache_json()?,
+        )?;
+        tracing::debug!("role credentials received");
+        Ok((fresh, false))
+    }
+
+    /// Serve a role's credentials on a loopback container-credentials endpoint until Ctrl-C,
+    /// re-minting them through the provider shortly before they expire.
+    async fn serve(
+        &self,
+        provider: &dyn CloudProvider,
+        session: &dyn ProviderSession,
+        identity: &SsoIdentity,
+        target: &Target,
+        config: &Config,
+        post_login_actions: PostLoginActions,
+    ) -> Result<()> {
+        let choice = &target.choice;
+        let (creds, _) = self
+            .load_or_fetch_credentials(provider, session, identity, target, config)
+            .await?;
+        let server = serve::CredentialServer::bind(
+            self.options.serve_port.unwrap_or(0),
+            creds.container_credentials_json()?,
+            creds.expiration_ms(),
+        )
+        .await?;
+        let updater = server.updater();
+        println!(
+            "{}",
+            export::render(ExportFormat::Shell, &server.env_vars())?
+        );
+        eprintln!(
+            "{}",
+            ui::info(&format!(
+                "Serving credentials for {} at {}. Press Ctrl-C to stop.",
+                choice.label(),
+                server.url()
+            ))
+        );
+
+        let refresh = async {
+            let mut expiration_ms = creds.expiration_ms();
+            loop {
+                tokio::time::sleep(serve::refresh_delay(expiration_ms, now_ms())).await;
+                match self
+                    .refresh_served_credentials(provider, target, config, post_login_actions)
+                    .await
+                {
+                    Ok(fresh) => {
+                        updater.set(fresh.container_credentials_json()?, fresh.expiration_ms());
+                        expiration_ms = fresh.expiration_ms();
+                        eprintln!("{}", ui::success("Refreshed served credentials"));
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "{}",
+                            ui::warn(&format!("Failed to refresh served credentials: {err}"))
+                        );
+                    }
+                }
+            }
+        };
+
+        tokio::select! {
+            result = server.run() => result,
+            result = refresh => result,
+            _ = tokio::signal::ctrl_c() => {
+                eprintln!("{}", ui::info("Stopped serving credentials."));
+                Ok(())
+            }
+        }
+    }
+
+    async fn refresh_served_credentials(
+        &self,
+        provider: &dyn CloudProvider,
+        target: &Target,
+        config: &Config,
+        post_login_actions: PostLoginActions,
+    ) -> Result<Box<dyn ProviderCredentials>> {
+        let (choice, scope, region) = (&target.choice, target.scope, target.region.as_deref());
+        let session = provider.ensure_session(false, post_login_actions).await?;
+        // The role (and any read-only role) already resolved once, so no consent prompt here.
+        let may_create =
+            config.auto_create_readonly_roles.unwrap_or(false) || self.options.assume_yes;
+        let cache = CredentialsCache::open(config.credential_cache, provider.cache_namespace())?;
+        fetch_and_cache(
+            provider,
+            session.as_ref(),
+            &cache,
+            choice,
+            scope,
+            region,
+            may_create,
+        )
+        .await
+    }
+
+    /// The roles to offer for `identity`. With `revalidate` (the interactive selector), a
+    /// cached list past its TTL is returned with `stale` set instead of waiting for a refresh.
     async fn prepare_visible_roles(
         &self,
         provider: &dyn CloudProvider,
         identity: &SsoIdentity,
+        revalidate: bool,
     ) -> Result<RoleSelectionContext> {
         let (config, _) = Config::load(self.options.config_path.as_deref())?;
         let refresh_seconds = self.options.refresh_seconds.or(config.refresh_seconds);
-        let selector_sort = self.options.selector_sort.unwrap_or(config.selector_sort);
         let post_login_actions = resolve_post_login_actions(&self.options, &config);
 
-        let (mut session, mut choices) =
-            fetch_choices_with_cache(provider, self.options.ignore_cache, post_login_actions)
-                .await?;
-
-        apply_visible_role_preferences(
-            &mut choices,
+        let (mut session, listing, mut stale) = fetch_choices_with_cache(
+            provider,
             identity,
-            self.options.show_all,
-            selector_sort,
-            self.options.initial_query.as_deref(),
-        );
+            self.options.ignore_cache,
+            revalidate,
+            post_login_actions,
+        )
+        .await?;
+        let mut visible = listing.choices;
+        self.apply_role_preferences(&mut visible, identity, &config);
 
-        let mut visible = choices;
         if visible.is_empty()
             && let Some(seconds) = refresh_seconds
         {
             loop {
                 tokio::time::sleep(std::time::Duration::from_secs(seconds)).await;
-                let (refreshed_session, mut refreshed) = fetch_choices_with_cache(
+                let (refreshed_session, listing, _) = fetch_choices_with_cache(
                     provider,
+                    identity,
                     self.options.ignore_cache,
+                    false,
                     post_login_actions,
                 )
                 .await?;
+                let mut refreshed = listing.choices;
                 session = refreshed_session;
-                apply_visible_role_preferences(
-                    &mut refreshed,
-                    identity,
-                    self.options.show_all,
-                    selector_sort,
-                    self.options.initial_query.as_deref(),
-                );
+                self.apply_role_preferences(&mut refreshed, identity, &config);
                 visible = refreshed;
+                stale = false;
                 if !visible.is_empty() {
                     break;
                 }
             }
         }
 
-        Ok(RoleSelectionContext { session, visible })
+        Ok(RoleSelectionContext {
+            session,
+            visible,
+            stale,
+        })
+    }
+
+    fn apply_role_preferences(
+        &self,
+        choices: &mut Vec<RoleChoice>,
+        identity: &SsoIdentity,
+        config: &Config,
+    ) {
+        apply_visible_role_preferences(
+            choices,
+            identity,
+            self.options.show_all,
+            self.options.selector_sort.unwrap_or(config.selector_sort),
+            self.options.initial_query.as_deref(),
+        );
+    }
+
+    /// Run the selector on a stale role list while a fresh one loads, merging the fresh list
+    /// into the open selector. Returns the selection and, if the refresh finished first, the
+    /// refreshed visible roles. A refresh still running when the selector closes is dropped;
+    /// the list is still stale next time, so it is retried then.
+    async fn select_while_refreshing(
+        &self,
+        provider: &dyn CloudProvider,
+        identity: &SsoIdentity,
+        config: &Config,
+        context: &RoleSelectionContext,
+        prompt: &str,
+    ) -> Result<(Option<tui::TuiSelection>, Option<Vec<RoleChoice>>)> {
+        let scope = self.options.scope;
+        let min_lifetime = config.min_credential_lifetime(identity);
+        let (updates, receiver) = std::sync::mpsc::channel();
+        let selector = select_role_async(
+            prompt,
+            &context.visible,
+            provider.active_markers(&context.visible, scope, min_lifetime),
+            guarded_flags(identity, &context.visible),
+            self.options.initial_query.as_deref(),
+            Some(receiver),
+        );
+        tokio::pin!(selector);
+        let refresh = async {
+            // The selector owns the terminal until it closes.
+            let _hidden = ui::hide_spinners();
+            let namespace = provider.cache_namespace();
+            let listing = provider.list_choices(context.session.as_ref()).await?;
+            let cached = roles_cache::load_cached_roles_with_age(&namespace)?;
+            settle_listing(
+                &namespace,
+                listing,
+                cached.as_ref().map(|(choices, _)| choices.as_slice()),
+            )
+        };
+
+        let refreshed = tokio::select! {
+            selected = &mut selector => return Ok((selected?, None)),
+            refreshed = refresh => refreshed,
+        };
+        let (refreshed, failures) = match refreshed {
+            Ok(RoleListing {
+                mut choices,
+                failures,
+            }) => {
+                self.apply_role_preferences(&mut choices, identity, config);
+                let _ = updates.send(tui::RoleListUpdate {
+                    markers: provider.active_markers(&choices, scope, min_lifetime),
+                    guarded: guarded_flags(identity, &choices),
+                    choices: choices.clone(),
+                });
+                (Some(choices), failures)
+            }
+            Err(err) => {
+                debug!(error = %err, "failed to refresh account/role list in the background");
+                (None, Vec::new())
+            }
+        };
+        drop(updates);
+        let selected = selector.await?;
+        warn_listing_failures(&failures);
+        Ok((selected, refreshed))
     }
 }
 
 struct RoleSelectionContext {
     session: Box<dyn ProviderSession>,
     visible: Vec<RoleChoice>,
+    /// `visible` is a cached list past its TTL.
+    stale: bool,
+}
+
+/// A resolved credential target, from the selector, the role flags, or a shortcut.
+#[derive(Clone)]
+struct Target {
+    choice: RoleChoice,
+    scope: AccessScope,
+    /// Region override for the exported credentials; `None` uses the provider default.
+    region: Option<String>,
+    omit_role_name: bool,
+}
+
+/// The identity shortcut named on the command line, unless an identity has that name.
+fn requested_shortcut<'a>(options: &AppOptions, identity: &'a SsoIdentity) -> Option<&'a Shortcut> {
+    let name = options.account.as_deref()?;
+    if identity.name == name {
+        return None;
+    }
+    identity.shortcut(name)
+}
+
+/// Build the target for a shortcut without listing accounts, naming the account from its
+/// configured alias or the cached role list when available.
+fn shortcut_target(
+    identity: &SsoIdentity,
+    namespace: &str,
+    shortcut: &Shortcut,
+    scope: AccessScope,
+) -> Result<Target> {
+    let cached_roles = roles_cache::load_cached_roles_with_age(namespace)?
+        .map(|(choices, _)| choices)
+        .unwrap_or_default();
+    let alias = identity
+        .accounts
+        .iter()
+        .find(|rule| rule.account_id == shortcut.account_id)
+        .and_then(|rule| rule.alias.as_deref())
+        .filter(|alias| !alias.trim().is_empty());
+    let account_name = match alias {
+        Some(alias) => alias.to_string(),
+        None => cached_account_name(&cached_roles, &shortcut.account_id),
+    };
+    let region = shortcut
+        .region
+        .as_deref()
+        .or_else(|| identity.region_for(&shortcut.account_id, &shortcut.role))
+        .map(str::to_string);
+    Ok(Target {
+        omit_role_name: has_single_role_for_account(&cached_roles, &shortcut.account_id),
+        region,
+        choice: RoleChoice {
+            account_id: shortcut.account_id.clone(),
+            account_name,
+            role_name: shortcut.role.clone(),
+        },
+        scope: if shortcut.readonly {
+            AccessScope::ReadOnly
+        } else {
+            scope
+        },
+    })
+}
+
+/// Account name from a cached role list, falling back to the account ID.
+fn cached_account_name(choices: &[RoleChoice], account_id: &str) -> String {
+    choices
+        .iter()
+        .find(|choice| choice.account_id == account_id)
+        .map(|choice| choice.account_name.clone())
+        .unwrap_or_else(|| account_id.to_string())
+}
+
+/// Write the identity's section of the shared credentials file when it has one; for other AWS
+/// identities, still drop roleman sections that have expired.
+fn update_shared_credentials(
+    identity: &SsoIdentity,
+    creds: &dyn ProviderCredentials,
+    profile_name: &str,
+) -> Result<()> {
+    if identity.shared_credentials_file {
+        return creds.write_shared_credentials(profile_name);
+    }
+    if identity.provider == ProviderKind::Aws
+        && let Err(err) = provider::aws::credentials_file::remove_expired_sections()
+    {
+        debug!(error = %err, "failed to clean up the shared credentials file");
+    }
+    Ok(())
 }
 
-fn write_env_file(path: &PathBuf, lines: &str) -> Result<()> {
+/// Write the exports privately, since they carry credentials.
+fn write_env_file(path: &Path, lines: &str) -> Result<()> {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent).map_err(|err| Error::Config(err.to_string()))?;
     }
-    std::fs::write(path, lines)
+    roles_cache::write_private(path, lines.as_bytes())
         .map_err(|err| Error::Config(err.to_string()))
         .map(|_| {
             tracing::trace!(path = %path.display(), "wrote env file");
         })
 }
 
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
 fn open_in_browser(url: &str) -> Result<()> {
     open::that(url).map_err(|err| Error::OpenBrowser(err.to_string()))
 }
@@ -464,18 +1424,88 @@ async fn select_role_async(
     prompt: &str,
     choices: &[RoleChoice],
     markers: Vec<crate::provider::ActiveMarker>,
+    guarded: Vec<bool>,
     initial_query: Option<&str>,
+    updates: Option<std::sync::mpsc::Receiver<tui::RoleListUpdate>>,
 ) -> Result<Option<tui::TuiSelection>> {
     let prompt = prompt.to_string();
     let choices = choices.to_vec();
     let initial_query = initial_query.map(ToOwned::to_owned);
     tokio::task::spawn_blocking(move || {
-        tui::select_role(&prompt, &choices, &markers, initial_query.as_deref())
+        tui::select_role(
+            &prompt,
+            &choices,
+            &markers,
+            &guarded,
+            initial_query.as_deref(),
+            updates,
+        )
     })
     .await
     .map_err(|err| Error::Tui(format!("failed to join tui task: {err}")))?
 }
 
+/// Which of `choices` are in accounts marked `confirm = true`.
+fn guarded_flags(identity: &SsoIdentity, choices: &[RoleChoice]) -> Vec<bool> {
+    choices
+        .iter()
+        .map(|choice| identity.requires_confirmation(&choice.account_id))
+        .collect()
+}
+
+/// A hook command clearing provider variables the shell still has from an earlier `set` that
+/// `vars` doesn't replace, such as GCP tokens when switching to an AWS identity.
+fn unset_leftover_vars(vars: &[EnvVar]) -> Option<String> {
+    let leftover = provider::managed_env_vars()
+        .filter(|name| {
+            std::env::var_os(name).is_some() && !vars.iter().any(|var| var.name == *name)
+        })
+        .collect::<Vec<_>>();
+    (!leftover.is_empty()).then(|| shell::hook_shell().unset_command(&leftover))
+}
+
+/// Mint credentials without prompting and store them in the credentials cache.
+async fn fetch_and_cache(
+    provider: &dyn CloudProvider,
+    session: &dyn ProviderSession,
+    cache: &CredentialsCache,
+    choice: &RoleChoice,
+    scope: AccessScope,
+    region: Option<&str>,
+    may_create: bool,
+) -> Result<Box<dyn ProviderCredentials>> {
+    let fresh = provider
+        .fetch_credentials(session, choice, scope, region, may_create)
+        .await?;
+    cache.save(
+        &choice.account_id,
+        &provider.cache_role_name(choice),
+        scope,
+        region,
+        fresh.expiration_ms(),
+        &fresh.to_cache_json()?,
+    )?;
+    Ok(fresh)
+}
+
+/// Guardrail for accounts marked `confirm = true` when roleman runs unattended (`caller`
+/// can't prompt): full-scope credentials are refused unless `--yes` was passed explicitly.
+fn require_unattended_consent(
+    identity: &SsoIdentity,
+    account_id: &str,
+    scope: AccessScope,
+    assume_yes: bool,
+    caller: &str,
+) -> Result<()> {
+    if scope != AccessScope::Full || !identity.requires_confirmation(account_id) || assume_yes {
+        return Ok(());
+    }
+    Err(Error::ConfirmationRequired(format!(
+        "account {account_id} requires confirmation for full access, which {caller} can't ask \
+         for; use read-only credentials or pass --yes"
+    )))
+}
+
 /// Fetch credentials, obtaining consent if the provider needs to create a cloud resource.
 ///
 /// `may_create` pre-authorizes creation (config knob or `--yes`). Otherwise, on
@@ -486,11 +1516,12 @@ async fn fetch_with_consent(
     session: &dyn ProviderSession,
     choice: &RoleChoice,
     scope: AccessScope,
+    region: Option<&str>,
     may_create: bool,
 ) -> Result<Box<dyn ProviderCredentials>> {
     let spinner = ui::spinner("Fetching role credentials...");
     match provider
-        .fetch_credentials(session, choice, scope, may_create)
+        .fetch_credentials(session, choice, scope, region, may_create)
         .await
     {
         Ok(creds) => {
@@ -513,7 +1544,7 @@ async fn fetch_with_consent(
             }
             let spinner = ui::spinner("Creating role and fetching credentials...");
             match provider
-                .fetch_credentials(session, choice, scope, true)
+                .fetch_credentials(session, choice, scope, region, true)
                 .await
             {
                 Ok(creds) => {
@@ -533,52 +1564,127 @@ async fn fetch_with_consent(
     }
 }
 
+/// The identity's role list: cached while younger than its `roles_cache_ttl`, otherwise
+/// fetched. With `revalidate`, an older cached list is returned as is and flagged stale, so
+/// the caller can show it while it refreshes. Accounts that failed to list are reported in the
+/// listing's `failures`, already warned about.
 async fn fetch_choices_with_cache(
     provider: &dyn CloudProvider,
+    identity: &SsoIdentity,
     ignore_cache: bool,
+    revalidate: bool,
     post_login_actions: PostLoginActions,
-) -> Result<(Box<dyn ProviderSession>, Vec<RoleChoice>)> {
+) -> Result<(Box<dyn ProviderSession>, RoleListing, bool)> {
     let session = provider
         .ensure_session(ignore_cache, post_login_actions)
         .await?;
     let namespace = provider.cache_namespace();
 
-    if !ignore_cache && let Some((choices, age)) = roles_cache::load_cached_roles(&namespace)? {
-        eprintln!(
-            "{}",
-            ui::info(&format!(
-                "Using cached account/role list (updated {} ago).",
-                roles_cache::format_age(age)
-            ))
-        );
-        return Ok((session, choices));
-    }
-
-    let cached_fallback = if ignore_cache {
+    let cached = if ignore_cache {
         None
     } else {
         roles_cache::load_cached_roles_with_age(&namespace)?
     };
+    let cached_listing = |choices: &[RoleChoice]| RoleListing {
+        choices: choices.to_vec(),
+        failures: Vec::new(),
+    };
+    if let Some((choices, age)) = &cached {
+        if *age <= identity.roles_cache_ttl() {
+            eprintln!(
+                "{}",
+                ui::info(&format!(
+                    "Using cached account/role list (updated {} ago).",
+                    format_duration(*age)
+                ))
+            );
+            return Ok((session, cached_listing(choices), false));
+        }
+        if revalidate && !choices.is_empty() {
+            eprintln!(
+                "{}",
+                ui::info(&format!(
+                    "Using cached account/role list from {} ago; refreshing it in the background.",
+                    format_duration(*age)
+                ))
+            );
+            return Ok((session, cached_listing(choices), true));
+        }
+    }
 
-    let choices = match provider.list_choices(session.as_ref()).await {
-        Ok(choices) => choices,
+    let listing = match provider.list_choices(session.as_ref()).await {
+        Ok(listing) => listing,
         Err(err) => {
-            if let Some((choices, age)) = cached_fallback {
+            if let Some((choices, age)) = cached {
                 eprintln!(
                     "{}",
                     ui::warn(&format!(
                         "Failed to refresh account/role list; using cached data from {} ago.",
-                        roles_cache::format_age(age)
+                        format_duration(age)
                     ))
                 );
-                return Ok((session, choices));
+                return Ok((session, cached_listing(&choices), false));
             }
             return Err(err);
         }
     };
 
-    roles_cache::save_cached_roles(&namespace, &choices)?;
-    Ok((session, choices))
+    let listing = settle_listing(
+        &namespace,
+        listing,
+        cached.as_ref().map(|(choices, _)| choices.as_slice()),
+    )?;
+    warn_listing_failures(&listing.failures);
+    Ok((session, listing, false))
+}
+
+/// Keep the roles of accounts that failed to list from the `cached` list, and save the
+/// merged list as the new cache. A listing with failures isn't saved, so the cache keeps
+/// its age and the failed accounts are listed again next time.
+fn settle_listing(
+    namespace: &str,
+    mut listing: RoleListing,
+    cached: Option<&[RoleChoice]>,
+) -> Result<RoleListing> {
+    if let Some(cached) = cached {
+        listing.fill_from_cache(cached);
+    }
+    if listing.failures.is_empty() {
+        roles_cache::save_cached_roles(namespace, &listing.choices)?;
+    }
+    Ok(listing)
+}
+
+/// Refuse to act on a role list that is missing accounts, naming the ones that failed.
+fn require_complete_listing(failures: &[ListingFailure]) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    let accounts = failures
+        .iter()
+        .map(|failure| format!("{} ({})", failure.account_name, failure.account_id))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(Error::Config(format!(
+        "couldn't list roles for {accounts}; not pruning profiles until every account lists"
+    )))
+}
+
+fn warn_listing_failures(failures: &[ListingFailure]) {
+    for failure in failures {
+        let fallback = match failure.cached_roles {
+            0 => "its roles are missing from the list".to_string(),
+            1 => "showing 1 cached role".to_string(),
+            count => format!("showing {count} cached roles"),
+        };
+        eprintln!(
+            "{}",
+            ui::warn(&format!(
+                "Couldn't list roles for {} ({}): {}; {fallback}.",
+                failure.account_name, failure.account_id, failure.error
+            ))
+        );
+    }
 }
 
 fn apply_visible_role_preferences(
@@ -588,6 +1694,7 @@ fn apply_visible_role_preferences(
     selector_sort: SelectorSortMode,
     initial_query: Option<&str>,
 ) {
+    add_chained_choices(choices, identity);
     if !show_all {
         apply_account_filters(choices, identity);
     }
@@ -599,6 +1706,29 @@ fn apply_visible_role_preferences(
     }
 }
 
+/// Append configured chained roles, named after their target account when it's listed.
+///
+/// Added after the role cache so config edits show up without a refresh.
+fn add_chained_choices(choices: &mut Vec<RoleChoice>, identity: &SsoIdentity) {
+    for chained in &identity.chained_roles {
+        let Some(account_id) = chained.account_id() else {
+            debug!(role_arn = %chained.role_arn, "skipping chained role with invalid ARN");
+            continue;
+        };
+        if choices
+            .iter()
+            .any(|choice| choice.account_id == account_id && choice.role_name == chained.name)
+        {
+            continue;
+        }
+        choices.push(RoleChoice {
+            account_id: account_id.to_string(),
+            account_name: cached_account_name(choices, account_id),
+            role_name: chained.name.clone(),
+        });
+    }
+}
+
 fn resolve_post_login_actions(options: &AppOptions, config: &Config) -> PostLoginActions {
     PostLoginActions {
         focus_terminal: options.focus_terminal_after_auth
@@ -607,6 +1737,257 @@ fn resolve_post_login_actions(options: &AppOptions, config: &Config) -> PostLogi
     }
 }
 
+fn resolve_identity(
+    options: &AppOptions,
+    config: &mut Config,
+    config_path: &Path,
+    config_exists: bool,
+) -> Result<SsoIdentity> {
+    if let Some(name) = options.account.as_deref() {
+        return config
+            .identities
+            .iter()
+            .find(|identity| identity.name == name)
+            .or_else(|| {
+                config
+                    .identities
+                    .iter()
+                    .find(|identity| identity.shortcut(name).is_some())
+            })
+            .cloned()
+            .ok_or(Error::MissingAccount);
+    }
+
+    if let Some(start_url) = options.start_url.clone() {
+        let region = options.sso_region.clone().ok_or(Error::MissingRegion)?;
+        let identity = SsoIdentity {
+            name: "manual".to_string(),
+            start_url,
+            sso_region: region,
+            provider: crate::config::ProviderKind::Aws,
+            accounts: Vec::new(),
+            ignore_roles: Vec::new(),
+            readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
+        };
+        if !matches!(
+            options.action,
+            AppAction::Login | AppAction::List | AppAction::CredentialProcess | AppAction::Renew
+        ) && !config_exists
+            && config.identities.is_empty()
+        {
+            maybe_save_account(config, config_path, &identity)?;
+        }
+        return Ok(identity);
+    }
+
+    if let Some(default_name) = config.default_identity.as_deref()
+        && let Some(identity) = config.identities.iter().find(|a| a.name == default_name)
+    {
+        return Ok(identity.clone());
+    }
+    if config.identities.len() == 1 {
+        return Ok(config.identities[0].clone());
+    }
+    if config.identities.is_empty() {
+        return Err(Error::MissingAccount);
+    }
+
+    prompt_select_account(&config.identities)
+}
+
+fn apply_account_filters(choices: &mut Vec<RoleChoice>, identity: &SsoIdentity) {
+    if !identity.ignore_roles.is_empty() {
+        choices.retain(|choice| !identity.ignore_roles.iter().any(|r| r == &choice.role_name));
+    }
+    if !identity.accounts.is_empty() {
+        choices.retain_mut(|choice| {
+            if let Some(rule) = identity
+                .accounts
+                .iter()
+                .find(|rule| rule.account_id == choice.account_id)
+            {
+                if rule.ignored {
+                    return false;
+                }
+                if let Some(alias) = &rule.alias
+                    && !alias.trim().is_empty()
+                {
+                    choice.account_name = alias.clone();
+                }
+                if rule.ignored_roles.iter().any(|r| r == &choice.role_name) {
+                    return false;
+                }
+            }
+            true
+        });
+    }
+}
+
+fn sort_choices(choices: &mut [RoleChoice], identity: &SsoIdentity) {
+    let mut precedence = std::collections::HashMap::new();
+    for rule in &identity.accounts {
+        if let Some(value) = rule.precedence {
+            precedence.insert(rule.account_id.clone(), value);
+        }
+    }
+    choices.sort_by_key(|choice| {
+        let priority = precedence.get(&choice.account_id).copied().unwrap_or(0);
+        (
+            std::cmp::Reverse(priority),
+            choice.account_name.to_lowercase(),
+            choice.role_name.to_lowercase(),
+        )
+    });
+}
+
+/// Every profile name `set` can write for the listed roles: from the filtered list it shows by
+/// default (account aliases applied, ignored roles dropped) and from the raw list it shows
+/// with `--show-all`. Whether the role name is omitted depends on which list it came from.
+fn current_profile_names(
+    provider: &dyn CloudProvider,
+    identity: &SsoIdentity,
+    choices: Vec<RoleChoice>,
+) -> std::collections::HashSet<String> {
+    let mut filtered = choices.clone();
+    apply_account_filters(&mut filtered, identity);
+    [choices, filtered]
+        .iter()
+        .flat_map(|list| {
+            list.iter().flat_map(|choice| {
+                let omit_role_name = has_single_role_for_account(list, &choice.account_id);
+                [AccessScope::Full, AccessScope::ReadOnly]
+                    .map(|scope| provider.profile_name(choice, scope, omit_role_name))
+            })
+        })
+        .collect()
+}
+
+fn has_single_role_for_account(choices: &[RoleChoice], account_id: &str) -> bool {
+    choices
+        .iter()
+        .filter(|choice| choice.account_id == account_id)
+        .take(2)
+        .count()
+        == 1
+}
+
+fn maybe_save_account(
+    config: &mut Config,
+    config_path: &Path,
+    account: &SsoIdentity,
+) -> Result<()> {
+    if !prompt_yes_no("No config found. Save this SSO account as default? [y/N] ")? {
+        return Ok(());
+    }
+    let suggested = guess_account_name(&account.start_url);
+    let prompt = format!("Account name [{}]: ", suggested);
+    let name = prompt_input(&prompt)?;
+    let final_name = if name.trim().is_empty() {
+        suggested
+    } else {
+        name.trim().to_string()
+    };
+    if final_name.is_empty() {
+        return Ok(());
+    }
+    let account = SsoIdentity {
+        name: final_name,
+        start_url: account.start_url.clone(),
+        sso_region: account.sso_region.clone(),
+        provider: account.provider,
+        accounts: Vec::new(),
+        ignore_roles: Vec::new(),
+        readonly_policy: account.readonly_policy.clone(),
+        credential_process: account.credential_process,
+        shared_credentials_file: account.shared_credentials_file,
+        isolated_aws_config: account.isolated_aws_config,
+        config_file: None,
+        shortcuts: account.shortcuts.clone(),
+        chained_roles: account.chained_roles.clone(),
+        min_credential_lifetime_seconds: account.min_credential_lifetime_seconds,
+        roles_cache_ttl_seconds: account.roles_cache_ttl_seconds,
+        list_concurrency: account.list_concurrency,
+        max_requests_per_second: account.max_requests_per_second,
+    };
+    config.default_identity = Some(account.name.clone());
+    config.identities.push(account);
+    config.save(config_path)?;
+    Ok(())
+}
+
+fn prompt_select_account(accounts: &[SsoIdentity]) -> Result<SsoIdentity> {
+    eprintln!("Select SSO account:");
+    for (idx, account) in accounts.iter().enumerate() {
+        eprintln!("  {}. {} ({})", idx + 1, account.name, account.sso_region);
+    }
+    let input = prompt_input("Enter choice: ")?;
+    let index = input
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|v| v.checked_sub(1));
+    if let Some(index) = index
+        && let Some(account) = accounts.get(index)
+    {
+        return Ok(account.clone());
+    }
+    Err(Error::MissingAccount)
+}
+
+fn prompt_yes_no(prompt: &str) -> Result<bool> {
+    let input = prompt_input(prompt)?;
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Ask on stderr, since stdout may carry exports or a `credential_process` document.
+fn prompt_input(prompt: &str) -> Result<String> {
+    use std::io::{self, Write};
+    let mut stderr = io::stderr();
+    stderr
+        .write_all(prompt.as_bytes())
+        .map_err(|err| Error::Config(err.to_string()))?;
+    stderr
+        .flush()
+        .map_err(|err| Error::Config(err.to_string()))?;
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .map_err(|err| Error::Config(err.to_string()))?;
+    Ok(input)
+}
+
+fn guess_account_name(start_url: &str) -> String {
+    let host = start_url
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let subdomain = host.split('.').next().unwrap_or_default();
+    let name = subdomain
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' {
+                ch
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    name.trim_matches('-').to_string()
+}
+
 #[cfg(test)]
 mod test_support;
 
@@ -618,7 +1999,7 @@ mod tests {
 
     #[test]
     fn writes_env_file() {
-        use crate::provider::{EnvVar, export_lines};
+        use crate::provider::EnvVar;
 
         let temp = TempDir::new().unwrap();
         let path = temp.path().join("env.sh");
@@ -627,12 +2008,33 @@ mod tests {
             EnvVar::new("AWS_PROFILE", "Acme-Cloud/ReadOnly"),
         ];
 
-        write_env_file(&path, &export_lines(&vars)).unwrap();
+        let bash = shell::shell_for_name("bash").unwrap();
+        write_env_file(&path, &bash.render_exports(&vars).unwrap()).unwrap();
         let contents = std::fs::read_to_string(path).unwrap();
         assert!(contents.contains("AWS_ACCESS_KEY_ID=AKIA123"));
         assert!(contents.contains("AWS_PROFILE=Acme-Cloud/ReadOnly"));
     }
 
+    #[test]
+    fn switching_providers_clears_the_other_providers_variables() {
+        let _lock = crate::test_support::lock_env();
+        let previous = std::env::var("CLOUDSDK_CORE_PROJECT").ok();
+        unsafe { std::env::set_var("CLOUDSDK_CORE_PROJECT", "mock-platform") };
+
+        let vars = vec![EnvVar::new("AWS_ACCESS_KEY_ID", "AKIA123")];
+        let unset = unset_leftover_vars(&vars).unwrap();
+        let names = unset.split_whitespace().collect::<Vec<_>>();
+        assert!(names.contains(&"CLOUDSDK_CORE_PROJECT"));
+        assert!(!names.contains(&"AWS_ACCESS_KEY_ID"));
+
+        unsafe {
+            match previous {
+                Some(value) => std::env::set_var("CLOUDSDK_CORE_PROJECT", value),
+                None => std::env::remove_var("CLOUDSDK_CORE_PROJECT"),
+            }
+        }
+    }
+
     #[test]
     fn guesses_account_name_from_url() {
         assert_eq!(guess_account_name("https://acme.awsapps.com/start"), "acme");
@@ -647,6 +2049,16 @@ mod tests {
             sso_region: "us-east-1".into(),
             provider: config::ProviderKind::Aws,
             readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
             accounts: vec![
                 config::AccountRule {
                     account_id: "2222".into(),
@@ -654,6 +2066,9 @@ mod tests {
                     ignored: false,
                     ignored_roles: Vec::new(),
                     precedence: Some(5),
+                    region: None,
+                    role_regions: Default::default(),
+                    confirm: false,
                 },
                 config::AccountRule {
                     account_id: "1111".into(),
@@ -661,6 +2076,9 @@ mod tests {
                     ignored: false,
                     ignored_roles: Vec::new(),
                     precedence: None,
+                    region: None,
+                    role_regions: Default::default(),
+                    confirm: false,
                 },
             ],
             ignore_roles: Vec::new(),
@@ -692,6 +2110,60 @@ mod tests {
         assert_eq!(choices[2].role_name, "ReadOnly");
     }
 
+    #[test]
+    fn adds_chained_roles_under_target_account() {
+        let chained = |name: &str, role_arn: &str| config::ChainedRole {
+            name: name.into(),
+            source_account_id: "1111".into(),
+            source_role: "Admin".into(),
+            role_arn: role_arn.into(),
+            external_id: None,
+            duration_seconds: None,
+        };
+        let identity = SsoIdentity {
+            name: "acme".into(),
+            start_url: "https://acme.awsapps.com/start".into(),
+            sso_region: "us-east-1".into(),
+            provider: config::ProviderKind::Aws,
+            readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: vec![
+                chained("deployer", "arn:aws:iam::2222:role/Deployer"),
+                chained("auditor", "arn:aws:iam::3333:role/Auditor"),
+                chained("broken", "not-an-arn"),
+            ],
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
+            accounts: Vec::new(),
+            ignore_roles: Vec::new(),
+        };
+        let mut choices = vec![RoleChoice {
+            account_id: "2222".into(),
+            account_name: "Workloads".into(),
+            role_name: "Admin".into(),
+        }];
+
+        add_chained_choices(&mut choices, &identity);
+        // Idempotent: a cached list that already has them doesn't grow.
+        add_chained_choices(&mut choices, &identity);
+
+        let labels: Vec<String> = choices.iter().map(RoleChoice::label).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "Workloads (2222) — Admin",
+                "Workloads (2222) — deployer",
+                "3333 (3333) — auditor",
+            ]
+        );
+    }
+
     #[test]
     fn detects_accounts_with_single_role() {
         let choices = vec![
@@ -717,6 +2189,57 @@ mod tests {
         assert!(!has_single_role_for_account(&choices, "3333"));
     }
 
+    fn profile_test_identity() -> SsoIdentity {
+        toml::from_str(
+            r#"
+            name = "work"
+            start_url = "https://example.awsapps.com/start"
+            sso_region = "us-east-1"
+            ignore_roles = ["Billing"]
+            accounts = [{ account_id = "1111", alias = "Platform" }]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn role(account_id: &str, account_name: &str, role_name: &str) -> RoleChoice {
+        RoleChoice {
+            account_id: account_id.into(),
+            account_name: account_name.into(),
+            role_name: role_name.into(),
+        }
+    }
+
+    #[test]
+    fn current_profiles_follow_account_aliases() {
+        let identity = profile_test_identity();
+        let provider = provider::for_identity(&identity).unwrap();
+        let choices = vec![
+            role("1111", "Acme Platform", "Admin"),
+            role("1111", "Acme Platform", "ReadOnly"),
+        ];
+        let current = current_profile_names(provider.as_ref(), &identity, choices);
+        // `set` shows the alias by default and the raw account name under `--show-all`.
+        assert!(current.contains("Platform/Admin"));
+        assert!(current.contains("Platform/ReadOnly"));
+        assert!(current.contains("Acme-Platform/Admin"));
+    }
+
+    #[test]
+    fn current_profiles_omit_role_name_when_ignored_roles_leave_one() {
+        let identity = profile_test_identity();
+        let provider = provider::for_identity(&identity).unwrap();
+        let choices = vec![
+            role("2222", "Acme", "Admin"),
+            role("2222", "Acme", "Billing"),
+        ];
+        let current = current_profile_names(provider.as_ref(), &identity, choices);
+        // Billing is ignored, so `set` and `profiles sync` name the only visible role `Acme`.
+        assert!(current.contains("Acme"));
+        assert!(current.contains("Acme/Admin"));
+        assert!(current.contains("Acme/Billing"));
+    }
+
     #[test]
     fn resolves_post_login_actions_from_config_defaults() {
         let config = Config {
@@ -729,6 +2252,10 @@ mod tests {
             hook_prompt: None,
             selector_sort: SelectorSortMode::Dynamic,
             auto_create_readonly_roles: None,
+            prompt_template: None,
+            min_credential_lifetime_seconds: None,
+            credential_refresh_fraction: None,
+            credential_cache: config::CredentialCacheBackend::File,
         };
         let options = AppOptions::default();
 
@@ -749,6 +2276,10 @@ mod tests {
             hook_prompt: None,
             selector_sort: SelectorSortMode::Dynamic,
             auto_create_readonly_roles: None,
+            prompt_template: None,
+            min_credential_lifetime_seconds: None,
+            credential_refresh_fraction: None,
+            credential_cache: config::CredentialCacheBackend::File,
         };
         let options = AppOptions::default();
 
@@ -769,6 +2300,10 @@ mod tests {
             hook_prompt: None,
             selector_sort: SelectorSortMode::Dynamic,
             auto_create_readonly_roles: None,
+            prompt_template: None,
+            min_credential_lifetime_seconds: None,
+            credential_refresh_fraction: None,
+            credential_cache: config::CredentialCacheBackend::File,
         };
         let options = AppOptions {
             focus_terminal_after_auth: true,
@@ -818,204 +2353,255 @@ mod tests {
         assert!(config.identities.is_empty());
         assert!(!config_path.exists());
     }
-}
 
-fn resolve_identity(
-    options: &AppOptions,
-    config: &mut Config,
-    config_path: &Path,
-    config_exists: bool,
-) -> Result<SsoIdentity> {
-    if let Some(name) = options.account.as_deref() {
-        return config
-            .identities
-            .iter()
-            .find(|identity| identity.name == name)
-            .cloned()
-            .ok_or(Error::MissingAccount);
+    fn identity_with_shortcut() -> SsoIdentity {
+        SsoIdentity {
+            name: "work".into(),
+            start_url: "https://acme.awsapps.com/start".into(),
+            sso_region: "us-east-1".into(),
+            provider: config::ProviderKind::Aws,
+            readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: vec![config::Shortcut {
+                name: "prod-ro".into(),
+                account_id: "1111".into(),
+                role: "ReadOnly".into(),
+                readonly: true,
+                region: None,
+            }],
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
+            accounts: vec![config::AccountRule {
+                account_id: "1111".into(),
+                alias: Some("Production".into()),
+                ignored: false,
+                ignored_roles: Vec::new(),
+                precedence: None,
+                region: None,
+                role_regions: Default::default(),
+                confirm: false,
+            }],
+            ignore_roles: Vec::new(),
+        }
     }
 
-    if let Some(start_url) = options.start_url.clone() {
-        let region = options.sso_region.clone().ok_or(Error::MissingRegion)?;
-        let identity = SsoIdentity {
-            name: "manual".to_string(),
-            start_url,
-            sso_region: region,
-            provider: crate::config::ProviderKind::Aws,
-            accounts: Vec::new(),
-            ignore_roles: Vec::new(),
-            readonly_policy: None,
+    #[test]
+    fn guardrail_only_intercepts_full_scope_exports() {
+        let mut identity = identity_with_shortcut();
+        identity.accounts[0].confirm = true;
+        let provider = provider::for_identity(&identity).unwrap();
+        let target = |account_id: &str, scope| Target {
+            choice: RoleChoice {
+                account_id: account_id.into(),
+                account_name: "Production".into(),
+                role_name: "Admin".into(),
+            },
+            scope,
+            region: None,
+            omit_role_name: false,
         };
-        if !matches!(options.action, AppAction::Login | AppAction::List)
-            && !config_exists
-            && config.identities.is_empty()
-        {
-            maybe_save_account(config, config_path, &identity)?;
+        let app = |action, assume_yes| {
+            App::new(AppOptions {
+                action,
+                assume_yes,
+                ..AppOptions::default()
+            })
+        };
+
+        // Read-only, unguarded accounts, the console and `--yes` pass straight through.
+        for (app, target) in [
+            (
+                app(AppAction::Set, false),
+                target("1111", AccessScope::ReadOnly),
+            ),
+            (
+                app(AppAction::Set, false),
+                target("2222", AccessScope::Full),
+            ),
+            (
+                app(AppAction::Open, false),
+                target("1111", AccessScope::Full),
+            ),
+            (
+                app(AppAction::Exec, true),
+                target("1111", AccessScope::Full),
+            ),
+        ] {
+            let confirmed = app
+                .confirm_guarded_target(provider.as_ref(), &identity, &target)
+                .unwrap();
+            assert_eq!(confirmed.scope, target.scope);
         }
-        return Ok(identity);
-    }
 
-    if let Some(default_name) = config.default_identity.as_deref()
-        && let Some(identity) = config.identities.iter().find(|a| a.name == default_name)
-    {
-        return Ok(identity.clone());
-    }
-    if config.identities.len() == 1 {
-        return Ok(config.identities[0].clone());
+        // Without a terminal there is nobody to type the name, so full access is refused.
+        if !std::io::stdin().is_terminal() {
+            let err = app(AppAction::Set, false)
+                .confirm_guarded_target(
+                    provider.as_ref(),
+                    &identity,
+                    &target("1111", AccessScope::Full),
+                )
+                .err()
+                .unwrap();
+            assert!(matches!(err, Error::ConfirmationRequired(_)));
+        }
     }
-    if config.identities.is_empty() {
-        return Err(Error::MissingAccount);
+
+    #[test]
+    fn unattended_runs_need_yes_for_guarded_accounts() {
+        let mut identity = identity_with_shortcut();
+        identity.accounts[0].confirm = true;
+        let check = |account_id, scope, assume_yes| {
+            require_unattended_consent(&identity, account_id, scope, assume_yes, "test")
+        };
+
+        assert!(matches!(
+            check("1111", AccessScope::Full, false),
+            Err(Error::ConfirmationRequired(_))
+        ));
+        assert!(check("1111", AccessScope::Full, true).is_ok());
+        assert!(check("1111", AccessScope::ReadOnly, false).is_ok());
+        assert!(check("2222", AccessScope::Full, false).is_ok());
     }
 
-    prompt_select_account(&config.identities)
-}
+    #[test]
+    fn shortcut_name_resolves_to_owning_identity() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join("config.toml");
+        let mut config = Config {
+            identities: vec![
+                SsoIdentity {
+                    name: "other".into(),
+                    shortcuts: Vec::new(),
+                    chained_roles: Vec::new(),
+                    min_credential_lifetime_seconds: None,
+                    roles_cache_ttl_seconds: None,
+                    list_concurrency: None,
+                    max_requests_per_second: None,
+                    ..identity_with_shortcut()
+                },
+                identity_with_shortcut(),
+            ],
+            ..Config::default()
+        };
+        let options = AppOptions {
+            account: Some("prod-ro".into()),
+            ..AppOptions::default()
+        };
 
-fn apply_account_filters(choices: &mut Vec<RoleChoice>, identity: &SsoIdentity) {
-    if !identity.ignore_roles.is_empty() {
-        choices.retain(|choice| !identity.ignore_roles.iter().any(|r| r == &choice.role_name));
+        let identity = resolve_identity(&options, &mut config, &config_path, true).unwrap();
+        assert_eq!(identity.name, "work");
+        let shortcut = requested_shortcut(&options, &identity).unwrap();
+        assert_eq!(shortcut.role, "ReadOnly");
+
+        // Naming the identity itself is not a shortcut request.
+        let options = AppOptions {
+            account: Some("work".into()),
+            ..AppOptions::default()
+        };
+        assert!(requested_shortcut(&options, &identity).is_none());
     }
-    if !identity.accounts.is_empty() {
-        choices.retain_mut(|choice| {
-            if let Some(rule) = identity
-                .accounts
-                .iter()
-                .find(|rule| rule.account_id == choice.account_id)
-            {
-                if rule.ignored {
-                    return false;
-                }
-                if let Some(alias) = &rule.alias
-                    && !alias.trim().is_empty()
-                {
-                    choice.account_name = alias.clone();
-                }
-                if rule.ignored_roles.iter().any(|r| r == &choice.role_name) {
-                    return false;
-                }
-            }
-            true
-        });
+
+    #[test]
+    fn pruning_needs_every_account_listed() {
+        assert!(require_complete_listing(&[]).is_ok());
+        let failure = ListingFailure {
+            account_id: "2222".into(),
+            account_name: "Staging".into(),
+            error: "throttled".into(),
+            cached_roles: 1,
+        };
+        let err = require_complete_listing(&[failure]).unwrap_err();
+        assert!(err.to_string().contains("Staging (2222)"));
     }
-}
 
-fn sort_choices(choices: &mut [RoleChoice], identity: &SsoIdentity) {
-    let mut precedence = std::collections::HashMap::new();
-    for rule in &identity.accounts {
-        if let Some(value) = rule.precedence {
-            precedence.insert(rule.account_id.clone(), value);
+    #[test]
+    fn partial_listings_do_not_refresh_the_cache() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("XDG_CACHE_HOME").ok();
+        unsafe {
+            std::env::set_var("XDG_CACHE_HOME", temp.path());
         }
-    }
-    choices.sort_by_key(|choice| {
-        let priority = precedence.get(&choice.account_id).copied().unwrap_or(0);
-        (
-            std::cmp::Reverse(priority),
-            choice.account_name.to_lowercase(),
-            choice.role_name.to_lowercase(),
-        )
-    });
-}
+        let choice = |account_id: &str| RoleChoice {
+            account_id: account_id.into(),
+            account_name: format!("Account {account_id}"),
+            role_name: "Admin".into(),
+        };
+        let cached = vec![choice("1111"), choice("2222")];
+        roles_cache::save_cached_roles("aws:test", &cached).unwrap();
 
-fn has_single_role_for_account(choices: &[RoleChoice], account_id: &str) -> bool {
-    choices
-        .iter()
-        .filter(|choice| choice.account_id == account_id)
-        .take(2)
-        .count()
-        == 1
-}
+        let listing = RoleListing {
+            choices: vec![choice("1111")],
+            failures: vec![ListingFailure {
+                account_id: "2222".into(),
+                account_name: "Account 2222".into(),
+                error: "throttled".into(),
+                cached_roles: 0,
+            }],
+        };
+        let settled = settle_listing("aws:test", listing, Some(&cached)).unwrap();
+        assert_eq!(settled.choices.len(), 2);
+        assert_eq!(settled.failures[0].cached_roles, 1);
+        let saved = |namespace| {
+            roles_cache::load_cached_roles_with_age(namespace)
+                .unwrap()
+                .map(|(choices, _)| choices.len())
+        };
+        // The cache keeps the earlier list; only a complete listing replaces it.
+        assert_eq!(saved("aws:test"), Some(2));
 
-fn maybe_save_account(
-    config: &mut Config,
-    config_path: &Path,
-    account: &SsoIdentity,
-) -> Result<()> {
-    if !prompt_yes_no("No config found. Save this SSO account as default? [y/N] ")? {
-        return Ok(());
-    }
-    let suggested = guess_account_name(&account.start_url);
-    let prompt = format!("Account name [{}]: ", suggested);
-    let name = prompt_input(&prompt)?;
-    let final_name = if name.trim().is_empty() {
-        suggested
-    } else {
-        name.trim().to_string()
-    };
-    if final_name.is_empty() {
-        return Ok(());
-    }
-    let account = SsoIdentity {
-        name: final_name,
-        start_url: account.start_url.clone(),
-        sso_region: account.sso_region.clone(),
-        provider: account.provider,
-        accounts: Vec::new(),
-        ignore_roles: Vec::new(),
-        readonly_policy: account.readonly_policy.clone(),
-    };
-    config.default_identity = Some(account.name.clone());
-    config.identities.push(account);
-    config.save(config_path)?;
-    Ok(())
-}
+        let complete = RoleListing {
+            choices: vec![choice("1111")],
+            failures: Vec::new(),
+        };
+        settle_listing("aws:test", complete, Some(&cached)).unwrap();
+        assert_eq!(saved("aws:test"), Some(1));
 
-fn prompt_select_account(accounts: &[SsoIdentity]) -> Result<SsoIdentity> {
-    eprintln!("Select SSO account:");
-    for (idx, account) in accounts.iter().enumerate() {
-        eprintln!("  {}. {} ({})", idx + 1, account.name, account.sso_region);
-    }
-    let input = prompt_input("Enter choice: ")?;
-    let index = input
-        .trim()
-        .parse::<usize>()
-        .ok()
-        .and_then(|v| v.checked_sub(1));
-    if let Some(index) = index
-        && let Some(account) = accounts.get(index)
-    {
-        return Ok(account.clone());
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("XDG_CACHE_HOME", value);
+            } else {
+                std::env::remove_var("XDG_CACHE_HOME");
+            }
+        }
     }
-    Err(Error::MissingAccount)
-}
-
-fn prompt_yes_no(prompt: &str) -> Result<bool> {
-    let input = prompt_input(prompt)?;
-    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
-}
 
-fn prompt_input(prompt: &str) -> Result<String> {
-    use std::io::{self, Write};
-    let mut stdout = io::stdout();
-    stdout
-        .write_all(prompt.as_bytes())
-        .map_err(|err| Error::Config(err.to_string()))?;
-    stdout
-        .flush()
-        .map_err(|err| Error::Config(err.to_string()))?;
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .map_err(|err| Error::Config(err.to_string()))?;
-    Ok(input)
-}
+    #[test]
+    fn shortcut_target_uses_alias_and_readonly_scope() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("XDG_CACHE_HOME").ok();
+        unsafe {
+            std::env::set_var("XDG_CACHE_HOME", temp.path());
+        }
 
-fn guess_account_name(start_url: &str) -> String {
-    let host = start_url
-        .trim()
-        .trim_start_matches("https://")
-        .trim_start_matches("http://")
-        .split('/')
-        .next()
-        .unwrap_or_default();
-    let subdomain = host.split('.').next().unwrap_or_default();
-    let name = subdomain
-        .chars()
-        .map(|ch| {
-            if ch.is_ascii_alphanumeric() || ch == '-' {
-                ch
+        let identity = identity_with_shortcut();
+        let target = shortcut_target(
+            &identity,
+            "aws:work",
+            &identity.shortcuts[0],
+            AccessScope::Full,
+        )
+        .unwrap();
+        assert_eq!(target.choice.account_name, "Production");
+        assert_eq!(target.choice.role_name, "ReadOnly");
+        assert_eq!(target.scope, AccessScope::ReadOnly);
+        // Without a cached role list we can't tell the account has a single role.
+        assert!(!target.omit_role_name);
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("XDG_CACHE_HOME", value);
             } else {
-                '-'
+                std::env::remove_var("XDG_CACHE_HOME");
             }
-        })
-        .collect::<String>();
-    name.trim_matches('-').to_string()
+        }
+    }
 }
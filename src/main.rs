@@ -3,14 +3,16 @@ use std::fs::OpenOptions;
 use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 
-mod shell;
-
-use crate::shell::{Shell, detect_shell_from_env, shell_for_name};
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use roleman::{
-    AccessScope, App, AppAction, AppOptions, Config,
+    AccessScope, App, AppAction, AppOptions, CacheEntry, CacheEntryDetails, CacheFilter, CacheKind,
+    Config, ExportFormat, RoleFilter,
     config::{HookPromptMode, SelectorSortMode},
-    history, ui,
+    format_duration, history,
+    provider::{self, EnvVar, aws::config as aws_config},
+    shell::{HOOK_VERSION, Shell, detect_shell_from_env, hook_shell, shell_for_name},
+    status::CredentialState,
+    ui,
 };
 use tracing_subscriber::prelude::*;
 
@@ -20,7 +22,7 @@ use tracing_subscriber::prelude::*;
     about = "Select an AWS IAM Identity Center role and export temporary AWS credentials",
     long_about = "Roleman lets you pick an AWS IAM Identity Center (AWS SSO) account and role, then emits shell exports for temporary AWS credentials.\n\nUse `roleman` for interactive credential export, `roleman login` to ensure you have a valid IAM Identity Center session, `roleman list` to inspect available account and role combinations, `roleman open` to open the selected role in the AWS access portal, and `roleman hook`/`roleman install-hook` for shell integration.",
     disable_help_subcommand = true,
-    after_help = "Examples:\n  roleman\n  roleman --account prod\n  roleman -q sandbox\n  roleman --no-cache --print\n  roleman --no-cache --close-auth-tab --focus-terminal-after-auth\n  roleman --sso-start-url https://acme.awsapps.com/start --sso-region us-east-1\n  roleman login\n  roleman login --account prod\n  roleman list\n  roleman list --format json\n  roleman open\n  roleman hook\n  roleman install-hook --alias"
+    after_help = "Examples:\n  roleman\n  roleman --account prod\n  roleman -q sandbox\n  roleman --account-id 123456789012 --role Admin\n  roleman --no-cache --print\n  roleman --no-cache --close-auth-tab --focus-terminal-after-auth\n  roleman --sso-start-url https://acme.awsapps.com/start --sso-region us-east-1\n  roleman login\n  roleman login --account prod\n  roleman list\n  roleman list --format json\n  roleman open\n  roleman serve\n  roleman hook\n  roleman install-hook --alias"
 )]
 struct Cli {
     #[command(subcommand)]
@@ -72,7 +74,7 @@ struct CommonArgs {
     #[arg(
         short = 'y',
         long = "yes",
-        help = "Skip confirmation before roleman creates a cloud resource (e.g. the read-only role)"
+        help = "Skip confirmations: creating a cloud resource (e.g. the read-only role) and full access to protected accounts"
     )]
     assume_yes: bool,
 
@@ -92,6 +94,35 @@ struct CommonArgs {
     )]
     initial_query: Option<String>,
 
+    #[arg(
+        long = "account-id",
+        value_name = "id",
+        help = "Pick the role in this AWS account ID without opening the selector"
+    )]
+    account_id: Option<String>,
+
+    #[arg(
+        long = "account-name",
+        value_name = "name",
+        help = "Pick the role in the account with this name or alias without opening the selector"
+    )]
+    account_name: Option<String>,
+
+    #[arg(
+        long = "role",
+        alias = "role-name",
+        value_name = "name",
+        help = "Pick the role with this name without opening the selector"
+    )]
+    role: Option<String>,
+
+    #[arg(
+        long = "region",
+        value_name = "region",
+        help = "Region to export with the credentials (overrides configured defaults)"
+    )]
+    region: Option<String>,
+
     #[arg(
         long = "refresh-seconds",
         help = "Polling interval in seconds while waiting for available roles"
@@ -132,9 +163,9 @@ enum CliCommand {
         alias = "s",
         about = "Select a role and emit AWS credential exports",
         long_about = "Launch the role selector and emit AWS credential exports for the chosen role.\n\nThis is equivalent to running `roleman` without a subcommand.",
-        after_help = "Examples:\n  roleman set\n  roleman set prod\n  roleman set --account prod\n  roleman set -q sandbox"
+        after_help = "Examples:\n  roleman set\n  roleman set prod\n  roleman set prod-ro\n  roleman set --account prod\n  roleman set -q sandbox\n  roleman set --account-name Platform --role ReadOnly\n  roleman set prod --format dotenv > .env\n  roleman set prod --format fish | source"
     )]
-    Set(RunSubcommandArgs),
+    Set(SetArgs),
     #[command(
         alias = "o",
         about = "Select a role and open it in the AWS access portal",
@@ -181,8 +212,8 @@ enum CliCommand {
     },
     #[command(
         alias = "u",
-        about = "Unset roleman-managed AWS environment variables",
-        long_about = "Prints shell commands to unset AWS environment variables managed by roleman.\n\nWhen running under a shell hook, writes the unset command to the hook env file so your current shell is updated."
+        about = "Unset roleman-managed AWS and GCP environment variables",
+        long_about = "Prints shell commands to unset the AWS and GCP environment variables managed by roleman.\n\nWhen running under a shell hook, writes the unset command to the hook env file so your current shell is updated."
     )]
     Unset,
     #[command(
@@ -191,12 +222,288 @@ enum CliCommand {
         after_help = "Examples:\n  roleman history\n  roleman history --limit 20\n  roleman history clear"
     )]
     History(HistoryArgs),
+    #[command(
+        about = "Inspect or prune roleman's local caches",
+        long_about = "List, show or delete what roleman keeps in its cache directory: minted role credentials, account/role lists and remembered desktop permissions.\n\nSecrets are never printed. Filters narrow `list` and `clear`; `clear` without filters empties the whole cache.",
+        after_help = "Examples:\n  roleman cache list\n  roleman cache list --kind credentials --account prod\n  roleman cache show creds-3f2a\n  roleman cache clear --expired-only\n  roleman cache clear --account-id 123456789012 --dry-run"
+    )]
+    Cache(CacheArgs),
     #[command(
         about = "Remove cloud resources roleman created (e.g. the read-only role)",
         long_about = "Scan the current account (using your active credentials) for resources roleman created and remove them. Operates on one account at a time — re-run after switching accounts.",
         after_help = "Examples:\n  roleman cleanup roles\n  roleman cleanup roles --dry-run\n  roleman cleanup roles --yes"
     )]
     Cleanup(CleanupArgs),
+    #[command(
+        about = "Generate, list or prune the AWS profiles roleman writes",
+        long_about = "Manage the `[profile ...]` sections roleman generates in the AWS config file.\n\n`sync` writes a profile for every visible role, for tools that enumerate profiles. `list` shows the generated profiles and marks those whose role is no longer in the role list as stale. `prune` removes the stale ones. Sections without `roleman_managed = true` are never touched.",
+        after_help = "Examples:\n  roleman profiles sync\n  roleman profiles sync --account prod --dry-run\n  roleman profiles list\n  roleman profiles prune --dry-run"
+    )]
+    Profiles(ProfilesArgs),
+    #[command(
+        name = "credential-process",
+        about = "Print credentials for one role in the AWS credential_process format",
+        long_about = "Print temporary credentials for a single account/role as the JSON document the AWS `credential_process` setting expects, reusing roleman's credential cache.\n\nSet `credential_process = true` on an identity to have roleman write this command into the profiles it generates, so long-running tools refresh credentials on their own.",
+        after_help = "Examples:\n  roleman credential-process --account-id 123456789012 --role-name Admin\n  roleman credential-process --account prod --account-id 123456789012 --role-name Admin --readonly"
+    )]
+    CredentialProcess(CredentialProcessArgs),
+    #[command(
+        about = "Serve a role's credentials on a local container-credentials endpoint",
+        long_about = "Launch the role selector, then serve the chosen role's credentials on a loopback HTTP endpoint compatible with `AWS_CONTAINER_CREDENTIALS_FULL_URI` and `AWS_CONTAINER_AUTHORIZATION_TOKEN`.\n\nCredentials are re-minted shortly before they expire, so SDK clients on this machine, and containers on the host network (`docker run --network host`), keep working. The endpoint listens on 127.0.0.1 only. Prints the two variables to export, then runs until interrupted.",
+        after_help = "Examples:\n  roleman serve\n  roleman serve prod --port 9911\n  roleman serve -q sandbox --readonly"
+    )]
+    Serve(ServeArgs),
+    #[command(
+        about = "Run a command with a role's credentials",
+        long_about = "Resolve a role exactly like `set`, then run the command with the role's credentials in its environment instead of exporting them into your shell.\n\nThe command also gets `ROLEMAN_ACTIVE` set to the active profile. roleman exits with the command's exit status, or 128 + the signal number if a signal killed it.",
+        after_help = "Examples:\n  roleman exec -- aws sts get-caller-identity\n  roleman exec prod-ro -- terraform plan\n  roleman exec --account-id 123456789012 --role Admin -- ./deploy.sh"
+    )]
+    Exec(ExecArgs),
+    #[command(
+        about = "Start a subshell with a role's credentials",
+        long_about = "Resolve a role exactly like `set`, then start `$SHELL` with the role's credentials in its environment. Exit the subshell to drop them; your current shell is never modified.\n\nThe subshell also gets `ROLEMAN_ACTIVE` set to the active profile.",
+        after_help = "Examples:\n  roleman shell\n  roleman shell prod-ro\n  roleman shell -q sandbox --readonly"
+    )]
+    Shell(RunSubcommandArgs),
+    #[command(
+        about = "Renew the shell's credentials before they expire (used by the shell hook)",
+        long_about = "Re-mint credentials for the role the current shell last exported with `roleman set`, from the credentials cache or the still-valid SSO session, and write them to the hook env file. Never opens the selector or a sign-in flow.\n\nThe shell hook runs this on its own shortly before `AWS_CREDENTIAL_EXPIRATION`; you rarely need to call it yourself.",
+        after_help = "Examples:\n  roleman renew"
+    )]
+    Renew(RenewArgs),
+    #[command(
+        name = "refresh-credentials",
+        hide = true,
+        about = "Re-mint a role's cached credentials (started in the background by `set`)"
+    )]
+    RefreshCredentials(RefreshCredentialsArgs),
+    #[command(
+        about = "Show the role the current shell is using",
+        long_about = "Report the active identity, account, role, scope, region and how long the exported credentials (and the SSO session) remain valid.\n\nReads only the shell environment and local caches, never the network.",
+        after_help = "Examples:\n  roleman status\n  roleman status --json"
+    )]
+    Status(StatusArgs),
+    #[command(
+        about = "Print a short prompt segment for the active role",
+        long_about = "Render the active role with a template, for starship, powerlevel10k or fish_prompt. Prints nothing when no role is active, and never touches the network.\n\nPlaceholders: {marker} (`*` valid, `!` stale), {state}, {identity}, {account}, {account_id}, {role}, {scope}, {readonly}, {region}, {profile}, {expires_in}, {session_expires_in}. Set `prompt_template` in config.toml to change the default.",
+        after_help = "Examples:\n  roleman prompt\n  roleman prompt --template '{account}/{role} {expires_in}'"
+    )]
+    Prompt(PromptArgs),
+}
+
+#[derive(Debug, Args)]
+struct ExecArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    #[arg(
+        value_name = "account",
+        id = "command_account",
+        help = "Configured identity name to use instead of default_identity, or a shortcut name"
+    )]
+    account: Option<String>,
+
+    #[arg(
+        last = true,
+        required = true,
+        value_name = "command",
+        help = "Command to run, after `--`"
+    )]
+    command: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct StatusArgs {
+    #[arg(long, help = "Print the status as JSON")]
+    json: bool,
+
+    #[arg(long = "config", help = "Path to config.toml")]
+    config_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct PromptArgs {
+    #[arg(
+        long,
+        value_name = "template",
+        help = "Template to render (overrides config prompt_template)"
+    )]
+    template: Option<String>,
+
+    #[arg(long = "config", help = "Path to config.toml")]
+    config_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct RenewArgs {
+    #[arg(long = "config", help = "Path to config.toml")]
+    config_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct RefreshCredentialsArgs {
+    #[arg(value_name = "target", help = "Target encoded like `_ROLEMAN_TARGET`")]
+    target: String,
+
+    #[arg(long = "config", help = "Path to config.toml")]
+    config_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct ServeArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    #[arg(
+        value_name = "account",
+        id = "command_account",
+        help = "Configured identity name to use instead of default_identity, or a shortcut name"
+    )]
+    account: Option<String>,
+
+    #[arg(
+        long = "port",
+        help = "Loopback port to listen on (defaults to a free port)"
+    )]
+    port: Option<u16>,
+}
+
+#[derive(Debug, Args)]
+struct CredentialProcessArgs {
+    #[arg(
+        short = 'a',
+        long = "account",
+        help = "Configured identity name to use instead of default_identity"
+    )]
+    account: Option<String>,
+
+    #[arg(long = "account-id", help = "AWS account ID of the role")]
+    account_id: String,
+
+    #[arg(long = "role-name", help = "Name of the IAM Identity Center role")]
+    role_name: String,
+
+    #[arg(
+        long = "readonly",
+        help = "Mint scoped-down read-only credentials for the role"
+    )]
+    readonly: bool,
+
+    #[arg(
+        short = 'y',
+        long = "yes",
+        help = "Mint full-access credentials for accounts marked `confirm = true`"
+    )]
+    yes: bool,
+
+    #[arg(long = "config", help = "Path to config.toml")]
+    config_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct CacheArgs {
+    #[command(subcommand)]
+    command: CacheSubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum CacheSubcommand {
+    #[command(about = "List cache entries")]
+    List(CacheListArgs),
+    #[command(about = "Show one cache entry (never its secrets)")]
+    Show(CacheShowArgs),
+    #[command(about = "Delete cache entries")]
+    Clear(CacheClearArgs),
+}
+
+#[derive(Debug, Args)]
+struct CacheFilterArgs {
+    #[arg(long = "kind", value_enum, help = "Only entries of this kind")]
+    kind: Option<CacheKindArg>,
+
+    #[arg(
+        short = 'a',
+        long = "account",
+        help = "Only entries of this configured identity"
+    )]
+    identity: Option<String>,
+
+    #[arg(long = "account-id", help = "Only credentials for this account ID")]
+    account_id: Option<String>,
+
+    #[arg(long = "role", help = "Only credentials for this role")]
+    role: Option<String>,
+
+    #[arg(
+        long = "expired-only",
+        help = "Only expired credentials and role lists too old to be reused"
+    )]
+    expired_only: bool,
+
+    #[arg(long = "config", help = "Path to config.toml")]
+    config_path: Option<PathBuf>,
+}
+
+impl CacheFilterArgs {
+    fn filter(&self) -> CacheFilter {
+        CacheFilter {
+            kind: self.kind.map(Into::into),
+            identity: self.identity.clone(),
+            account_id: self.account_id.clone(),
+            role_name: self.role.clone(),
+            expired_only: self.expired_only,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+struct CacheListArgs {
+    #[command(flatten)]
+    filter: CacheFilterArgs,
+
+    #[arg(long, help = "Print entries as JSON")]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct CacheShowArgs {
+    #[arg(
+        value_name = "file",
+        help = "Entry file name as listed, or a unique prefix of it"
+    )]
+    entry: String,
+
+    #[arg(long, help = "Print the entry as JSON")]
+    json: bool,
+
+    #[arg(long = "config", help = "Path to config.toml")]
+    config_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct CacheClearArgs {
+    #[command(flatten)]
+    filter: CacheFilterArgs,
+
+    #[arg(long = "dry-run", help = "List what would be deleted without deleting")]
+    dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CacheKindArg {
+    Credentials,
+    Roles,
+    Desktop,
+}
+
+impl From<CacheKindArg> for CacheKind {
+    fn from(value: CacheKindArg) -> Self {
+        match value {
+            CacheKindArg::Credentials => CacheKind::Credentials,
+            CacheKindArg::Roles => CacheKind::Roles,
+            CacheKindArg::Desktop => CacheKind::DesktopPermissions,
+        }
+    }
 }
 
 #[derive(Debug, Args)]
@@ -211,6 +518,50 @@ enum CleanupCommand {
     Roles(CleanupRolesArgs),
 }
 
+#[derive(Debug, Args)]
+struct ProfilesArgs {
+    #[command(subcommand)]
+    command: ProfilesSubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfilesSubcommand {
+    #[command(about = "Write a profile for every visible role")]
+    Sync(ProfilesChangeArgs),
+    #[command(about = "List generated profiles and whether their role still exists")]
+    List(ProfilesTargetArgs),
+    #[command(about = "Remove generated profiles whose role is no longer listed")]
+    Prune(ProfilesChangeArgs),
+}
+
+#[derive(Debug, Args)]
+struct ProfilesTargetArgs {
+    #[arg(
+        short = 'a',
+        long = "account",
+        help = "Configured identity name to use instead of default_identity"
+    )]
+    account: Option<String>,
+
+    #[arg(
+        long = "no-cache",
+        help = "Ignore the role list cache and fetch it again"
+    )]
+    no_cache: bool,
+
+    #[arg(long = "config", help = "Path to config.toml")]
+    config_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct ProfilesChangeArgs {
+    #[command(flatten)]
+    target: ProfilesTargetArgs,
+
+    #[arg(long = "dry-run", help = "Print what would change without writing")]
+    dry_run: bool,
+}
+
 #[derive(Debug, Args)]
 struct CleanupRolesArgs {
     #[arg(
@@ -244,11 +595,32 @@ struct RunSubcommandArgs {
     #[arg(
         value_name = "account",
         id = "command_account",
-        help = "Configured identity name to use instead of default_identity"
+        help = "Configured identity name to use instead of default_identity, or a shortcut name"
     )]
     account: Option<String>,
 }
 
+#[derive(Debug, Args)]
+struct SetArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+
+    #[arg(
+        value_name = "account",
+        id = "command_account",
+        help = "Configured identity name to use instead of default_identity, or a shortcut name"
+    )]
+    account: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ExportFormatArg::Shell,
+        help = "How to render the credentials; formats other than shell print to stdout instead of the hook env file"
+    )]
+    format: ExportFormatArg,
+}
+
 #[derive(Debug, Args)]
 struct ListArgs {
     #[arg(
@@ -340,6 +712,38 @@ enum OutputFormatArg {
     Json,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExportFormatArg {
+    /// POSIX `export` lines, as sourced by the shell hook
+    Shell,
+    /// NAME=value lines for dotenv loaders (not docker --env-file)
+    Dotenv,
+    /// A JSON object of names to values
+    Json,
+    /// PowerShell $env: assignments
+    Powershell,
+    /// A nushell load-env call
+    Nushell,
+    /// fish set -gx commands
+    Fish,
+    /// An .envrc snippet for direnv
+    Direnv,
+}
+
+impl From<ExportFormatArg> for ExportFormat {
+    fn from(value: ExportFormatArg) -> Self {
+        match value {
+            ExportFormatArg::Shell => ExportFormat::Shell,
+            ExportFormatArg::Dotenv => ExportFormat::Dotenv,
+            ExportFormatArg::Json => ExportFormat::Json,
+            ExportFormatArg::Powershell => ExportFormat::Powershell,
+            ExportFormatArg::Nushell => ExportFormat::Nushell,
+            ExportFormatArg::Fish => ExportFormat::Fish,
+            ExportFormatArg::Direnv => ExportFormat::Direnv,
+        }
+    }
+}
+
 impl From<SortArg> for SelectorSortMode {
     fn from(value: SortArg) -> Self {
         match value {
@@ -420,6 +824,13 @@ fn main() {
             }
             return;
         }
+        Some(CliCommand::Cache(args)) => {
+            if let Err(err) = handle_cache(args) {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+            return;
+        }
         Some(CliCommand::Cleanup(args)) => {
             if let Err(err) = handle_cleanup(args) {
                 eprintln!("error: {err}");
@@ -427,6 +838,57 @@ fn main() {
             }
             return;
         }
+        Some(CliCommand::Profiles(args)) => {
+            if let Err(err) = handle_profiles(args) {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(CliCommand::CredentialProcess(args)) => {
+            if let Err(err) = handle_credential_process(args) {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(CliCommand::Status(args)) => {
+            if let Err(err) = handle_status(&cli.common, args) {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(CliCommand::Prompt(args)) => {
+            let options = AppOptions {
+                config_path: args
+                    .config_path
+                    .clone()
+                    .or_else(|| cli.common.config_path.clone()),
+                ..AppOptions::default()
+            };
+            // A broken prompt segment should never break the prompt itself.
+            match App::new(options).prompt(args.template.as_deref()) {
+                Ok(segment) if !segment.is_empty() => println!("{segment}"),
+                Ok(_) => {}
+                Err(err) => tracing::debug!(error = %err, "failed to render prompt"),
+            }
+            return;
+        }
+        Some(CliCommand::Renew(args)) => {
+            if let Err(err) = handle_renew(&cli.common, args) {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(CliCommand::RefreshCredentials(args)) => {
+            if let Err(err) = handle_refresh_credentials(&cli.common, args) {
+                tracing::debug!(error = %err, "background refresh failed");
+                std::process::exit(1);
+            }
+            return;
+        }
         _ => {}
     }
 
@@ -445,9 +907,14 @@ fn main() {
 
     let runtime = tokio::runtime::Runtime::new().expect("failed to start runtime");
     let result = runtime.block_on(App::new(options).run());
-    if let Err(err) = result {
-        eprintln!("error: {err}");
-        std::process::exit(1);
+    match result {
+        Ok(()) => {}
+        // The command already reported its own failure; just pass the status through.
+        Err(roleman::Error::CommandExit(code)) => std::process::exit(code),
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
     }
 
     drop(_guard);
@@ -457,7 +924,10 @@ fn build_app_options(cli: &Cli) -> AppOptions {
     match &cli.command {
         Some(CliCommand::Set(args)) => {
             let common = merge_common_args(&cli.common, &args.common);
-            app_options_from_parts(&common, AppAction::Set, args.account.clone())
+            AppOptions {
+                export_format: args.format.into(),
+                ..app_options_from_parts(&common, AppAction::Set, args.account.clone())
+            }
         }
         Some(CliCommand::Open(args)) => {
             let common = merge_common_args(&cli.common, &args.common);
@@ -471,6 +941,24 @@ fn build_app_options(cli: &Cli) -> AppOptions {
             let common = merge_list_args(&cli.common, args);
             app_options_from_parts(&common, AppAction::List, args.command_account.clone())
         }
+        Some(CliCommand::Serve(args)) => {
+            let common = merge_common_args(&cli.common, &args.common);
+            AppOptions {
+                serve_port: args.port,
+                ..app_options_from_parts(&common, AppAction::Serve, args.account.clone())
+            }
+        }
+        Some(CliCommand::Exec(args)) => {
+            let common = merge_common_args(&cli.common, &args.common);
+            AppOptions {
+                command: args.command.clone(),
+                ..app_options_from_parts(&common, AppAction::Exec, args.account.clone())
+            }
+        }
+        Some(CliCommand::Shell(args)) => {
+            let common = merge_common_args(&cli.common, &args.common);
+            app_options_from_parts(&common, AppAction::Shell, args.account.clone())
+        }
         _ => app_options_from_parts(&cli.common, AppAction::Set, None),
     }
 }
@@ -493,6 +981,11 @@ fn app_options_from_parts(
         account: common.account.clone().or(positional_account),
         show_all: common.show_all,
         initial_query: common.initial_query.clone(),
+        role_filter: RoleFilter {
+            account_id: common.account_id.clone(),
+            account_name: common.account_name.clone(),
+            role_name: common.role.clone(),
+        },
         selector_sort: common.sort.map(Into::into),
         action,
         scope: if common.readonly {
@@ -501,6 +994,10 @@ fn app_options_from_parts(
             AccessScope::Full
         },
         assume_yes: common.assume_yes,
+        serve_port: None,
+        command: Vec::new(),
+        region: common.region.clone(),
+        export_format: ExportFormat::Shell,
     }
 }
 
@@ -524,6 +1021,16 @@ fn merge_common_args(parent: &CommonArgs, child: &CommonArgs) -> CommonArgs {
             .initial_query
             .clone()
             .or_else(|| parent.initial_query.clone()),
+        account_id: child
+            .account_id
+            .clone()
+            .or_else(|| parent.account_id.clone()),
+        account_name: child
+            .account_name
+            .clone()
+            .or_else(|| parent.account_name.clone()),
+        role: child.role.clone().or_else(|| parent.role.clone()),
+        region: child.region.clone().or_else(|| parent.region.clone()),
         refresh_seconds: child.refresh_seconds.or(parent.refresh_seconds),
         env_file: child.env_file.clone().or_else(|| parent.env_file.clone()),
         print_env: child.print_env || parent.print_env,
@@ -555,6 +1062,10 @@ fn merge_list_args(parent: &CommonArgs, args: &ListArgs) -> CommonArgs {
         assume_yes: parent.assume_yes,
         sort: args.sort.or(parent.sort),
         initial_query: None,
+        account_id: None,
+        account_name: None,
+        role: None,
+        region: None,
         refresh_seconds: args.refresh_seconds.or(parent.refresh_seconds),
         env_file: None,
         print_env: false,
@@ -582,10 +1093,19 @@ fn resolve_hook_shell(shell_name: Option<&str>) -> Result<&'static dyn Shell, St
     })
 }
 
+/// Variables `roleman unset` clears: every provider's credentials plus roleman's own.
+fn managed_vars() -> Vec<&'static str> {
+    provider::managed_env_vars()
+        .chain([
+            "_ROLEMAN_RENEW_AT",
+            "_ROLEMAN_TARGET",
+            aws_config::SAVED_CONFIG_FILE_VAR,
+        ])
+        .collect()
+}
+
 fn print_unset_exports() {
-    println!(
-        "unset AWS_ACCESS_KEY_ID AWS_SECRET_ACCESS_KEY AWS_SESSION_TOKEN AWS_CREDENTIAL_EXPIRATION AWS_DEFAULT_REGION AWS_REGION AWS_PROFILE"
-    );
+    print!("{}", unset_payload());
 }
 
 fn handle_unset() {
@@ -626,6 +1146,145 @@ fn handle_history(args: &HistoryArgs) -> Result<(), String> {
     Ok(())
 }
 
+fn handle_cache(args: &CacheArgs) -> Result<(), String> {
+    let config_path = match &args.command {
+        CacheSubcommand::List(list) => list.filter.config_path.clone(),
+        CacheSubcommand::Show(show) => show.config_path.clone(),
+        CacheSubcommand::Clear(clear) => clear.filter.config_path.clone(),
+    };
+    let app = App::new(AppOptions {
+        config_path,
+        ..AppOptions::default()
+    });
+    match &args.command {
+        CacheSubcommand::List(list) => {
+            let entries = app
+                .cache_entries(&list.filter.filter())
+                .map_err(|err| err.to_string())?;
+            if list.json {
+                let json = serde_json::to_string_pretty(&entries).map_err(|err| err.to_string())?;
+                println!("{json}");
+            } else if entries.is_empty() {
+                println!("No cache entries.");
+            } else {
+                print_cache_table(&entries);
+            }
+        }
+        CacheSubcommand::Show(show) => {
+            let details = app
+                .cache_entry(&show.entry)
+                .map_err(|err| err.to_string())?;
+            if show.json {
+                let json = serde_json::to_string_pretty(&details).map_err(|err| err.to_string())?;
+                println!("{json}");
+            } else {
+                print_cache_details(&details);
+            }
+        }
+        CacheSubcommand::Clear(clear) => {
+            let entries = app
+                .clear_cache(&clear.filter.filter(), clear.dry_run)
+                .map_err(|err| err.to_string())?;
+            if entries.is_empty() {
+                println!("No matching cache entries.");
+                return Ok(());
+            }
+            print_cache_table(&entries);
+            let verb = if clear.dry_run {
+                "Would delete"
+            } else {
+                "Deleted"
+            };
+            let noun = if entries.len() == 1 {
+                "entry"
+            } else {
+                "entries"
+            };
+            println!("{verb} {} cache {noun}.", entries.len());
+        }
+    }
+    Ok(())
+}
+
+fn print_cache_table(entries: &[CacheEntry]) {
+    let headers = [
+        "Kind", "Identity", "Account", "Role", "Scope", "Status", "File",
+    ];
+    let dash = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    let rows: Vec<Vec<String>> = entries
+        .iter()
+        .map(|entry| {
+            vec![
+                entry.kind.label().to_string(),
+                entry
+                    .identity
+                    .clone()
+                    .or_else(|| entry.namespace.clone())
+                    .unwrap_or_else(|| "-".to_string()),
+                dash(&entry.account_id),
+                dash(&entry.role_name),
+                dash(&entry.scope),
+                cache_entry_status(entry),
+                entry.file.clone(),
+            ]
+        })
+        .collect();
+    println!("{}", format_table(&headers, &rows));
+}
+
+fn cache_entry_status(entry: &CacheEntry) -> String {
+    match entry.kind {
+        CacheKind::Credentials if entry.expired => "expired".to_string(),
+        CacheKind::Credentials => match entry.expires_in_seconds {
+            Some(seconds) => format!("valid for {}", format_seconds(seconds)),
+            None => "valid".to_string(),
+        },
+        CacheKind::Roles => {
+            let count = entry.role_count.unwrap_or(0);
+            let age = entry
+                .age_seconds
+                .map(|seconds| format!(", fetched {} ago", format_seconds(seconds)))
+                .unwrap_or_default();
+            let stale = if entry.expired { ", stale" } else { "" };
+            format!("{count} roles{age}{stale}")
+        }
+        CacheKind::DesktopPermissions => "-".to_string(),
+    }
+}
+
+fn print_cache_details(details: &CacheEntryDetails) {
+    let entry = &details.entry;
+    let rows = [
+        ("Kind", Some(entry.kind.label().to_string())),
+        ("Identity", entry.identity.clone()),
+        ("Namespace", entry.namespace.clone()),
+        ("Account ID", entry.account_id.clone()),
+        ("Role", entry.role_name.clone()),
+        ("Scope", entry.scope.clone()),
+        ("Region", entry.region.clone()),
+        ("Issued at", details.issued_at.clone()),
+        ("Expires at", entry.expires_at.clone()),
+        ("Status", Some(cache_entry_status(entry))),
+        (
+            "Encrypted",
+            (entry.kind == CacheKind::Credentials).then(|| entry.encrypted.to_string()),
+        ),
+        ("Path", Some(details.path.clone())),
+    ];
+    for (label, value) in rows {
+        if let Some(value) = value {
+            println!("{:<12} {value}", format!("{label}:"));
+        }
+    }
+    if let Some(settings) = &details.settings {
+        println!("{:<12} {settings}", "Settings:");
+    }
+    if !details.roles.is_empty() {
+        println!();
+        print_role_table(&details.roles);
+    }
+}
+
 fn handle_cleanup(args: &CleanupArgs) -> Result<(), String> {
     let CleanupCommand::Roles(roles) = &args.command;
     let options = AppOptions {
@@ -639,6 +1298,188 @@ fn handle_cleanup(args: &CleanupArgs) -> Result<(), String> {
         .map_err(|err| err.to_string())
 }
 
+fn handle_profiles(args: &ProfilesArgs) -> Result<(), String> {
+    let target = match &args.command {
+        ProfilesSubcommand::Sync(change) | ProfilesSubcommand::Prune(change) => &change.target,
+        ProfilesSubcommand::List(target) => target,
+    };
+    let options = AppOptions {
+        account: target.account.clone(),
+        config_path: target.config_path.clone(),
+        ignore_cache: target.no_cache,
+        ..AppOptions::default()
+    };
+    let app = App::new(options);
+    let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+    let plural = |count: usize| if count == 1 { "profile" } else { "profiles" };
+    match &args.command {
+        ProfilesSubcommand::Sync(sync) => {
+            let names = runtime
+                .block_on(app.sync_profiles(sync.dry_run))
+                .map_err(|err| err.to_string())?;
+            if names.is_empty() {
+                println!("No roles available.");
+                return Ok(());
+            }
+            for name in &names {
+                println!("{name}");
+            }
+            let verb = if sync.dry_run { "Would write" } else { "Wrote" };
+            println!("{verb} {} {}.", names.len(), plural(names.len()));
+        }
+        ProfilesSubcommand::List(_) => {
+            let profiles = runtime
+                .block_on(app.list_profiles())
+                .map_err(|err| err.to_string())?;
+            if profiles.is_empty() {
+                println!("No generated profiles.");
+                return Ok(());
+            }
+            let rows: Vec<Vec<String>> = profiles
+                .iter()
+                .map(|profile| {
+                    let status = if profile.stale { "stale" } else { "current" };
+                    vec![profile.name.clone(), status.to_string()]
+                })
+                .collect();
+            println!("{}", format_table(&["Profile", "Status"], &rows));
+        }
+        ProfilesSubcommand::Prune(prune) => {
+            let names = runtime
+                .block_on(app.prune_profiles(prune.dry_run))
+                .map_err(|err| err.to_string())?;
+            if names.is_empty() {
+                println!("No stale profiles.");
+                return Ok(());
+            }
+            for name in &names {
+                println!("{name}");
+            }
+            let verb = if prune.dry_run {
+                "Would remove"
+            } else {
+                "Removed"
+            };
+            println!("{verb} {} {}.", names.len(), plural(names.len()));
+        }
+    }
+    Ok(())
+}
+
+fn handle_credential_process(args: &CredentialProcessArgs) -> Result<(), String> {
+    let options = AppOptions {
+        account: args.account.clone(),
+        config_path: args.config_path.clone(),
+        action: AppAction::CredentialProcess,
+        scope: if args.readonly {
+            AccessScope::ReadOnly
+        } else {
+            AccessScope::Full
+        },
+        assume_yes: args.yes,
+        ..AppOptions::default()
+    };
+    let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+    let json = runtime
+        .block_on(App::new(options).credential_process(&args.account_id, &args.role_name))
+        .map_err(|err| err.to_string())?;
+    println!("{json}");
+    Ok(())
+}
+
+fn handle_status(common: &CommonArgs, args: &StatusArgs) -> Result<(), String> {
+    let options = AppOptions {
+        config_path: args
+            .config_path
+            .clone()
+            .or_else(|| common.config_path.clone()),
+        ..AppOptions::default()
+    };
+    let status = App::new(options)
+        .status(true)
+        .map_err(|err| err.to_string())?;
+    if args.json {
+        let json = serde_json::to_string_pretty(&status).map_err(|err| err.to_string())?;
+        println!("{json}");
+        return Ok(());
+    }
+    if status.state == CredentialState::None {
+        println!("No active role in this shell.");
+        return Ok(());
+    }
+    let account = match (&status.account_name, &status.account_id) {
+        (Some(name), Some(id)) => Some(format!("{name} ({id})")),
+        (None, Some(id)) => Some(id.clone()),
+        _ => None,
+    };
+    let credentials = match (status.state, status.credentials_remaining_seconds) {
+        (CredentialState::Stale, Some(0)) => "expired".to_string(),
+        (CredentialState::Stale, _) => "stale".to_string(),
+        (_, Some(seconds)) => format!("valid for {}", format_seconds(seconds)),
+        _ => "valid".to_string(),
+    };
+    let session = status
+        .session_remaining_seconds
+        .map(|seconds| match seconds {
+            0 => "expired".to_string(),
+            seconds => format!("valid for {}", format_seconds(seconds)),
+        });
+    let rows = [
+        ("Identity", status.identity.clone()),
+        ("Account", account),
+        ("Role", status.role_name.clone()),
+        ("Scope", status.scope.map(str::to_string)),
+        ("Region", status.region.clone()),
+        ("Profile", status.profile.clone()),
+        ("Credentials", Some(credentials)),
+        ("SSO session", session),
+    ];
+    for (label, value) in rows {
+        if let Some(value) = value {
+            println!("{:<12} {value}", format!("{label}:"));
+        }
+    }
+    Ok(())
+}
+
+fn format_seconds(seconds: u64) -> String {
+    format_duration(std::time::Duration::from_secs(seconds))
+}
+
+fn handle_renew(common: &CommonArgs, args: &RenewArgs) -> Result<(), String> {
+    let options = AppOptions {
+        config_path: args
+            .config_path
+            .clone()
+            .or_else(|| common.config_path.clone()),
+        env_file: common.env_file.clone(),
+        action: AppAction::Renew,
+        ..AppOptions::default()
+    };
+    let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+    runtime
+        .block_on(App::new(options).renew())
+        .map_err(|err| err.to_string())
+}
+
+fn handle_refresh_credentials(
+    common: &CommonArgs,
+    args: &RefreshCredentialsArgs,
+) -> Result<(), String> {
+    let options = AppOptions {
+        config_path: args
+            .config_path
+            .clone()
+            .or_else(|| common.config_path.clone()),
+        action: AppAction::Renew,
+        ..AppOptions::default()
+    };
+    let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+    runtime
+        .block_on(App::new(options).refresh_cached_credentials(&args.target))
+        .map_err(|err| err.to_string())
+}
+
 fn handle_list(args: &ListArgs, options: AppOptions) -> Result<(), String> {
     let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
     let roles = runtime
@@ -761,8 +1602,21 @@ fn compact_home_path(path: &str) -> String {
     }
 }
 
-fn unset_payload() -> &'static str {
-    "unset AWS_ACCESS_KEY_ID AWS_SECRET_ACCESS_KEY AWS_SESSION_TOKEN AWS_CREDENTIAL_EXPIRATION AWS_DEFAULT_REGION AWS_REGION AWS_PROFILE\n"
+fn unset_payload() -> String {
+    let shell = hook_shell();
+    let mut lines = vec![shell.unset_command(&managed_vars())];
+    // Swap roleman's isolated AWS config file back for the user's own, if they had one.
+    if aws_config::isolated_config_exported() {
+        let restore = aws_config::user_config_file()
+            .and_then(|path| {
+                shell
+                    .render_exports(&[EnvVar::new("AWS_CONFIG_FILE", path)])
+                    .ok()
+            })
+            .unwrap_or_else(|| shell.unset_command(&["AWS_CONFIG_FILE"]));
+        lines.push(restore);
+    }
+    format!("{}\n", lines.join("\n"))
 }
 
 fn install_hook(force: bool, alias: bool) -> Result<(), String> {
@@ -803,7 +1657,6 @@ fn remove_hook_lines(contents: &str) -> String {
             let trimmed = line.trim();
             trimmed != "alias rl='roleman'"
                 && trimmed != "alias rl roleman"
-                && trimmed != "export _ROLEMAN_HOOK_VERSION=1"
                 && !trimmed.starts_with("eval \"$(roleman hook ")
                 && !trimmed.starts_with("roleman hook ")
                 && !trimmed.contains("_ROLEMAN_HOOK_ENV")
@@ -825,7 +1678,8 @@ fn maybe_prompt_install_hook(config_path: Option<&std::path::Path>) {
     if matches!(mode, HookPromptMode::Never) {
         return;
     }
-    if std::env::var("_ROLEMAN_HOOK_VERSION").is_ok() {
+    let hook_version = std::env::var("_ROLEMAN_HOOK_VERSION").ok();
+    if hook_version.as_deref() == Some(HOOK_VERSION) {
         return;
     }
     let Some(shell) = detect_shell_from_env() else {
@@ -835,7 +1689,7 @@ fn maybe_prompt_install_hook(config_path: Option<&std::path::Path>) {
         return;
     };
     let install_line = shell.install_line();
-    if std::env::var("_ROLEMAN_HOOK_ENV").is_ok() {
+    if hook_version.is_some() || std::env::var("_ROLEMAN_HOOK_ENV").is_ok() {
         let reload_cmd = shell.reload_command(&path);
         ui::print_warn(&format!(
             "Shell hook looks outdated. Please reload your shell: {reload_cmd}"
@@ -935,9 +1789,12 @@ fn hook_prompt_mode(config: &Config) -> HookPromptMode {
 
 #[cfg(test)]
 mod tests {
-    use super::{Cli, CliCommand, HistorySubcommand, OutputFormatArg, build_app_options};
+    use super::{
+        CacheArgs, CacheSubcommand, Cli, CliCommand, HistorySubcommand, OutputFormatArg,
+        ProfilesArgs, ProfilesSubcommand, ProfilesTargetArgs, build_app_options,
+    };
     use clap::Parser;
-    use roleman::{AppAction, RoleChoice};
+    use roleman::{AppAction, CacheKind, ExportFormat, RoleChoice};
     use std::sync::{Mutex, MutexGuard};
 
     static ENV_LOCK: Mutex<()> = Mutex::new(());
@@ -964,6 +1821,19 @@ mod tests {
         assert!(matches!(options.action, AppAction::Set));
     }
 
+    #[test]
+    fn parses_set_export_format() {
+        let cli = Cli::try_parse_from(["roleman", "set", "prod", "--format", "powershell"])
+            .expect("expected set --format to parse");
+        let options = build_app_options(&cli);
+        assert_eq!(options.account.as_deref(), Some("prod"));
+        assert_eq!(options.export_format, ExportFormat::Powershell);
+
+        let cli = Cli::try_parse_from(["roleman", "set"]).expect("expected set to parse");
+        assert_eq!(build_app_options(&cli).export_format, ExportFormat::Shell);
+        assert!(Cli::try_parse_from(["roleman", "open", "--format", "json"]).is_err());
+    }
+
     #[test]
     fn parses_open_alias_with_flag_account() {
         let cli = Cli::try_parse_from(["roleman", "o", "--account", "prod"])
@@ -1032,6 +1902,70 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_profiles_commands() {
+        let cli = Cli::try_parse_from(["roleman", "profiles", "prune", "-a", "work", "--dry-run"])
+            .expect("expected profiles prune");
+        match cli.command {
+            Some(CliCommand::Profiles(ProfilesArgs {
+                command: ProfilesSubcommand::Prune(args),
+            })) => {
+                assert!(args.dry_run);
+                assert_eq!(args.target.account.as_deref(), Some("work"));
+            }
+            _ => panic!("expected profiles prune command"),
+        }
+
+        let cli = Cli::try_parse_from(["roleman", "profiles", "list", "--no-cache"])
+            .expect("expected profiles list");
+        assert!(matches!(
+            cli.command,
+            Some(CliCommand::Profiles(ProfilesArgs {
+                command: ProfilesSubcommand::List(ProfilesTargetArgs { no_cache: true, .. }),
+            }))
+        ));
+        assert!(Cli::try_parse_from(["roleman", "profiles", "list", "--dry-run"]).is_err());
+    }
+
+    #[test]
+    fn parses_cache_commands() {
+        let cli = Cli::try_parse_from([
+            "roleman",
+            "cache",
+            "clear",
+            "--expired-only",
+            "--kind",
+            "credentials",
+            "--dry-run",
+        ])
+        .expect("expected cache clear");
+        match cli.command {
+            Some(CliCommand::Cache(CacheArgs {
+                command: CacheSubcommand::Clear(args),
+            })) => {
+                let filter = args.filter.filter();
+                assert!(filter.expired_only);
+                assert_eq!(filter.kind, Some(CacheKind::Credentials));
+                assert!(args.dry_run);
+            }
+            _ => panic!("expected cache clear command"),
+        }
+
+        let cli = Cli::try_parse_from(["roleman", "cache", "show", "creds-ab", "--json"])
+            .expect("expected cache show");
+        match cli.command {
+            Some(CliCommand::Cache(CacheArgs {
+                command: CacheSubcommand::Show(args),
+            })) => {
+                assert_eq!(args.entry, "creds-ab");
+                assert!(args.json);
+            }
+            _ => panic!("expected cache show command"),
+        }
+
+        assert!(Cli::try_parse_from(["roleman", "cache"]).is_err());
+    }
+
     #[test]
     fn parses_history_json_flag() {
         let cli = Cli::try_parse_from(["roleman", "history", "--json"])
@@ -1130,6 +2064,136 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_credential_process_command() {
+        let cli = Cli::try_parse_from([
+            "roleman",
+            "credential-process",
+            "--account",
+            "prod",
+            "--account-id",
+            "123456789012",
+            "--role-name",
+            "Admin",
+            "--readonly",
+        ])
+        .expect("expected credential-process to parse");
+        match cli.command {
+            Some(CliCommand::CredentialProcess(args)) => {
+                assert_eq!(args.account.as_deref(), Some("prod"));
+                assert_eq!(args.account_id, "123456789012");
+                assert_eq!(args.role_name, "Admin");
+                assert!(args.readonly);
+                assert!(!args.yes);
+            }
+            _ => panic!("expected credential-process command"),
+        }
+        assert!(
+            Cli::try_parse_from(["roleman", "credential-process", "--role-name", "Admin"]).is_err()
+        );
+    }
+
+    #[test]
+    fn parses_non_interactive_role_flags() {
+        let cli = Cli::try_parse_from([
+            "roleman",
+            "--account-id",
+            "123456789012",
+            "set",
+            "--role",
+            "Admin",
+        ])
+        .expect("expected role flags to parse");
+        let options = build_app_options(&cli);
+        assert_eq!(
+            options.role_filter,
+            roleman::RoleFilter {
+                account_id: Some("123456789012".into()),
+                account_name: None,
+                role_name: Some("Admin".into()),
+            }
+        );
+        assert!(!options.role_filter.is_empty());
+        let cli = Cli::try_parse_from(["roleman", "set"]).expect("expected set to parse");
+        assert!(build_app_options(&cli).role_filter.is_empty());
+    }
+
+    #[test]
+    fn parses_region_override() {
+        let cli = Cli::try_parse_from(["roleman", "--region", "eu-west-1", "set", "prod"])
+            .expect("expected --region to parse");
+        assert_eq!(build_app_options(&cli).region.as_deref(), Some("eu-west-1"));
+        let cli = Cli::try_parse_from([
+            "roleman",
+            "--region",
+            "eu-west-1",
+            "set",
+            "--region",
+            "us-east-2",
+        ])
+        .expect("expected subcommand --region to parse");
+        assert_eq!(build_app_options(&cli).region.as_deref(), Some("us-east-2"));
+    }
+
+    #[test]
+    fn parses_exec_command_after_separator() {
+        let cli = Cli::try_parse_from([
+            "roleman",
+            "exec",
+            "prod-ro",
+            "--readonly",
+            "--",
+            "aws",
+            "s3",
+            "ls",
+            "--recursive",
+        ])
+        .expect("expected exec to parse");
+        let options = build_app_options(&cli);
+        assert!(matches!(options.action, AppAction::Exec));
+        assert_eq!(options.account.as_deref(), Some("prod-ro"));
+        assert_eq!(options.command, vec!["aws", "s3", "ls", "--recursive"]);
+        assert_eq!(options.scope, roleman::AccessScope::ReadOnly);
+
+        assert!(Cli::try_parse_from(["roleman", "exec", "prod"]).is_err());
+
+        let cli = Cli::try_parse_from(["roleman", "shell", "-q", "sandbox"])
+            .expect("expected shell to parse");
+        let options = build_app_options(&cli);
+        assert!(matches!(options.action, AppAction::Shell));
+        assert!(options.command.is_empty());
+    }
+
+    #[test]
+    fn parses_status_and_prompt() {
+        let cli =
+            Cli::try_parse_from(["roleman", "status", "--json"]).expect("expected status to parse");
+        match cli.command {
+            Some(CliCommand::Status(args)) => assert!(args.json),
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        let cli = Cli::try_parse_from(["roleman", "prompt", "--template", "{account}/{role}"])
+            .expect("expected prompt to parse");
+        match cli.command {
+            Some(CliCommand::Prompt(args)) => {
+                assert_eq!(args.template.as_deref(), Some("{account}/{role}"))
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_serve_with_port() {
+        let cli = Cli::try_parse_from(["roleman", "serve", "prod", "--port", "9911", "--readonly"])
+            .expect("expected serve to parse");
+        let options = build_app_options(&cli);
+        assert!(matches!(options.action, AppAction::Serve));
+        assert_eq!(options.account.as_deref(), Some("prod"));
+        assert_eq!(options.serve_port, Some(9911));
+        assert_eq!(options.scope, roleman::AccessScope::ReadOnly);
+    }
+
     #[test]
     fn cleanup_requires_a_target() {
         // `roleman cleanup` with no subcommand is an error (must say `roles`).
@@ -1145,6 +2209,30 @@ mod tests {
         assert!(matches!(options.action, AppAction::Set));
     }
 
+    #[test]
+    fn unset_clears_every_providers_variables() {
+        let _lock = lock_env();
+        let payload = super::unset_payload();
+        let unset = payload.lines().next().unwrap();
+        for name in [
+            "AWS_ACCESS_KEY_ID",
+            "AWS_PROFILE",
+            "CLOUDSDK_AUTH_ACCESS_TOKEN",
+            "GOOGLE_OAUTH_ACCESS_TOKEN",
+            "CLOUDSDK_CORE_ACCOUNT",
+            "CLOUDSDK_CORE_PROJECT",
+            "GOOGLE_CLOUD_PROJECT",
+            "CLOUDSDK_COMPUTE_REGION",
+            "GOOGLE_CLOUD_REGION",
+            "_ROLEMAN_TARGET",
+        ] {
+            assert!(
+                unset.split_whitespace().any(|word| word == name),
+                "{name} missing from {unset}"
+            );
+        }
+    }
+
     #[test]
     fn compacts_home_prefix_to_tilde() {
         let _lock = lock_env();
@@ -1172,7 +2260,7 @@ mod tests {
 
     #[test]
     fn formats_role_table() {
-        let roles = vec![
+        let roles = [
             RoleChoice {
                 account_id: "123456789012".into(),
                 account_name: "Platform".into(),
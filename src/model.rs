@@ -42,6 +42,47 @@ impl RoleChoice {
     }
 }
 
+/// What a provider listed: the selectable targets, plus the accounts whose roles it
+/// couldn't list this time.
+#[derive(Debug, Clone, Default)]
+pub struct RoleListing {
+    pub choices: Vec<RoleChoice>,
+    pub failures: Vec<ListingFailure>,
+}
+
+/// An account whose roles failed to list.
+#[derive(Debug, Clone)]
+pub struct ListingFailure {
+    pub account_id: String,
+    pub account_name: String,
+    pub error: String,
+    /// Roles filled in from the cached list instead.
+    pub cached_roles: usize,
+}
+
+impl RoleListing {
+    /// Keep the failed accounts' roles from an earlier listing, so one failing account
+    /// doesn't drop out of the selector.
+    pub fn fill_from_cache(&mut self, cached: &[RoleChoice]) {
+        for failure in &mut self.failures {
+            let roles = cached
+                .iter()
+                .filter(|choice| choice.account_id == failure.account_id);
+            for choice in roles {
+                self.choices.push(choice.clone());
+                failure.cached_roles += 1;
+            }
+        }
+    }
+}
+
+/// A profile roleman generated, and whether its role is gone from the current role list.
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneratedProfile {
+    pub name: String,
+    pub stale: bool,
+}
+
 /// Raw role credentials as returned by the AWS SSO `GetRoleCredentials` API.
 #[derive(Debug, Deserialize)]
 pub struct AwsRoleCredentials {
@@ -54,3 +95,53 @@ pub struct AwsRoleCredentials {
     #[serde(rename = "expiration")]
     pub expiration: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choice(account_id: &str, role_name: &str) -> RoleChoice {
+        RoleChoice {
+            account_id: account_id.into(),
+            account_name: format!("Account {account_id}"),
+            role_name: role_name.into(),
+        }
+    }
+
+    #[test]
+    fn fills_failed_accounts_from_cache() {
+        let mut listing = RoleListing {
+            choices: vec![choice("111", "Admin")],
+            failures: vec![
+                ListingFailure {
+                    account_id: "222".into(),
+                    account_name: "Account 222".into(),
+                    error: "throttled".into(),
+                    cached_roles: 0,
+                },
+                ListingFailure {
+                    account_id: "333".into(),
+                    account_name: "Account 333".into(),
+                    error: "access denied".into(),
+                    cached_roles: 0,
+                },
+            ],
+        };
+        let cached = [
+            choice("111", "Stale"),
+            choice("222", "Admin"),
+            choice("222", "ReadOnly"),
+        ];
+
+        listing.fill_from_cache(&cached);
+
+        let roles: Vec<_> = listing
+            .choices
+            .iter()
+            .map(|choice| format!("{}/{}", choice.account_id, choice.role_name))
+            .collect();
+        assert_eq!(roles, ["111/Admin", "222/Admin", "222/ReadOnly"]);
+        assert_eq!(listing.failures[0].cached_roles, 2);
+        assert_eq!(listing.failures[1].cached_roles, 0);
+    }
+}
@@ -1,12 +1,23 @@
-use std::collections::HashMap;
-use std::fs;
 use std::path::{Path, PathBuf};
 
+use super::ini;
 use crate::config::SsoIdentity;
 use crate::error::{Error, Result};
 use crate::model::RoleChoice;
+use crate::provider::AccessScope;
+use crate::ui;
 
-const ROLEMAN_MANAGED_KEY: &str = "roleman_managed";
+pub(super) const ROLEMAN_MANAGED_KEY: &str = "roleman_managed";
+const CREDENTIAL_PROCESS_KEY: &str = "credential_process";
+/// Keys that make a profile resolve credentials through IAM Identity Center directly.
+const SSO_PROFILE_KEYS: [&str; 3] = ["sso_session", "sso_account_id", "sso_role_name"];
+/// Keys that make a profile assume a role from another profile (role chaining).
+const CHAINED_PROFILE_KEYS: [&str; 4] = [
+    "role_arn",
+    "source_profile",
+    "external_id",
+    "duration_seconds",
+];
 
 pub fn profile_name_for(choice: &RoleChoice, omit_role_name: bool) -> String {
     let account = sanitize_component(&choice.account_name);
@@ -23,17 +34,84 @@ pub fn ensure_sso_session(identity: &SsoIdentity) -> Result<String> {
         ("sso_start_url", identity.start_url.as_str()),
         ("sso_region", identity.sso_region.as_str()),
     ];
-    ensure_section_entries(&format!("sso-session {session}"), &entries)?;
+    let path = managed_config_path(identity)?;
+    ensure_section_entries(&path, &format!("sso-session {session}"), &entries, &[])?;
     Ok(session)
 }
 
+/// Write the profile for a role and return the config file it was written to. Identities with
+/// `credential_process` enabled get a `credential_process` entry (and lose any `sso_*` keys) so
+/// SDKs call back into roleman; on accounts marked `confirm = true` that entry always asks for
+/// read-only credentials, since `credential-process` can't ask for confirmation. Chained roles
+/// get a `role_arn` profile sourcing a `<profile>-source` SSO profile.
 pub fn ensure_role_profile(
     profile_name: &str,
     choice: &RoleChoice,
     identity: &SsoIdentity,
     region: &str,
-) -> Result<()> {
-    let session = sso_session_name(identity);
+    scope: AccessScope,
+) -> Result<PathBuf> {
+    let path = managed_config_path(identity)?;
+    if identity.credential_process {
+        let scope =
+            if scope == AccessScope::Full && identity.requires_confirmation(&choice.account_id) {
+                eprintln!(
+                    "{}",
+                    ui::warn(&format!(
+                        "Profile {profile_name} gets read-only credentials: account {} requires \
+                     confirmation for full access, which credential_process can't ask for.",
+                        choice.account_id
+                    ))
+                );
+                AccessScope::ReadOnly
+            } else {
+                scope
+            };
+        let command = credential_process_command(identity, choice, scope);
+        let entries = vec![
+            (CREDENTIAL_PROCESS_KEY, command.as_str()),
+            ("region", region),
+            (ROLEMAN_MANAGED_KEY, "true"),
+        ];
+        let stale = [SSO_PROFILE_KEYS.as_slice(), &CHAINED_PROFILE_KEYS].concat();
+        ensure_profile_entries(&path, profile_name, &entries, &stale)?;
+        return Ok(path);
+    }
+    // A cached SSO token skips login, so the session may not have been written to this file yet.
+    let session = ensure_sso_session(identity)?;
+    if let Some(chained) = identity.chained_role(&choice.account_id, &choice.role_name) {
+        let source_profile = format!("{profile_name}-source");
+        let source_entries = vec![
+            ("sso_session", session.as_str()),
+            ("sso_account_id", chained.source_account_id.as_str()),
+            ("sso_role_name", chained.source_role.as_str()),
+            ("region", region),
+            (ROLEMAN_MANAGED_KEY, "true"),
+        ];
+        ensure_profile_entries(&path, &source_profile, &source_entries, &[])?;
+
+        let duration = chained.duration_seconds.map(|seconds| seconds.to_string());
+        let mut entries = vec![
+            ("role_arn", chained.role_arn.as_str()),
+            ("source_profile", source_profile.as_str()),
+        ];
+        if let Some(external_id) = &chained.external_id {
+            entries.push(("external_id", external_id.as_str()));
+        }
+        if let Some(duration) = &duration {
+            entries.push(("duration_seconds", duration.as_str()));
+        }
+        entries.push(("region", region));
+        entries.push((ROLEMAN_MANAGED_KEY, "true"));
+        let mut stale = [SSO_PROFILE_KEYS.as_slice(), &[CREDENTIAL_PROCESS_KEY]].concat();
+        stale.extend(
+            CHAINED_PROFILE_KEYS
+                .iter()
+                .filter(|key| !entries.iter().any(|(entry, _)| entry == *key)),
+        );
+        ensure_profile_entries(&path, profile_name, &entries, &stale)?;
+        return Ok(path);
+    }
     let entries = vec![
         ("sso_session", session.as_str()),
         ("sso_account_id", choice.account_id.as_str()),
@@ -41,7 +119,48 @@ pub fn ensure_role_profile(
         ("region", region),
         (ROLEMAN_MANAGED_KEY, "true"),
     ];
-    ensure_profile_entries(profile_name, &entries)
+    let stale = [&[CREDENTIAL_PROCESS_KEY], CHAINED_PROFILE_KEYS.as_slice()].concat();
+    ensure_profile_entries(&path, profile_name, &entries, &stale)?;
+    Ok(path)
+}
+
+/// The `credential_process` command for a role: this roleman binary, with the `--config` the
+/// identity was loaded from.
+fn credential_process_command(
+    identity: &SsoIdentity,
+    choice: &RoleChoice,
+    scope: AccessScope,
+) -> String {
+    let program = std::env::current_exe()
+        .ok()
+        .and_then(|path| path.to_str().map(quote_argument))
+        .unwrap_or_else(|| "roleman".to_string());
+    let mut command = format!(
+        "{program} credential-process --account {} --account-id {} --role-name {}",
+        quote_argument(&identity.name),
+        quote_argument(&choice.account_id),
+        quote_argument(&choice.role_name)
+    );
+    if matches!(scope, AccessScope::ReadOnly) {
+        command.push_str(" --readonly");
+    }
+    if let Some(path) = identity.config_file.as_deref().and_then(Path::to_str) {
+        command.push_str(" --config ");
+        command.push_str(&quote_argument(path));
+    }
+    command
+}
+
+/// Quote an argument for the AWS SDKs' `credential_process` command-line splitting.
+fn quote_argument(value: &str) -> String {
+    if !value.is_empty()
+        && !value
+            .chars()
+            .any(|ch| ch.is_whitespace() || matches!(ch, '"' | '\'' | '\\'))
+    {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
 }
 
 fn sanitize_component(value: &str) -> String {
@@ -64,151 +183,208 @@ fn sanitize_component(value: &str) -> String {
     }
 }
 
+/// Holds the user's own `AWS_CONFIG_FILE` while roleman's isolated file is exported in its
+/// place, so switching identities or `roleman unset` can put it back.
+pub const SAVED_CONFIG_FILE_VAR: &str = "_ROLEMAN_USER_AWS_CONFIG_FILE";
+
+/// The AWS config file: the user's `AWS_CONFIG_FILE`, else `~/.aws/config`.
 pub fn aws_config_path() -> Result<PathBuf> {
+    if let Some(path) = user_config_file() {
+        return Ok(PathBuf::from(path));
+    }
     let home = std::env::var("HOME").map_err(|_| Error::MissingHome)?;
     Ok(Path::new(&home).join(".aws").join("config"))
 }
 
-fn sso_session_name(identity: &SsoIdentity) -> String {
-    format!("roleman-{}", sanitize_component(&identity.name))
+/// roleman's own AWS config file for identities with `isolated_aws_config`:
+/// `$XDG_STATE_HOME/roleman/aws-config`, else `~/.local/state/roleman/aws-config`.
+pub fn isolated_config_path() -> Result<PathBuf> {
+    let state_dir = match std::env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = std::env::var("HOME").map_err(|_| Error::MissingHome)?;
+            Path::new(&home).join(".local").join("state")
+        }
+    };
+    Ok(state_dir.join("roleman").join("aws-config"))
 }
 
-fn ensure_profile_entries(profile: &str, entries: &[(&str, &str)]) -> Result<()> {
-    ensure_section_entries(&format!("profile {profile}"), entries)
+/// Whether `AWS_CONFIG_FILE` points at roleman's isolated file, so switching to an identity
+/// without `isolated_aws_config` must export the regular config file again.
+pub fn isolated_config_exported() -> bool {
+    std::env::var_os("AWS_CONFIG_FILE")
+        .is_some_and(|path| isolated_config_path().is_ok_and(|isolated| isolated == path))
 }
 
-fn find_section(lines: &[String], header: &str) -> (Option<usize>, Option<usize>) {
-    let mut start = None;
-    for (idx, line) in lines.iter().enumerate() {
-        let trimmed = line.trim();
-        if trimmed.starts_with('[') && trimmed.ends_with(']') {
-            if let Some(section_start) = start {
-                return (Some(section_start), Some(idx));
-            }
-            if trimmed == header {
-                start = Some(idx);
-            }
-        }
-    }
-    (start, None)
+/// The user's own `AWS_CONFIG_FILE`: the exported one, or the one saved in
+/// [`SAVED_CONFIG_FILE_VAR`] while roleman's isolated file is exported instead.
+pub fn user_config_file() -> Option<String> {
+    let name = if isolated_config_exported() {
+        SAVED_CONFIG_FILE_VAR
+    } else {
+        "AWS_CONFIG_FILE"
+    };
+    std::env::var(name).ok().filter(|path| !path.is_empty())
 }
 
-fn parse_key_value(line: &str) -> Option<(String, String)> {
-    let trimmed = line.trim();
-    if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
-        return None;
+/// The file roleman writes `identity`'s `sso-session` and profiles into.
+pub fn managed_config_path(identity: &SsoIdentity) -> Result<PathBuf> {
+    if identity.isolated_aws_config {
+        isolated_config_path()
+    } else {
+        aws_config_path()
     }
-    let (key, value) = trimmed.split_once('=')?;
-    Some((key.trim().to_string(), value.trim().to_string()))
 }
 
-fn is_truthy(value: &str) -> bool {
-    matches!(value.trim().to_lowercase().as_str(), "true" | "1" | "yes")
+fn sso_session_name(identity: &SsoIdentity) -> String {
+    format!("roleman-{}", sanitize_component(&identity.name))
 }
 
-fn ensure_section_entries(section: &str, entries: &[(&str, &str)]) -> Result<()> {
-    let path = aws_config_path()?;
-    let header = format!("[{}]", section);
+fn ensure_profile_entries(
+    path: &Path,
+    profile: &str,
+    entries: &[(&str, &str)],
+    stale: &[&str],
+) -> Result<()> {
+    ensure_section_entries(path, &format!("profile {profile}"), entries, stale)
+}
 
-    let contents = fs::read_to_string(&path).unwrap_or_default();
-    let had_trailing_newline = contents.ends_with('\n');
-    let mut lines: Vec<String> = if contents.is_empty() {
-        Vec::new()
-    } else {
-        contents.lines().map(|line| line.to_string()).collect()
-    };
+/// Names of the roleman-managed profiles `identity` owns in its config file. Chained roles'
+/// `-source` profiles are left out; they go with the profile that references them.
+pub fn managed_profiles(identity: &SsoIdentity) -> Result<Vec<String>> {
+    let config = ini::read_file(&managed_config_path(identity)?)?;
+    let owned = owned_profiles(&config, identity);
+    let sources = owned
+        .iter()
+        .filter_map(|name| config.section(&format!("profile {name}")))
+        .filter_map(|section| section.get("source_profile"))
+        .collect::<Vec<_>>();
+    Ok(owned
+        .iter()
+        .filter(|name| !sources.contains(&name.as_str()))
+        .cloned()
+        .collect())
+}
 
-    let (start, end) = find_section(&lines, &header);
-    if let Some(start) = start {
-        let end = end.unwrap_or(lines.len());
-        let mut key_lines: HashMap<String, usize> = HashMap::new();
-        let mut key_values: HashMap<String, String> = HashMap::new();
-        for (idx, line) in lines.iter().enumerate().take(end).skip(start + 1) {
-            if let Some((key, value)) = parse_key_value(line) {
-                key_lines.insert(key.clone(), idx);
-                key_values.insert(key, value);
+/// Remove the named profiles (and their chained `-source` profiles) from `identity`'s config
+/// file. Names that aren't roleman-managed profiles of this identity are skipped.
+pub fn remove_profiles(identity: &SsoIdentity, names: &[String]) -> Result<()> {
+    let path = managed_config_path(identity)?;
+    ini::update_file(&path, false, |config| {
+        let owned = owned_profiles(config, identity);
+        let mut remove = Vec::new();
+        for name in names.iter().filter(|name| owned.contains(name)) {
+            let section = config.section(&format!("profile {name}"));
+            if let Some(source) = section.and_then(|section| section.get("source_profile"))
+                && owned.iter().any(|owned| owned == source)
+            {
+                remove.push(source.to_string());
             }
+            remove.push(name.clone());
         }
-        let roleman_value = key_values.get(ROLEMAN_MANAGED_KEY);
-        let managed = roleman_value.map(|value| is_truthy(value)).unwrap_or(false);
-        if let Some(value) = roleman_value
-            && !is_truthy(value)
-        {
-            return Err(Error::Config(format!(
-                "section {section} already exists and is not managed by roleman"
-            )));
+        for name in remove {
+            config.remove_section(&format!("profile {name}"));
         }
+        Ok(())
+    })
+}
 
-        if !managed {
-            let mut missing_required = Vec::new();
-            for (key, desired) in entries {
-                if *key == ROLEMAN_MANAGED_KEY {
-                    continue;
-                }
-                match key_values.get(*key) {
-                    Some(existing) if existing == desired => {}
-                    Some(_) => {
-                        return Err(Error::Config(format!(
-                            "section {section} already exists and is not managed by roleman"
-                        )));
-                    }
-                    None => missing_required.push(*key),
-                }
-            }
-            if !missing_required.is_empty() {
-                return Err(Error::Config(format!(
-                    "section {section} already exists and is not managed by roleman"
-                )));
-            }
+/// Profiles whose every section is roleman-managed and belongs to `identity`, in file order.
+fn owned_profiles(config: &ini::Ini, identity: &SsoIdentity) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for section in config.sections() {
+        let Some(name) = section.name().strip_prefix("profile ") else {
+            continue;
+        };
+        let name = name.trim();
+        let ours = config
+            .sections()
+            .filter(|other| other.name() == section.name())
+            .all(|other| is_managed(other) && belongs_to(config, other, identity));
+        if ours && !names.iter().any(|existing| existing == name) {
+            names.push(name.to_string());
         }
+    }
+    names
+}
 
-        for (key, value) in entries {
-            if let Some(idx) = key_lines.get(*key) {
-                lines[*idx] = format!("{key} = {value}");
-            }
-        }
+/// Whether a profile was generated for `identity`: it uses the identity's `sso-session`, calls
+/// `roleman credential-process` for it, or chains from a profile that uses its session. The
+/// roleman path in front of `credential-process` is not compared, since it moves with the binary.
+fn belongs_to(config: &ini::Ini, section: &ini::Section, identity: &SsoIdentity) -> bool {
+    let session = sso_session_name(identity);
+    let uses_session = |section: &ini::Section| section.get("sso_session") == Some(&session);
+    if uses_session(section) {
+        return true;
+    }
+    if let Some(command) = section.get(CREDENTIAL_PROCESS_KEY) {
+        let arguments = format!(
+            " credential-process --account {} ",
+            quote_argument(&identity.name)
+        );
+        return command.contains(&arguments);
+    }
+    section
+        .get("source_profile")
+        .and_then(|source| config.section(&format!("profile {source}")))
+        .is_some_and(uses_session)
+}
 
-        let missing = entries
-            .iter()
-            .filter(|(key, _)| !key_lines.contains_key(*key))
-            .map(|(key, value)| format!("{key} = {value}"))
-            .collect::<Vec<_>>();
-        if !missing.is_empty() {
-            let mut out = Vec::with_capacity(lines.len() + missing.len());
-            for (idx, line) in lines.iter().enumerate() {
-                if idx == end {
-                    out.extend(missing.iter().cloned());
-                }
-                out.push(line.clone());
-            }
-            if end == lines.len() {
-                out.extend(missing);
-            }
-            lines = out;
-        }
-    } else {
-        if !lines.is_empty() && lines.last().is_some_and(|line| !line.trim().is_empty()) {
-            lines.push(String::new());
+pub(super) fn is_managed(section: &ini::Section) -> bool {
+    section.get(ROLEMAN_MANAGED_KEY).is_some_and(is_truthy)
+}
+
+pub(super) fn is_truthy(value: &str) -> bool {
+    matches!(value.trim().to_lowercase().as_str(), "true" | "1" | "yes")
+}
+
+/// Upsert `entries` into `section`, dropping any `stale` keys left over from a previous
+/// layout of the same section. Everything else in the file is left as written.
+fn ensure_section_entries(
+    path: &Path,
+    section: &str,
+    entries: &[(&str, &str)],
+    stale: &[&str],
+) -> Result<()> {
+    ini::update_file(path, false, |config| {
+        if let Some(existing) = config.section(section) {
+            check_adoptable(section, existing, entries)?;
         }
-        lines.push(header);
+        let target = config.section_or_insert(section);
         for (key, value) in entries {
-            lines.push(format!("{key} = {value}"));
+            target.set(key, value);
         }
-    }
+        for key in stale {
+            target.remove(key);
+        }
+        Ok(())
+    })
+}
 
-    let mut output = lines.join("\n");
-    if had_trailing_newline || (!output.is_empty() && !output.ends_with('\n')) {
-        output.push('\n');
-    }
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|err| Error::Config(err.to_string()))?;
+/// An existing section may be rewritten when roleman manages it, or when it already holds
+/// exactly the entries roleman would write (so adopting it changes nothing the user set).
+fn check_adoptable(section: &str, existing: &ini::Section, entries: &[(&str, &str)]) -> Result<()> {
+    let not_managed = || {
+        Error::Config(format!(
+            "section {section} already exists and is not managed by roleman"
+        ))
+    };
+    match existing.get(ROLEMAN_MANAGED_KEY) {
+        Some(value) if is_truthy(value) => Ok(()),
+        Some(_) => Err(not_managed()),
+        None => entries
+            .iter()
+            .filter(|(key, _)| *key != ROLEMAN_MANAGED_KEY)
+            .all(|(key, desired)| existing.get(key) == Some(*desired))
+            .then_some(())
+            .ok_or_else(not_managed),
     }
-    fs::write(&path, output).map_err(|err| Error::Config(err.to_string()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
     use tempfile::TempDir;
 
     #[test]
@@ -234,6 +410,7 @@ mod tests {
         let previous = std::env::var("HOME").ok();
         unsafe {
             std::env::set_var("HOME", temp.path());
+            std::env::remove_var("AWS_CONFIG_FILE");
         }
 
         let identity = SsoIdentity {
@@ -242,6 +419,16 @@ mod tests {
             sso_region: "us-east-1".into(),
             provider: crate::config::ProviderKind::Aws,
             readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
             accounts: Vec::new(),
             ignore_roles: Vec::new(),
         };
@@ -253,7 +440,14 @@ mod tests {
         let session = ensure_sso_session(&identity).unwrap();
         assert_eq!(session, "roleman-work");
         let profile_name = profile_name_for(&choice, false);
-        ensure_role_profile(&profile_name, &choice, &identity, "us-east-1").unwrap();
+        ensure_role_profile(
+            &profile_name,
+            &choice,
+            &identity,
+            "us-east-1",
+            AccessScope::Full,
+        )
+        .unwrap();
         let config_path = aws_config_path().unwrap();
         let contents = fs::read_to_string(config_path).unwrap();
         assert!(contents.contains("[sso-session roleman-work]"));
@@ -274,4 +468,383 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn writes_chained_profile_with_sso_source() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("HOME").ok();
+        unsafe {
+            std::env::set_var("HOME", temp.path());
+            std::env::remove_var("AWS_CONFIG_FILE");
+        }
+
+        let identity = SsoIdentity {
+            name: "work".into(),
+            start_url: "https://example.awsapps.com/start".into(),
+            sso_region: "us-east-1".into(),
+            provider: crate::config::ProviderKind::Aws,
+            readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: vec![crate::config::ChainedRole {
+                name: "deployer".into(),
+                source_account_id: "1111".into(),
+                source_role: "Admin".into(),
+                role_arn: "arn:aws:iam::2222:role/Deployer".into(),
+                external_id: Some("acme".into()),
+                duration_seconds: None,
+            }],
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
+            accounts: Vec::new(),
+            ignore_roles: Vec::new(),
+        };
+        let choice = RoleChoice {
+            account_id: "2222".into(),
+            account_name: "Workloads".into(),
+            role_name: "deployer".into(),
+        };
+        let profile_name = profile_name_for(&choice, false);
+        ensure_role_profile(
+            &profile_name,
+            &choice,
+            &identity,
+            "eu-west-1",
+            AccessScope::Full,
+        )
+        .unwrap();
+        let contents = fs::read_to_string(aws_config_path().unwrap()).unwrap();
+        assert!(contents.contains(
+            "[profile Workloads/deployer]\n\
+             role_arn = arn:aws:iam::2222:role/Deployer\n\
+             source_profile = Workloads/deployer-source\n\
+             external_id = acme\n\
+             region = eu-west-1\n"
+        ));
+        assert!(contents.contains(
+            "[profile Workloads/deployer-source]\n\
+             sso_session = roleman-work\n\
+             sso_account_id = 1111\n\
+             sso_role_name = Admin\n"
+        ));
+        assert!(!contents.contains("duration_seconds"));
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("HOME", value);
+            } else {
+                std::env::remove_var("HOME");
+            }
+        }
+    }
+
+    #[test]
+    fn guarded_accounts_get_read_only_credential_process_profiles() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("HOME").ok();
+        unsafe {
+            std::env::set_var("HOME", temp.path());
+            std::env::remove_var("AWS_CONFIG_FILE");
+        }
+
+        let identity = SsoIdentity {
+            name: "work".into(),
+            start_url: "https://example.awsapps.com/start".into(),
+            sso_region: "us-east-1".into(),
+            provider: crate::config::ProviderKind::Aws,
+            readonly_policy: None,
+            credential_process: true,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
+            accounts: vec![crate::config::AccountRule {
+                account_id: "1234".into(),
+                alias: Some("prod".into()),
+                ignored: false,
+                ignored_roles: Vec::new(),
+                precedence: None,
+                region: None,
+                role_regions: Default::default(),
+                confirm: true,
+            }],
+            ignore_roles: Vec::new(),
+        };
+        let choice = RoleChoice {
+            account_id: "1234".into(),
+            account_name: "Prod".into(),
+            role_name: "Admin".into(),
+        };
+        let profile_name = profile_name_for(&choice, false);
+        ensure_role_profile(
+            &profile_name,
+            &choice,
+            &identity,
+            "us-east-1",
+            AccessScope::Full,
+        )
+        .unwrap();
+        let contents = fs::read_to_string(aws_config_path().unwrap()).unwrap();
+        assert!(contents.contains("--role-name Admin --readonly"));
+        // Which is what `credential-process` accepts without `--yes`; full scope it refuses.
+        assert!(
+            crate::require_unattended_consent(
+                &identity,
+                "1234",
+                AccessScope::ReadOnly,
+                false,
+                "credential-process"
+            )
+            .is_ok()
+        );
+        assert!(
+            crate::require_unattended_consent(
+                &identity,
+                "1234",
+                AccessScope::Full,
+                false,
+                "credential-process"
+            )
+            .is_err()
+        );
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("HOME", value);
+            } else {
+                std::env::remove_var("HOME");
+            }
+        }
+    }
+
+    #[test]
+    fn switches_profile_to_credential_process() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("HOME").ok();
+        unsafe {
+            std::env::set_var("HOME", temp.path());
+            std::env::remove_var("AWS_CONFIG_FILE");
+        }
+
+        let mut identity = SsoIdentity {
+            name: "my work".into(),
+            start_url: "https://example.awsapps.com/start".into(),
+            sso_region: "us-east-1".into(),
+            provider: crate::config::ProviderKind::Aws,
+            readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
+            accounts: Vec::new(),
+            ignore_roles: Vec::new(),
+        };
+        let choice = RoleChoice {
+            account_id: "1234".into(),
+            account_name: "Acme Cloud".into(),
+            role_name: "Admin".into(),
+        };
+        let profile_name = profile_name_for(&choice, false);
+        ensure_role_profile(
+            &profile_name,
+            &choice,
+            &identity,
+            "us-east-1",
+            AccessScope::Full,
+        )
+        .unwrap();
+
+        identity.credential_process = true;
+        identity.config_file = Some(PathBuf::from("/home/me/roleman config.toml"));
+        ensure_role_profile(
+            &profile_name,
+            &choice,
+            &identity,
+            "us-east-1",
+            AccessScope::ReadOnly,
+        )
+        .unwrap();
+        let contents = fs::read_to_string(aws_config_path().unwrap()).unwrap();
+        // The test binary stands in for roleman here.
+        let program = quote_argument(std::env::current_exe().unwrap().to_str().unwrap());
+        assert!(contents.contains(&format!(
+            "credential_process = {program} credential-process --account \"my work\" \
+             --account-id 1234 --role-name Admin --readonly \
+             --config \"/home/me/roleman config.toml\""
+        )));
+        assert!(!contents.contains("sso_session = "));
+        assert!(!contents.contains("sso_account_id"));
+        assert!(contents.contains("region = us-east-1"));
+        assert!(contents.contains("roleman_managed = true"));
+
+        // Turning the option back off restores the SSO keys and drops the hook.
+        identity.credential_process = false;
+        ensure_role_profile(
+            &profile_name,
+            &choice,
+            &identity,
+            "us-east-1",
+            AccessScope::Full,
+        )
+        .unwrap();
+        let contents = fs::read_to_string(aws_config_path().unwrap()).unwrap();
+        assert!(!contents.contains("credential_process"));
+        assert!(contents.contains("sso_role_name = Admin"));
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("HOME", value);
+            } else {
+                std::env::remove_var("HOME");
+            }
+        }
+    }
+
+    #[test]
+    fn quotes_arguments_the_sdks_would_split() {
+        assert_eq!(quote_argument("Admin"), "Admin");
+        assert_eq!(quote_argument(""), "\"\"");
+        assert_eq!(quote_argument("my work"), "\"my work\"");
+        assert_eq!(quote_argument("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(
+            quote_argument(r"C:\Program Files\roleman.exe"),
+            r#""C:\\Program Files\\roleman.exe""#
+        );
+    }
+
+    #[test]
+    fn isolated_identity_leaves_aws_config_alone() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous_home = std::env::var("HOME").ok();
+        let previous_state = std::env::var("XDG_STATE_HOME").ok();
+        unsafe {
+            std::env::set_var("HOME", temp.path());
+            std::env::set_var("XDG_STATE_HOME", temp.path().join("state"));
+            std::env::remove_var("AWS_CONFIG_FILE");
+        }
+
+        let identity: SsoIdentity = toml::from_str(
+            "name = \"work\"\nstart_url = \"https://example.awsapps.com/start\"\n\
+             sso_region = \"us-east-1\"\nisolated_aws_config = true\n",
+        )
+        .unwrap();
+        let choice = RoleChoice {
+            account_id: "1234".into(),
+            account_name: "Acme Cloud".into(),
+            role_name: "Admin".into(),
+        };
+        let path = ensure_role_profile(
+            "Acme-Cloud/Admin",
+            &choice,
+            &identity,
+            "us-east-1",
+            AccessScope::Full,
+        )
+        .unwrap();
+        assert_eq!(path, temp.path().join("state/roleman/aws-config"));
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("[sso-session roleman-work]"));
+        assert!(contents.contains("[profile Acme-Cloud/Admin]"));
+        assert!(!temp.path().join(".aws/config").exists());
+
+        // roleman's own export is not mistaken for the user's AWS_CONFIG_FILE.
+        unsafe { std::env::set_var("AWS_CONFIG_FILE", &path) };
+        assert!(isolated_config_exported());
+        assert_eq!(aws_config_path().unwrap(), temp.path().join(".aws/config"));
+        // The user's own file, saved aside while the isolated one is exported.
+        let custom = temp.path().join("custom-config");
+        unsafe { std::env::set_var(SAVED_CONFIG_FILE_VAR, &custom) };
+        assert_eq!(aws_config_path().unwrap(), custom);
+        unsafe { std::env::remove_var(SAVED_CONFIG_FILE_VAR) };
+        unsafe { std::env::set_var("AWS_CONFIG_FILE", &custom) };
+        assert!(!isolated_config_exported());
+        assert_eq!(aws_config_path().unwrap(), custom);
+
+        unsafe {
+            std::env::remove_var("AWS_CONFIG_FILE");
+            match previous_home {
+                Some(value) => std::env::set_var("HOME", value),
+                None => std::env::remove_var("HOME"),
+            }
+            match previous_state {
+                Some(value) => std::env::set_var("XDG_STATE_HOME", value),
+                None => std::env::remove_var("XDG_STATE_HOME"),
+            }
+        }
+    }
+
+    #[test]
+    fn lists_and_removes_only_its_own_profiles() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join("config");
+        let previous = std::env::var("AWS_CONFIG_FILE").ok();
+        unsafe { std::env::set_var("AWS_CONFIG_FILE", &config_path) };
+        fs::write(
+            &config_path,
+            "[profile mine]\nregion = eu-west-1\n\n\
+             [profile Acme/Admin]\nsso_session = roleman-work\nroleman_managed = true\n\n\
+             [profile Acme/Deploy-source]\nsso_session = roleman-work\nroleman_managed = true\n\
+             [profile Acme/Deploy]\nrole_arn = arn:aws:iam::1:role/Deploy\n\
+             source_profile = Acme/Deploy-source\nroleman_managed = true\n\n\
+             [profile Acme/Tool]\ncredential_process = roleman credential-process --account work \
+             --account-id 1 --role-name Tool\nroleman_managed = true\n\n\
+             [profile Other/Admin]\nsso_session = roleman-other\nroleman_managed = true\n\n\
+             [profile Acme/Kept]\nsso_session = roleman-work\nroleman_managed = false\n",
+        )
+        .unwrap();
+        let identity: SsoIdentity = toml::from_str(
+            "name = \"work\"\nstart_url = \"https://example.awsapps.com/start\"\n\
+             sso_region = \"us-east-1\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            managed_profiles(&identity).unwrap(),
+            ["Acme/Admin", "Acme/Deploy", "Acme/Tool"]
+        );
+        let names = [
+            "Acme/Deploy",
+            "Acme/Tool",
+            "Other/Admin",
+            "Acme/Kept",
+            "mine",
+        ]
+        .map(String::from);
+        remove_profiles(&identity, &names).unwrap();
+        assert_eq!(
+            fs::read_to_string(&config_path).unwrap(),
+            "[profile mine]\nregion = eu-west-1\n\n\
+             [profile Acme/Admin]\nsso_session = roleman-work\nroleman_managed = true\n\n\
+             [profile Other/Admin]\nsso_session = roleman-other\nroleman_managed = true\n\n\
+             [profile Acme/Kept]\nsso_session = roleman-work\nroleman_managed = false\n"
+        );
+
+        unsafe {
+            match previous {
+                Some(value) => std::env::set_var("AWS_CONFIG_FILE", value),
+                None => std::env::remove_var("AWS_CONFIG_FILE"),
+            }
+        }
+    }
 }
@@ -0,0 +1,294 @@
+//! Roleman-managed sections of the AWS shared credentials file (`~/.aws/credentials`).
+//!
+//! Some tools (older SDKs, Docker Desktop, JetBrains IDEs) only read static keys from the shared
+//! credentials file. Identities with `shared_credentials_file = true` get a section named after
+//! the exported profile, holding the minted keys plus `roleman_managed = true` and
+//! `roleman_expiration`. Every write, and every `set` or renewal for another AWS identity,
+//! also drops roleman sections whose credentials have expired. Sections without the marker are
+//! never modified, and everything outside the sections roleman rewrites is kept byte for byte.
+//!
+//! Edits go through [`ini::update_file`], so concurrent writers serialize and readers such as
+//! the AWS CLI never see a half-written file. The file is always written with mode 0600.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::config::{ROLEMAN_MANAGED_KEY, is_managed};
+use super::ini::{self, Ini, Section};
+use crate::error::{Error, Result};
+
+/// RFC 3339 expiry of the keys in a roleman section; cleanup removes the section after it.
+const ROLEMAN_EXPIRATION_KEY: &str = "roleman_expiration";
+
+/// The shared credentials file: `AWS_SHARED_CREDENTIALS_FILE`, else `~/.aws/credentials`.
+pub fn shared_credentials_path() -> Result<PathBuf> {
+    if let Ok(path) = std::env::var("AWS_SHARED_CREDENTIALS_FILE")
+        && !path.is_empty()
+    {
+        return Ok(PathBuf::from(path));
+    }
+    let home = std::env::var("HOME").map_err(|_| Error::MissingHome)?;
+    Ok(Path::new(&home).join(".aws").join("credentials"))
+}
+
+/// Keys written into a roleman section of the shared credentials file.
+pub struct SharedCredentials<'a> {
+    pub access_key_id: &'a str,
+    pub secret_access_key: &'a str,
+    pub session_token: &'a str,
+    /// RFC 3339 expiry, as exported in `AWS_CREDENTIAL_EXPIRATION`.
+    pub expiration: &'a str,
+}
+
+/// Write `credentials` into the `[profile]` section and drop expired roleman sections.
+///
+/// Fails without touching the file when `[profile]` exists and isn't managed by roleman.
+pub fn write_credentials(profile: &str, credentials: &SharedCredentials<'_>) -> Result<()> {
+    let path = shared_credentials_path()?;
+    ini::update_file(&path, true, |document| {
+        remove_expired(document, now_secs(), Some(profile));
+        upsert_managed(
+            document,
+            profile,
+            &[
+                ("aws_access_key_id", credentials.access_key_id),
+                ("aws_secret_access_key", credentials.secret_access_key),
+                ("aws_session_token", credentials.session_token),
+                (ROLEMAN_EXPIRATION_KEY, credentials.expiration),
+            ],
+        )
+    })
+}
+
+/// Drop expired roleman sections without writing any. The file is only locked and rewritten
+/// when it holds one, so this is cheap to call for identities that don't write keys.
+pub fn remove_expired_sections() -> Result<()> {
+    let path = shared_credentials_path()?;
+    let now = now_secs();
+    let has_expired = ini::read_file(&path)?
+        .sections()
+        .any(|section| is_managed(section) && is_expired(section, now));
+    if !has_expired {
+        return Ok(());
+    }
+    ini::update_file(&path, true, |document| {
+        remove_expired(document, now, None);
+        Ok(())
+    })
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Drop roleman sections that expired at or before `now`, other than `keep`. A name is only
+/// removed when every section carrying it is an expired roleman section.
+fn remove_expired(document: &mut Ini, now: u64, keep: Option<&str>) {
+    let expired = document
+        .sections()
+        .filter(|section| Some(section.name()) != keep)
+        .filter(|section| is_managed(section) && is_expired(section, now))
+        .map(|section| section.name().to_string())
+        .collect::<Vec<_>>();
+    for name in expired {
+        let all_expired = document
+            .sections()
+            .filter(|section| section.name() == name)
+            .all(|section| is_managed(section) && is_expired(section, now));
+        if all_expired {
+            document.remove_section(&name);
+        }
+    }
+}
+
+/// Make the managed `[name]` section hold exactly `entries`, appending it when missing.
+fn upsert_managed(document: &mut Ini, name: &str, entries: &[(&str, &str)]) -> Result<()> {
+    if let Some(section) = document.section(name)
+        && !is_managed(section)
+    {
+        return Err(Error::Config(format!(
+            "section [{name}] in the shared credentials file already exists and is not managed by roleman"
+        )));
+    }
+    let section = document.section_or_insert(name);
+    section.set(ROLEMAN_MANAGED_KEY, "true");
+    for (key, value) in entries {
+        section.set(key, value);
+    }
+    let stale = section
+        .keys()
+        .filter(|key| *key != ROLEMAN_MANAGED_KEY && !entries.iter().any(|(entry, _)| entry == key))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    for key in stale {
+        section.remove(&key);
+    }
+    Ok(())
+}
+
+/// Whether the section's `roleman_expiration` has passed; sections without a readable
+/// expiry are kept.
+fn is_expired(section: &Section, now: u64) -> bool {
+    section
+        .get(ROLEMAN_EXPIRATION_KEY)
+        .and_then(|value| {
+            time::OffsetDateTime::parse(value, &time::format_description::well_known::Rfc3339).ok()
+        })
+        .is_some_and(|expiry| expiry.unix_timestamp() <= now as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const NOW: u64 = 1_760_000_000;
+    const PAST: &str = "2025-10-09T08:00:00Z";
+    const FUTURE: &str = "2025-10-09T10:00:00Z";
+
+    fn upsert(contents: &str, name: &str, expiration: &str) -> Result<String> {
+        let mut document = Ini::parse(contents);
+        remove_expired(&mut document, NOW, Some(name));
+        upsert_managed(
+            &mut document,
+            name,
+            &[
+                ("aws_access_key_id", "ASIANEW"),
+                (ROLEMAN_EXPIRATION_KEY, expiration),
+            ],
+        )?;
+        Ok(document.render())
+    }
+
+    #[test]
+    fn appends_a_managed_section_and_keeps_the_rest() {
+        let contents = "# my keys\n[default]\naws_access_key_id = AKIAUSER ; inline\n";
+        assert_eq!(
+            upsert(contents, "Acme/Admin", FUTURE).unwrap(),
+            format!(
+                "{contents}\n[Acme/Admin]\nroleman_managed = true\naws_access_key_id = ASIANEW\nroleman_expiration = {FUTURE}\n"
+            )
+        );
+        assert_eq!(
+            upsert("", "Acme/Admin", FUTURE).unwrap(),
+            format!(
+                "[Acme/Admin]\nroleman_managed = true\naws_access_key_id = ASIANEW\nroleman_expiration = {FUTURE}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn rewrites_its_own_section_in_place() {
+        let contents = format!(
+            "[Acme/Admin]\nroleman_managed = true\naws_access_key_id = ASIAOLD\nroleman_expiration = {PAST}\n\n# user keys\n[default]\naws_access_key_id = AKIAUSER"
+        );
+        assert_eq!(
+            upsert(&contents, "Acme/Admin", FUTURE).unwrap(),
+            format!(
+                "[Acme/Admin]\nroleman_managed = true\naws_access_key_id = ASIANEW\nroleman_expiration = {FUTURE}\n\n# user keys\n[default]\naws_access_key_id = AKIAUSER"
+            )
+        );
+    }
+
+    #[test]
+    fn refuses_sections_it_does_not_manage() {
+        let contents = "[Acme/Admin]\naws_access_key_id = AKIAUSER\n";
+        assert!(upsert(contents, "Acme/Admin", FUTURE).is_err());
+        let contents = "[Acme/Admin]\nroleman_managed = false\n";
+        assert!(upsert(contents, "Acme/Admin", FUTURE).is_err());
+    }
+
+    #[test]
+    fn drops_expired_roleman_sections_only() {
+        let contents = format!(
+            "[old]\nroleman_managed = true\nroleman_expiration = {PAST}\n# keep me\n\n[fresh]\nroleman_managed = true\nroleman_expiration = {FUTURE}\n\n[mine]\nroleman_expiration = {PAST}\n\n[odd]\nroleman_managed = true\nroleman_expiration = soon\n"
+        );
+        let mut document = Ini::parse(&contents);
+        remove_expired(&mut document, NOW, None);
+        assert_eq!(
+            document.render(),
+            format!(
+                "# keep me\n\n[fresh]\nroleman_managed = true\nroleman_expiration = {FUTURE}\n\n[mine]\nroleman_expiration = {PAST}\n\n[odd]\nroleman_managed = true\nroleman_expiration = soon\n"
+            )
+        );
+    }
+
+    #[test]
+    fn cleanup_only_rewrites_files_with_expired_sections() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("credentials");
+        let previous = std::env::var("AWS_SHARED_CREDENTIALS_FILE").ok();
+        unsafe { std::env::set_var("AWS_SHARED_CREDENTIALS_FILE", &path) };
+
+        remove_expired_sections().unwrap();
+        assert!(!path.exists());
+        assert!(!temp.path().join("credentials.roleman-lock").exists());
+
+        fs::write(
+            &path,
+            format!(
+                "[default]\naws_access_key_id = AKIAUSER\n\n\
+                 [old]\nroleman_managed = true\nroleman_expiration = {PAST}\n"
+            ),
+        )
+        .unwrap();
+        remove_expired_sections().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[default]\naws_access_key_id = AKIAUSER\n\n"
+        );
+
+        unsafe {
+            match previous {
+                Some(value) => std::env::set_var("AWS_SHARED_CREDENTIALS_FILE", value),
+                None => std::env::remove_var("AWS_SHARED_CREDENTIALS_FILE"),
+            }
+        }
+    }
+
+    #[test]
+    fn writes_privately_through_symlinks() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let real = temp.path().join("dotfiles-credentials");
+        fs::write(&real, "[default]\naws_access_key_id = AKIAUSER\n").unwrap();
+        let link = temp.path().join("credentials");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        #[cfg(not(unix))]
+        fs::copy(&real, &link).unwrap();
+        let previous = std::env::var("AWS_SHARED_CREDENTIALS_FILE").ok();
+        unsafe { std::env::set_var("AWS_SHARED_CREDENTIALS_FILE", &link) };
+
+        let credentials = SharedCredentials {
+            access_key_id: "ASIANEW",
+            secret_access_key: "secret",
+            session_token: "token",
+            expiration: "2999-01-01T00:00:00Z",
+        };
+        write_credentials("Acme/Admin", &credentials).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+            let mode = fs::metadata(&real).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let contents = fs::read_to_string(&link).unwrap();
+        assert!(contents.starts_with("[default]\naws_access_key_id = AKIAUSER\n\n[Acme/Admin]\n"));
+        assert!(contents.contains("aws_session_token = token\n"));
+
+        unsafe {
+            match previous {
+                Some(value) => std::env::set_var("AWS_SHARED_CREDENTIALS_FILE", value),
+                None => std::env::remove_var("AWS_SHARED_CREDENTIALS_FILE"),
+            }
+        }
+    }
+}
@@ -0,0 +1,622 @@
+//! A round-tripping model of the INI dialect the AWS config and credentials files use.
+//!
+//! The files are hand-edited, so roleman must change only what it means to: parsing and
+//! rendering an unmodified document gives back the same bytes, and edits leave every other line
+//! (comments, blank lines, spacing, unknown keys) exactly as it was.
+//!
+//! Dialect: `[section]` headers, `key = value` properties, and `#`/`;` comment lines. An
+//! indented line after a property continues it; for a property with an empty value those lines
+//! are nested sub-properties, as in
+//!
+//! ```ini
+//! [profile dev]
+//! s3 =
+//!     max_concurrent_requests = 20
+//!     addressing_style = path
+//! ```
+//!
+//! [`update_file`] applies an edit under an advisory lock and replaces the file atomically.
+
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::util::write_atomic;
+
+/// A parsed INI file: lines before the first section, then the sections in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ini {
+    preamble: Vec<Line>,
+    sections: Vec<Section>,
+    trailing_newline: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    name: String,
+    header: String,
+    body: Vec<Line>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// Blank lines, comments and anything else that isn't a property, kept verbatim.
+    Other(String),
+    Property(Property),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Property {
+    key: String,
+    value: String,
+    /// The `key = value` line as written.
+    raw: String,
+    /// Indented lines continuing the value, or nested sub-properties when `value` is empty.
+    continuation: Vec<String>,
+}
+
+impl Ini {
+    pub fn parse(contents: &str) -> Self {
+        let mut ini = Self {
+            preamble: Vec::new(),
+            sections: Vec::new(),
+            trailing_newline: contents.is_empty() || contents.ends_with('\n'),
+        };
+        let mut lines = contents.split('\n').collect::<Vec<_>>();
+        if ini.trailing_newline {
+            // The empty string after the final newline isn't a line.
+            lines.pop();
+        }
+        for line in lines {
+            if let Some(name) = section_name(line) {
+                ini.sections.push(Section {
+                    name: name.to_string(),
+                    header: line.to_string(),
+                    body: Vec::new(),
+                });
+                continue;
+            }
+            let body = match ini.sections.last_mut() {
+                Some(section) => &mut section.body,
+                None => &mut ini.preamble,
+            };
+            if is_continuation(line)
+                && let Some(Line::Property(property)) = body.last_mut()
+            {
+                property.continuation.push(line.to_string());
+                continue;
+            }
+            body.push(match parse_property(line) {
+                Some((key, value)) => Line::Property(Property {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                    raw: line.to_string(),
+                    continuation: Vec::new(),
+                }),
+                None => Line::Other(line.to_string()),
+            });
+        }
+        ini
+    }
+
+    pub fn render(&self) -> String {
+        let mut lines = Vec::new();
+        render_lines(&self.preamble, &mut lines);
+        for section in &self.sections {
+            lines.push(section.header.as_str());
+            render_lines(&section.body, &mut lines);
+        }
+        let mut out = lines.join("\n");
+        if self.trailing_newline && !lines.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &Section> {
+        self.sections.iter()
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    pub fn section_mut(&mut self, name: &str) -> Option<&mut Section> {
+        self.sections
+            .iter_mut()
+            .find(|section| section.name == name)
+    }
+
+    /// The section called `name`, appended (after a blank line) when it doesn't exist yet.
+    pub fn section_or_insert(&mut self, name: &str) -> &mut Section {
+        let index = match self
+            .sections
+            .iter()
+            .position(|section| section.name == name)
+        {
+            Some(index) => index,
+            None => {
+                let last = match self.sections.last_mut() {
+                    Some(section) => &mut section.body,
+                    None => &mut self.preamble,
+                };
+                if last.last().is_some_and(
+                    |line| !matches!(line, Line::Other(text) if text.trim().is_empty()),
+                ) {
+                    last.push(Line::Other(String::new()));
+                }
+                self.sections.push(Section {
+                    name: name.to_string(),
+                    header: format!("[{name}]"),
+                    body: Vec::new(),
+                });
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[index]
+    }
+
+    /// Remove every section called `name`, keeping the comments and blank lines that trail
+    /// each (they usually introduce the next section). Returns whether there was one.
+    pub fn remove_section(&mut self, name: &str) -> bool {
+        let mut removed = false;
+        while let Some(index) = self
+            .sections
+            .iter()
+            .position(|section| section.name == name)
+        {
+            let mut section = self.sections.remove(index);
+            let mut trailing = section.body.split_off(section.body_end());
+            let kept = match index.checked_sub(1) {
+                Some(previous) => &mut self.sections[previous].body,
+                None => &mut self.preamble,
+            };
+            // Don't stack the removed section's blank separator onto the one before it.
+            if kept.last().is_none_or(Line::is_blank) {
+                let blanks = trailing.iter().take_while(|line| line.is_blank()).count();
+                trailing.drain(..blanks);
+            }
+            kept.extend(trailing);
+            removed = true;
+        }
+        removed
+    }
+}
+
+impl Section {
+    /// The name inside the brackets, e.g. `profile dev`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of `key` on its own line; empty for a property with nested sub-properties.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.property(key).map(|property| property.value.as_str())
+    }
+
+    /// The nested `key = value` pairs under `key`, such as the settings of an `s3 =` block.
+    pub fn sub_properties(&self, key: &str) -> Vec<(&str, &str)> {
+        self.property(key)
+            .filter(|property| property.value.is_empty())
+            .map(|property| {
+                property
+                    .continuation
+                    .iter()
+                    .filter_map(|line| parse_property(line))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Every top-level key, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.properties().map(|property| property.key.as_str())
+    }
+
+    /// Set `key` to a single-line `value`. An existing property is rewritten in place (losing
+    /// any continuation or nested lines); a new one goes after the last property, ahead of
+    /// trailing comments and blank lines.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = format!("{key} = {value}");
+        if let Some(property) = self.property_mut(key) {
+            if property.value != value || !property.continuation.is_empty() {
+                property.value = value.to_string();
+                property.raw = line;
+                property.continuation.clear();
+            }
+            return;
+        }
+        let at = self.body_end();
+        self.body.insert(
+            at,
+            Line::Property(Property {
+                key: key.to_string(),
+                value: value.to_string(),
+                raw: line,
+                continuation: Vec::new(),
+            }),
+        );
+    }
+
+    /// Remove every `key` property with its continuation lines. Returns whether there was one.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.body.len();
+        self.body
+            .retain(|line| !matches!(line, Line::Property(property) if property.key == key));
+        self.body.len() != before
+    }
+
+    fn properties(&self) -> impl Iterator<Item = &Property> {
+        self.body.iter().filter_map(|line| match line {
+            Line::Property(property) => Some(property),
+            Line::Other(_) => None,
+        })
+    }
+
+    fn property(&self, key: &str) -> Option<&Property> {
+        self.properties().find(|property| property.key == key)
+    }
+
+    fn property_mut(&mut self, key: &str) -> Option<&mut Property> {
+        self.body.iter_mut().find_map(|line| match line {
+            Line::Property(property) if property.key == key => Some(property),
+            _ => None,
+        })
+    }
+
+    /// Index just past the last property.
+    fn body_end(&self) -> usize {
+        self.body
+            .iter()
+            .rposition(|line| matches!(line, Line::Property(_)))
+            .map_or(0, |index| index + 1)
+    }
+}
+
+impl Line {
+    fn is_blank(&self) -> bool {
+        matches!(self, Self::Other(text) if text.trim().is_empty())
+    }
+}
+
+fn render_lines<'a>(body: &'a [Line], out: &mut Vec<&'a str>) {
+    for line in body {
+        match line {
+            Line::Other(text) => out.push(text),
+            Line::Property(property) => {
+                out.push(&property.raw);
+                out.extend(property.continuation.iter().map(String::as_str));
+            }
+        }
+    }
+}
+
+fn section_name(line: &str) -> Option<&str> {
+    let trimmed = line.trim();
+    let inner = trimmed.strip_prefix('[')?;
+    let (name, rest) = inner.split_once(']')?;
+    let rest = rest.trim_start();
+    (rest.is_empty() || rest.starts_with('#') || rest.starts_with(';')).then(|| name.trim())
+}
+
+fn is_comment(trimmed: &str) -> bool {
+    trimmed.starts_with('#') || trimmed.starts_with(';')
+}
+
+/// An indented, non-blank line, which continues the property above it.
+fn is_continuation(line: &str) -> bool {
+    line.starts_with([' ', '\t']) && !line.trim().is_empty()
+}
+
+fn parse_property(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || is_comment(trimmed) {
+        return None;
+    }
+    let (key, value) = trimmed.split_once('=')?;
+    let key = key.trim();
+    (!key.is_empty()).then(|| (key, value.trim()))
+}
+
+/// Parse the file at `path` without taking its lock; writers replace it atomically, so a
+/// reader always sees a complete version. A missing file reads as empty.
+pub fn read_file(path: &Path) -> Result<Ini> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Ini::parse(&contents)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Ini::parse("")),
+        Err(err) => Err(Error::Config(format!("{}: {err}", path.display()))),
+    }
+}
+
+/// Run `edit` over the file at `path` while holding its advisory lock, and atomically replace
+/// the file when the result differs. A missing file reads as empty.
+///
+/// `private` files are written 0600; others keep the permissions of the file they replace.
+/// A symlinked file is edited at its target, so dotfile-managed files stay linked.
+pub fn update_file(
+    path: &Path,
+    private: bool,
+    edit: impl FnOnce(&mut Ini) -> Result<()>,
+) -> Result<()> {
+    let io_err = |err: std::io::Error| Error::Config(format!("{}: {err}", path.display()));
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_err)?;
+    }
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path(&path))
+        .map_err(io_err)?;
+    lock.lock().map_err(io_err)?;
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(io_err(err)),
+    };
+    let mut ini = Ini::parse(&contents);
+    edit(&mut ini)?;
+    let updated = ini.render();
+    if updated != contents {
+        write_atomic(&path, updated.as_bytes(), private).map_err(io_err)?;
+    }
+    Ok(())
+}
+
+/// The lock file beside `path`; it is left in place, since removing it would race other
+/// writers.
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".roleman-lock");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use tempfile::TempDir;
+
+    const CONFIG: &str = "# managed by hand\n\
+        [default]\n\
+        region = us-east-1 ; trailing text stays part of the value\n\
+        \n\
+        [profile dev]\n\
+        sso_session = work\n\
+        s3 =\n    max_concurrent_requests = 20\n\taddressing_style = path\n\
+        # about prod\n\
+        \n\
+        [profile prod]   # inline header comment\n\
+        role_arn = arn:aws:iam::1:role/Admin\n\
+        source_profile = dev\n";
+
+    #[test]
+    fn roundtrips_and_reads_nested_properties() {
+        let ini = Ini::parse(CONFIG);
+        assert_eq!(ini.render(), CONFIG);
+        let dev = ini.section("profile dev").unwrap();
+        assert_eq!(dev.get("s3"), Some(""));
+        assert_eq!(
+            dev.sub_properties("s3"),
+            [
+                ("max_concurrent_requests", "20"),
+                ("addressing_style", "path")
+            ]
+        );
+        assert_eq!(dev.keys().collect::<Vec<_>>(), ["sso_session", "s3"]);
+        assert_eq!(
+            ini.section("profile prod").unwrap().get("source_profile"),
+            Some("dev")
+        );
+        assert_eq!(
+            ini.sections().map(Section::name).collect::<Vec<_>>(),
+            ["default", "profile dev", "profile prod"]
+        );
+    }
+
+    #[test]
+    fn edits_touch_only_their_lines() {
+        let mut ini = Ini::parse(CONFIG);
+        let dev = ini.section_mut("profile dev").unwrap();
+        dev.set("sso_session", "work");
+        dev.set("region", "eu-west-1");
+        assert!(dev.remove("sso_session"));
+        assert_eq!(
+            ini.render(),
+            CONFIG.replace(
+                "sso_session = work\ns3 =\n    max_concurrent_requests = 20\n\taddressing_style = path\n",
+                "s3 =\n    max_concurrent_requests = 20\n\taddressing_style = path\nregion = eu-west-1\n"
+            )
+        );
+
+        let mut ini = Ini::parse(CONFIG);
+        ini.section_mut("profile dev").unwrap().set("s3", "flat");
+        assert!(ini.render().contains("s3 = flat\n# about prod\n"));
+    }
+
+    #[test]
+    fn appends_and_removes_sections() {
+        let mut ini = Ini::parse(CONFIG);
+        ini.section_or_insert("sso-session work")
+            .set("sso_region", "us-east-1");
+        assert!(
+            ini.render()
+                .ends_with("source_profile = dev\n\n[sso-session work]\nsso_region = us-east-1\n")
+        );
+
+        let mut ini = Ini::parse(CONFIG);
+        assert!(ini.remove_section("profile dev"));
+        assert!(!ini.remove_section("profile dev"));
+        assert_eq!(
+            ini.render(),
+            CONFIG.replace(
+                "[profile dev]\nsso_session = work\ns3 =\n    max_concurrent_requests = 20\n\taddressing_style = path\n",
+                ""
+            )
+        );
+
+        let mut ini = Ini::parse("");
+        ini.section_or_insert("default").set("region", "us-east-1");
+        assert_eq!(ini.render(), "[default]\nregion = us-east-1\n");
+    }
+
+    #[test]
+    fn updates_files_atomically_and_keeps_permissions() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config");
+        fs::write(&path, CONFIG).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        update_file(&path, false, |ini| {
+            ini.section_or_insert("default").set("output", "json");
+            Ok(())
+        })
+        .unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains(
+            "[default]\nregion = us-east-1 ; trailing text stays part of the value\noutput = json\n"
+        ));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        }
+
+        // A failed edit leaves the file alone.
+        let result = update_file(&path, false, |ini| {
+            ini.remove_section("default");
+            Err(Error::Config("no".into()))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        let leftovers = fs::read_dir(temp.path())
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(".tmp")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config");
+        std::thread::scope(|scope| {
+            for index in 0..8 {
+                let path = &path;
+                scope.spawn(move || {
+                    update_file(path, false, |ini| {
+                        ini.section_or_insert(&format!("profile p{index}"))
+                            .set("region", "us-east-1");
+                        Ok(())
+                    })
+                    .unwrap();
+                });
+            }
+        });
+        let ini = Ini::parse(&fs::read_to_string(&path).unwrap());
+        assert_eq!(ini.sections().count(), 8);
+    }
+
+    /// Lines drawn from the shapes real config files contain, plus arbitrary text.
+    fn line() -> impl Strategy<Value = String> {
+        prop_oneof![
+            Just(String::new()),
+            Just("   ".to_string()),
+            "[a-z_]{1,8} ?= ?[ -~]{0,12}",
+            "[ \t]{1,4}[a-z_]{1,8} = [a-z0-9]{0,6}",
+            "[#;][ -~]{0,12}",
+            "\\[[a-z -]{0,10}\\]",
+            "\\[profile [a-z]{1,6}\\] *[#;]?[a-z ]{0,6}",
+            "[^\\n\\r]{0,16}",
+        ]
+    }
+
+    fn document() -> impl Strategy<Value = String> {
+        (prop::collection::vec(line(), 0..24), any::<bool>()).prop_map(|(lines, newline)| {
+            let mut text = lines.join("\n");
+            if newline {
+                text.push('\n');
+            }
+            text
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn parse_then_render_is_identity(text in document()) {
+            prop_assert_eq!(Ini::parse(&text).render(), text);
+        }
+
+        #[test]
+        fn set_is_visible_idempotent_and_local(
+            text in document(),
+            section in "[a-z]{1,6}",
+            key in "[a-z_]{1,8}",
+            value in "[ -~&&[^=]]{0,12}",
+        ) {
+            let original = Ini::parse(&text);
+            let mut ini = original.clone();
+            ini.section_or_insert(&section).set(&key, &value);
+            let rendered = ini.render();
+            let reparsed = Ini::parse(&rendered);
+            let target = reparsed.section(&section).unwrap();
+            prop_assert_eq!(target.get(&key), Some(value.trim()));
+
+            let mut again = reparsed.clone();
+            again.section_or_insert(&section).set(&key, &value);
+            prop_assert_eq!(again.render(), rendered.clone());
+
+            // Every other section is unchanged, except that a new section is separated from
+            // the one before it by a blank line.
+            let others = |ini: &Ini| {
+                ini.sections()
+                    .filter(|s| s.name() != section)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+            let (before, after) = (others(&original), others(&reparsed));
+            prop_assert_eq!(before.len(), after.len());
+            for (before, after) in before.iter().zip(&after) {
+                prop_assert_eq!(&before.header, &after.header);
+                let extra = &after.body[before.body.len().min(after.body.len())..];
+                prop_assert!(after.body.starts_with(&before.body));
+                prop_assert!(extra.is_empty() || extra == [Line::Other(String::new())]);
+            }
+        }
+
+        #[test]
+        fn removing_a_section_drops_only_its_properties(
+            text in document(),
+            section in "[a-z]{1,6}",
+        ) {
+            let mut ini = Ini::parse(&text);
+            ini.section_or_insert(&section).set("roleman_managed", "true");
+            let others: Vec<_> = ini
+                .sections()
+                .filter(|s| s.name() != section)
+                .map(|s| (s.name().to_string(), s.keys().map(str::to_string).collect::<Vec<_>>()))
+                .collect();
+            ini.remove_section(&section);
+            let reparsed = Ini::parse(&ini.render());
+            prop_assert!(reparsed.section(&section).is_none());
+            let remaining: Vec<_> = reparsed
+                .sections()
+                .map(|s| (s.name().to_string(), s.keys().map(str::to_string).collect::<Vec<_>>()))
+                .collect();
+            prop_assert_eq!(remaining, others);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::{Duration, Instant};
+
+use super::sdk::{self as aws_sdk, ClientRegistration, DeviceTokenPoll};
+use super::sso_cache::{self, SsoTokenFile};
+use crate::desktop;
+use crate::error::{Error, Result};
+use crate::model::CacheEntry;
+use crate::ui;
+
+/// Client name shown in the IAM Identity Center authorization prompt.
+const OIDC_CLIENT_NAME: &str = "roleman";
+/// Extra delay the OIDC spec asks for after each `slow_down` response.
+const SLOW_DOWN_INCREMENT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostLoginActions {
+    pub focus_terminal: bool,
+    pub close_browser_tab: bool,
+}
+
+/// Sign in to IAM Identity Center with the OIDC device authorization flow and
+/// store the token in `~/.aws/sso/cache` under `session`'s cache file.
+pub async fn sso_login(
+    session: &str,
+    start_url: &str,
+    region: &str,
+    open_browser: bool,
+    post_login_actions: PostLoginActions,
+) -> Result<CacheEntry> {
+    let registration = aws_sdk::register_client(region, OIDC_CLIENT_NAME).await?;
+    let authorization =
+        aws_sdk::start_device_authorization(region, &registration, start_url).await?;
+
+    eprintln!(
+        "{}",
+        ui::action(&format!(
+            "Confirm code {} in your browser to sign in.",
+            authorization.user_code
+        ))
+    );
+    eprintln!(
+        "{}",
+        ui::hint(&format!(
+            "If the browser does not open, visit {}",
+            authorization.verification_uri_complete
+        ))
+    );
+    if open_browser && let Err(err) = open::that(&authorization.verification_uri_complete) {
+        tracing::debug!(error = %err, "failed to open verification url");
+    }
+
+    let spinner = ui::spinner("Waiting for SSO authorization...");
+    let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+    let mut interval = Duration::from_secs(authorization.interval);
+    let token = loop {
+        let poll = match aws_sdk::create_token_for_device(
+            region,
+            &registration,
+            &authorization.device_code,
+        )
+        .await
+        {
+            Ok(poll) => poll,
+            Err(err) => {
+                spinner.finish_and_clear();
+                return Err(err);
+            }
+        };
+        match poll {
+            DeviceTokenPoll::Ready(token) => break token,
+            DeviceTokenPoll::Pending => {}
+            DeviceTokenPoll::SlowDown => interval += SLOW_DOWN_INCREMENT,
+        }
+        if Instant::now() + interval >= deadline {
+            spinner.finish_and_clear();
+            return Err(Error::Config(
+                "SSO device authorization expired before it was approved".to_string(),
+            ));
+        }
+        tokio::time::sleep(interval).await;
+    };
+    spinner.finish_with_message(ui::success("Signed in to IAM Identity Center"));
+
+    let entry = sso_cache::write_session_token(
+        session,
+        &SsoTokenFile {
+            start_url: start_url.to_string(),
+            region: region.to_string(),
+            access_token: token.access_token,
+            expires_at: sso_cache::expires_at_from_now(token.expires_in),
+            client_id: Some(registration.client_id),
+            client_secret: Some(registration.client_secret),
+            registration_expires_at: Some(sso_cache::aws_time_from_epoch(registration.expires_at)),
+            refresh_token: token.refresh_token,
+            extra: serde_json::Map::new(),
+        },
+    )?;
+    run_post_login_actions(post_login_actions);
+    Ok(entry)
+}
+
+/// Renew the cached token for `start_url` with its refresh token, rewriting the cache
+/// file in place. Returns `None` when there is nothing to refresh or the refresh was
+/// rejected, in which case the caller should fall back to [`sso_login`].
+pub async fn refresh_sso_token(start_url: &str) -> Option<CacheEntry> {
+    let (path, mut cached) = match sso_cache::load_refreshable_token(start_url) {
+        Ok(Some(found)) => found,
+        Ok(None) => return None,
+        Err(err) => {
+            tracing::debug!(error = %err, "failed to look up refreshable sso token");
+            return None;
+        }
+    };
+    let registration = ClientRegistration {
+        client_id: cached.client_id.clone()?,
+        client_secret: cached.client_secret.clone()?,
+        expires_at: 0,
+    };
+    let refresh_token = cached.refresh_token.clone()?;
+    let token = match aws_sdk::create_token_with_refresh_token(
+        &cached.region,
+        &registration,
+        &refresh_token,
+    )
+    .await
+    {
+        Ok(token) => token,
+        Err(err) => {
+            tracing::debug!(error = %err, "sso token refresh failed");
+            return None;
+        }
+    };
+
+    cached.access_token = token.access_token;
+    cached.expires_at = sso_cache::expires_at_from_now(token.expires_in);
+    // Refresh tokens may or may not rotate; keep the old one if no new one came back.
+    if token.refresh_token.is_some() {
+        cached.refresh_token = token.refresh_token;
+    }
+    match sso_cache::write_token_file(&path, &cached) {
+        Ok(entry) => {
+            sso_cache::report_refreshed(&entry);
+            Some(entry)
+        }
+        Err(err) => {
+            tracing::debug!(error = %err, "failed to write refreshed sso token");
+            None
+        }
+    }
+}
+
+fn run_post_login_actions(actions: PostLoginActions) {
+    if !actions.focus_terminal && !actions.close_browser_tab {
+        return;
+    }
+
+    let permission_requirements = desktop::permission_requirements();
+
+    if actions.close_browser_tab {
+        if permission_requirements.close_auth_browser_tab
+            && desktop::should_warn_close_auth_tab_permission_prompt()
+        {
+            ui::print_warn(
+                "Closing the auth tab may require OS automation permission on this platform.",
+            );
+        }
+        if let Err(err) = desktop::close_auth_browser_tab() {
+            if let Some(help) = desktop::close_auth_tab_permission_denied_help(&err) {
+                ui::print_warn(help);
+            }
+            ui::print_warn(&format!("Post-auth automation skipped: {err}"));
+            tracing::debug!(error = %err, "post-auth close browser tab failed");
+        }
+    }
+
+    if actions.focus_terminal
+        && let Err(err) = desktop::focus_terminal_app()
+    {
+        if permission_requirements.focus_terminal_app {
+            tracing::debug!("focus-terminal action may require OS permission on this platform");
+        }
+        ui::print_warn(&format!("Post-auth automation skipped: {err}"));
+        tracing::debug!(error = %err, "post-auth focus terminal failed");
+    }
+}
@@ -1,26 +1,30 @@
 //! AWS IAM Identity Center (SSO) implementation of [`CloudProvider`].
 
-pub mod cli;
 pub mod config;
+pub mod credentials_file;
+pub mod ini;
+pub mod login;
 pub mod sdk;
 pub mod sso_cache;
+pub mod throttle;
 
 use std::any::Any;
+use std::borrow::Cow;
+use std::time::Duration;
 
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
-use self::cli as aws_cli;
 use self::config as aws_config;
 use self::sdk as aws_sdk;
-use crate::config::{ReadonlyPolicy, SsoIdentity};
-use crate::credentials_cache::{self, CachedCredentialsStatus};
+use self::throttle::Throttle;
+use crate::config::{ChainedRole, ReadonlyPolicy, SsoIdentity};
 use crate::error::{Error, Result};
-use crate::model::{AwsRoleCredentials, CacheEntry, RoleChoice};
+use crate::model::{AwsRoleCredentials, CacheEntry, ListingFailure, RoleChoice, RoleListing};
 use crate::provider::{
     AccessScope, AccountCleanup, ActiveMarker, CloudProvider, EnvVar, ManagedResource,
-    PostLoginActions, ProfileBinding, ProviderCredentials, ProviderSession,
+    PostLoginActions, ProfileBinding, ProviderCredentials, ProviderSession, active_marker,
 };
 use crate::ui;
 use sha1::{Digest, Sha1};
@@ -38,14 +42,29 @@ const OWNER_TAG_KEY: &str = "roleman:owner";
 /// Tag recording the full original caller identity (ARN) that created the role.
 const CREATED_BY_TAG_KEY: &str = "roleman:created-by";
 
+/// Credential variables [`AwsCredentials::env_vars`] exports. The config-file variables are
+/// left out: `unset` restores those rather than clearing them.
+pub const CREDENTIAL_ENV_VARS: &[&str] = &[
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+    "AWS_CREDENTIAL_EXPIRATION",
+    "AWS_DEFAULT_REGION",
+    "AWS_REGION",
+    "AWS_PROFILE",
+];
+
 /// AWS provider bound to a single configured identity.
 pub struct AwsProvider {
     identity: SsoIdentity,
+    /// Paces every SSO listing call this provider makes.
+    throttle: Throttle,
 }
 
 impl AwsProvider {
     pub fn new(identity: SsoIdentity) -> Self {
-        Self { identity }
+        let throttle = Throttle::new(identity.max_requests_per_second());
+        Self { identity, throttle }
     }
 }
 
@@ -106,6 +125,9 @@ impl ProviderCredentials for AwsCredentials {
         if let Some(path) = &binding.config_file {
             vars.push(EnvVar::new("AWS_CONFIG_FILE", path.clone()));
         }
+        if let Some(path) = &binding.saved_config_file {
+            vars.push(EnvVar::new(aws_config::SAVED_CONFIG_FILE_VAR, path.clone()));
+        }
         vars
     }
 
@@ -116,6 +138,39 @@ impl ProviderCredentials for AwsCredentials {
     fn to_cache_json(&self) -> Result<String> {
         serde_json::to_string(self).map_err(|err| Error::AwsSdk(err.to_string()))
     }
+
+    fn credential_process_json(&self) -> Result<String> {
+        let output = serde_json::json!({
+            "Version": 1,
+            "AccessKeyId": self.access_key_id,
+            "SecretAccessKey": self.secret_access_key,
+            "SessionToken": self.session_token,
+            "Expiration": format_expiration(self.expiration_ms),
+        });
+        serde_json::to_string(&output).map_err(|err| Error::AwsSdk(err.to_string()))
+    }
+
+    fn write_shared_credentials(&self, profile_name: &str) -> Result<()> {
+        credentials_file::write_credentials(
+            profile_name,
+            &credentials_file::SharedCredentials {
+                access_key_id: &self.access_key_id,
+                secret_access_key: &self.secret_access_key,
+                session_token: &self.session_token,
+                expiration: &format_expiration(self.expiration_ms),
+            },
+        )
+    }
+
+    fn container_credentials_json(&self) -> Result<String> {
+        let output = serde_json::json!({
+            "AccessKeyId": self.access_key_id,
+            "SecretAccessKey": self.secret_access_key,
+            "Token": self.session_token,
+            "Expiration": format_expiration(self.expiration_ms),
+        });
+        serde_json::to_string(&output).map_err(|err| Error::AwsSdk(err.to_string()))
+    }
 }
 
 #[async_trait::async_trait]
@@ -128,9 +183,9 @@ impl CloudProvider for AwsProvider {
         let ignore_sso_cache = env_truthy("ROLEMAN_IGNORE_SSO_CACHE");
         if !ignore_cache
             && !ignore_sso_cache
-            && let Ok(entry) = sso_cache::load_valid_cache(&self.identity.start_url)
+            && let Some(session) = self.resume_session().await?
         {
-            return Ok(Box::new(AwsSession { cache: entry }));
+            return Ok(session);
         }
         if ignore_sso_cache {
             eprintln!(
@@ -139,18 +194,33 @@ impl CloudProvider for AwsProvider {
             );
         }
         let session = aws_config::ensure_sso_session(&self.identity)?;
-        aws_cli::sso_login_session(&session, post_login)?;
-        let entry = sso_cache::load_valid_cache(&self.identity.start_url)?;
+        let entry = login::sso_login(
+            &session,
+            &self.identity.start_url,
+            &self.identity.sso_region,
+            true,
+            post_login,
+        )
+        .await?;
         Ok(Box::new(AwsSession { cache: entry }))
     }
 
-    async fn list_choices(&self, session: &dyn ProviderSession) -> Result<Vec<RoleChoice>> {
+    async fn resume_session(&self) -> Result<Option<Box<dyn ProviderSession>>> {
+        if let Ok(entry) = sso_cache::load_valid_cache(&self.identity.start_url) {
+            return Ok(Some(Box::new(AwsSession { cache: entry })));
+        }
+        Ok(login::refresh_sso_token(&self.identity.start_url)
+            .await
+            .map(|entry| Box::new(AwsSession { cache: entry }) as Box<dyn ProviderSession>))
+    }
+
+    async fn list_choices(&self, session: &dyn ProviderSession) -> Result<RoleListing> {
         let session = session_of(session)?;
         let token = &session.cache.access_token;
         let region = &session.cache.region;
 
         let accounts_spinner = ui::spinner("Fetching SSO accounts...");
-        let mut accounts = match aws_sdk::list_accounts(token, region).await {
+        let mut accounts = match aws_sdk::list_accounts(token, region, &self.throttle).await {
             Ok(accounts) => accounts,
             Err(err) => {
                 accounts_spinner.finish_and_clear();
@@ -163,36 +233,60 @@ impl CloudProvider for AwsProvider {
             debug!(account_id = %account.id, account_name = %account.name, "fetched account");
         }
 
-        let roles_spinner = ui::spinner("Fetching roles for all accounts...");
-        let roles_by_account = futures::stream::iter(accounts.clone())
-            .map(|account| {
-                let token = token.clone();
-                let region = region.clone();
-                async move {
-                    let roles = aws_sdk::list_account_roles(&token, &region, &account.id).await?;
-                    Ok::<_, Error>((account, roles))
-                }
+        let total = accounts.len();
+        let roles_spinner = ui::spinner(&format!("Fetching roles for all accounts [0/{total}]..."));
+        let mut pending = futures::stream::iter(accounts)
+            .map(|account| async move {
+                let roles =
+                    aws_sdk::list_account_roles(token, region, &account.id, &self.throttle).await;
+                (account, roles)
             })
-            .buffer_unordered(10)
-            .collect::<Vec<_>>()
-            .await;
-
-        let roles_by_account = match roles_by_account.into_iter().collect::<Result<Vec<_>>>() {
-            Ok(roles) => roles,
-            Err(err) => {
-                roles_spinner.finish_and_clear();
-                return Err(err);
-            }
-        };
-        roles_spinner.finish_with_message(ui::success("Fetched roles"));
+            .buffer_unordered(self.identity.list_concurrency());
+        let mut roles_by_account = Vec::with_capacity(total);
+        while let Some(listed) = pending.next().await {
+            roles_by_account.push(listed);
+            roles_spinner.set_message(format!(
+                "Fetching roles for all accounts [{}/{total}]...",
+                roles_by_account.len()
+            ));
+        }
 
-        let mut choices = Vec::new();
+        // One failing account shouldn't hide the others; report it alongside them.
+        let mut listing = RoleListing::default();
         for (account, roles) in roles_by_account {
-            for role in roles {
-                choices.push(RoleChoice::new(&account, &role));
+            match roles {
+                Ok(roles) => {
+                    for role in roles {
+                        listing.choices.push(RoleChoice::new(&account, &role));
+                    }
+                }
+                Err(err) => {
+                    debug!(account_id = %account.id, error = %err, "failed to list account roles");
+                    listing.failures.push(ListingFailure {
+                        account_id: account.id,
+                        account_name: account.name,
+                        error: err.to_string(),
+                        cached_roles: 0,
+                    });
+                }
             }
         }
-        Ok(choices)
+        if total > 0 && listing.failures.len() == total {
+            roles_spinner.finish_and_clear();
+            return Err(Error::AwsSdk(format!(
+                "couldn't list roles for any account: {}",
+                listing.failures[0].error
+            )));
+        }
+        if listing.failures.is_empty() {
+            roles_spinner.finish_with_message(ui::success("Fetched roles"));
+        } else {
+            roles_spinner.finish_with_message(ui::warn(&format!(
+                "Fetched roles; {} of the accounts failed",
+                listing.failures.len()
+            )));
+        }
+        Ok(listing)
     }
 
     async fn fetch_credentials(
@@ -200,26 +294,41 @@ impl CloudProvider for AwsProvider {
         session: &dyn ProviderSession,
         choice: &RoleChoice,
         scope: AccessScope,
+        region: Option<&str>,
         may_create: bool,
     ) -> Result<Box<dyn ProviderCredentials>> {
         let session = session_of(session)?;
-        let region = session.cache.region.clone();
+        // SSO and STS calls stay in the SSO region; the override only picks what we export.
+        let sso_region = session.cache.region.clone();
+        if let Some(chained) = self
+            .identity
+            .chained_role(&choice.account_id, &choice.role_name)
+        {
+            let raw = self.assume_chained(session, chained, scope).await?;
+            return Ok(Box::new(AwsCredentials::from_raw(
+                raw,
+                region.unwrap_or(&sso_region),
+            )));
+        }
         let base = aws_sdk::get_role_credentials(
             &session.cache.access_token,
-            &region,
+            &sso_region,
             &choice.account_id,
             &choice.role_name,
         )
         .await?;
 
-        let creds = match scope {
-            AccessScope::Full => AwsCredentials::from_raw(base, &region),
+        let raw = match scope {
+            AccessScope::Full => base,
             AccessScope::ReadOnly => {
-                self.downscope(&base, &region, &choice.account_id, may_create)
+                self.downscope(&base, &sso_region, &choice.account_id, may_create)
                     .await?
             }
         };
-        Ok(Box::new(creds))
+        Ok(Box::new(AwsCredentials::from_raw(
+            raw,
+            region.unwrap_or(&sso_region),
+        )))
     }
 
     fn credentials_from_cache_json(&self, json: &str) -> Result<Box<dyn ProviderCredentials>> {
@@ -233,22 +342,51 @@ impl CloudProvider for AwsProvider {
         session: &dyn ProviderSession,
         choice: &RoleChoice,
         scope: AccessScope,
+        region: Option<&str>,
         omit_role_name: bool,
     ) -> Result<ProfileBinding> {
         let session = session_of(session)?;
         let profile_name = profile_name(choice, scope, omit_role_name);
-        aws_config::ensure_role_profile(
+        let path = aws_config::ensure_role_profile(
             &profile_name,
             choice,
             &self.identity,
-            &session.cache.region,
+            region.unwrap_or(&session.cache.region),
+            scope,
         )?;
+        // Also export the regular file when leaving an isolated identity in the same shell,
+        // and keep the user's own AWS_CONFIG_FILE aside while the isolated file replaces it.
+        let isolated_exported = aws_config::isolated_config_exported();
+        let saved_config_file = match (self.identity.isolated_aws_config, isolated_exported) {
+            (true, false) => Some(aws_config::user_config_file().unwrap_or_default()),
+            (false, true) => Some(String::new()),
+            _ => None,
+        };
         Ok(ProfileBinding {
             profile_name,
-            config_file: None,
+            config_file: (self.identity.isolated_aws_config || isolated_exported)
+                .then(|| path.to_string_lossy().into_owned()),
+            saved_config_file,
         })
     }
 
+    fn profile_name(
+        &self,
+        choice: &RoleChoice,
+        scope: AccessScope,
+        omit_role_name: bool,
+    ) -> String {
+        profile_name(choice, scope, omit_role_name)
+    }
+
+    fn generated_profiles(&self) -> Result<Vec<String>> {
+        aws_config::managed_profiles(&self.identity)
+    }
+
+    fn remove_generated_profiles(&self, names: &[String]) -> Result<()> {
+        aws_config::remove_profiles(&self.identity, names)
+    }
+
     fn console_url(&self, choice: &RoleChoice) -> String {
         let base = self.identity.start_url.trim_end_matches('/');
         format!(
@@ -262,7 +400,17 @@ impl CloudProvider for AwsProvider {
         format!("aws:{}:{}", self.identity.name, self.identity.start_url)
     }
 
-    fn active_markers(&self, choices: &[RoleChoice], scope: AccessScope) -> Vec<ActiveMarker> {
+    fn cache_role_name<'a>(&self, choice: &'a RoleChoice) -> Cow<'a, str> {
+        self.identity
+            .credentials_cache_role(&choice.account_id, &choice.role_name)
+    }
+
+    fn active_markers(
+        &self,
+        choices: &[RoleChoice],
+        scope: AccessScope,
+        min_lifetime: Duration,
+    ) -> Vec<ActiveMarker> {
         let namespace = self.cache_namespace();
         let current_profile = std::env::var("AWS_PROFILE").ok();
         let mut roles_per_account: std::collections::HashMap<&str, usize> =
@@ -290,23 +438,28 @@ impl CloudProvider for AwsProvider {
                 if active != candidate && active != legacy {
                     return ActiveMarker::Inactive;
                 }
-                match credentials_cache::cached_credentials_status(
+                active_marker(
                     &namespace,
-                    &choice.account_id,
-                    &choice.role_name,
+                    &self.identity,
+                    choice,
                     scope,
-                ) {
-                    Ok(CachedCredentialsStatus::Valid) => ActiveMarker::ActiveValid,
-                    Ok(_) => ActiveMarker::ActiveStale,
-                    Err(err) => {
-                        debug!(error = %err, "failed to check cached credentials");
-                        ActiveMarker::ActiveStale
-                    }
-                }
+                    "AWS_REGION",
+                    min_lifetime,
+                )
             })
             .collect()
     }
 
+    fn session_expiration_ms(&self) -> Option<u64> {
+        match sso_cache::latest_token_expiry(&self.identity.start_url) {
+            Ok(expiry) => expiry.map(|seconds| seconds * 1000),
+            Err(err) => {
+                debug!(error = %err, "failed to read SSO token cache");
+                None
+            }
+        }
+    }
+
     async fn current_account(&self) -> Result<String> {
         aws_sdk::ambient_account(&self.identity.sso_region).await
     }
@@ -329,8 +482,12 @@ impl CloudProvider for AwsProvider {
         progress: &(dyn for<'a> Fn(&'a str) + Send + Sync),
     ) -> Result<Vec<AccountCleanup>> {
         let session = session_of(session)?;
-        let mut accounts =
-            aws_sdk::list_accounts(&session.cache.access_token, &session.cache.region).await?;
+        let mut accounts = aws_sdk::list_accounts(
+            &session.cache.access_token,
+            &session.cache.region,
+            &self.throttle,
+        )
+        .await?;
         accounts.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
         let total = accounts.len();
         let mut results = Vec::new();
@@ -392,8 +549,13 @@ impl AwsProvider {
         account_id: &str,
     ) -> Result<aws_sdk_iam::Client> {
         let region = &session.cache.region;
-        let roles =
-            aws_sdk::list_account_roles(&session.cache.access_token, region, account_id).await?;
+        let roles = aws_sdk::list_account_roles(
+            &session.cache.access_token,
+            region,
+            account_id,
+            &self.throttle,
+        )
+        .await?;
         let mut last_err =
             Error::AwsSdk(format!("no role with IAM access in account {account_id}"));
         for role in &roles {
@@ -420,6 +582,60 @@ impl AwsProvider {
         Err(last_err)
     }
 
+    /// Mint credentials for a chained role: take the source SSO role, then assume the target.
+    ///
+    /// `--readonly` needs no roleman-owned role here; the read-only policy rides along as a
+    /// session policy on the `AssumeRole` call itself. The session is named after the SSO user,
+    /// so CloudTrail attributes chained calls to a person, as it does for SSO roles.
+    async fn assume_chained(
+        &self,
+        session: &AwsSession,
+        chained: &ChainedRole,
+        scope: AccessScope,
+    ) -> Result<AwsRoleCredentials> {
+        let region = &session.cache.region;
+        let source = aws_sdk::get_role_credentials(
+            &session.cache.access_token,
+            region,
+            &chained.source_account_id,
+            &chained.source_role,
+        )
+        .await?;
+        let caller_arn = match aws_sdk::get_caller_arn(region, &source).await {
+            Ok(arn) => Some(arn),
+            Err(err) => {
+                debug!(error = %err, "failed to resolve the chained role's source caller");
+                None
+            }
+        };
+        let session_name = chained_session_name(caller_arn.as_deref(), &chained.name);
+        let (policy_arns, inline) = match scope {
+            AccessScope::Full => (Vec::new(), None),
+            AccessScope::ReadOnly => {
+                resolve_readonly_policy(&self.identity, &partition_of(&chained.role_arn))
+            }
+        };
+        aws_sdk::assume_role(
+            region,
+            &source,
+            &chained.role_arn,
+            &session_name,
+            aws_sdk::AssumeRoleOptions {
+                policy_arns: &policy_arns,
+                inline_policy: inline.as_deref(),
+                external_id: chained.external_id.as_deref(),
+                duration_seconds: chained.duration_seconds,
+            },
+        )
+        .await
+        .map_err(|err| {
+            Error::AwsSdk(format!(
+                "assuming {} from {}/{} failed: {err}",
+                chained.role_arn, chained.source_account_id, chained.source_role
+            ))
+        })
+    }
+
     /// Drop write access by creating (or reusing) a roleman-owned read-only role and assuming it.
     ///
     /// SSO permission-set roles can't re-assume themselves, so we provision a separate role
@@ -430,7 +646,7 @@ impl AwsProvider {
         region: &str,
         account_id: &str,
         may_create: bool,
-    ) -> Result<AwsCredentials> {
+    ) -> Result<AwsRoleCredentials> {
         // Partition + caller identity from the live (base SSO) caller. The caller's session
         // name is the human's identity, so the role name is per-caller: two engineers in the
         // same account get distinct (both read-only) roles.
@@ -459,17 +675,15 @@ impl AwsProvider {
         // Always pass a read-only session policy: effective perms = intersection(role, session),
         // so the result is read-only even if the role's attached policy were broader.
         let (policy_arns, inline) = resolve_readonly_policy(&self.identity, &partition);
-        let scoped = self
-            .assume_with_retry(
-                base,
-                region,
-                &role_arn,
-                &policy_arns,
-                inline.as_deref(),
-                just_created,
-            )
-            .await?;
-        Ok(AwsCredentials::from_raw(scoped, region))
+        self.assume_with_retry(
+            base,
+            region,
+            &role_arn,
+            &policy_arns,
+            inline.as_deref(),
+            just_created,
+        )
+        .await
     }
 
     /// Ensure the per-caller read-only role exists, is roleman-owned, and carries exactly the
@@ -628,7 +842,7 @@ impl AwsProvider {
                         )));
                     }
                     debug!(attempt, "assume-role not ready yet, retrying after backoff");
-                    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                    tokio::time::sleep(Duration::from_secs(2)).await;
                     attempt += 1;
                 }
             }
@@ -664,6 +878,25 @@ fn caller_session_name(caller_arn: &str) -> Option<String> {
     }
 }
 
+/// `RoleSessionName` for a chained role: the SSO user behind the source role (their email,
+/// as in SSO role sessions), else `roleman-<role>`. Characters STS rejects become `-`, and
+/// the name is cut to STS's 64-character limit.
+fn chained_session_name(caller_arn: Option<&str>, role_name: &str) -> String {
+    let name = caller_arn
+        .and_then(caller_session_name)
+        .unwrap_or_else(|| format!("roleman-{role_name}"));
+    name.chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || "_+=,.@-".contains(ch) {
+                ch
+            } else {
+                '-'
+            }
+        })
+        .take(64)
+        .collect()
+}
+
 /// Deterministic, per-caller read-only role name: `roleman-ro-<sanitized-owner>-<hash8>`.
 ///
 /// Derived purely from the caller identity (no local state), bounded to IAM's 64-char limit.
@@ -830,6 +1063,16 @@ mod tests {
             sso_region: "us-east-1".into(),
             provider: crate::config::ProviderKind::Aws,
             readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
             accounts: Vec::new(),
             ignore_roles: Vec::new(),
         })
@@ -847,6 +1090,7 @@ mod tests {
         let binding = ProfileBinding {
             profile_name: "Acme-Cloud/ReadOnly".into(),
             config_file: Some("/tmp/roleman-aws-config".into()),
+            saved_config_file: Some("/home/me/aws-config".into()),
         };
         let vars = creds.env_vars(&binding);
         let names: Vec<&str> = vars.iter().map(|var| var.name.as_str()).collect();
@@ -862,6 +1106,7 @@ mod tests {
                 "AWS_REGION",
                 "AWS_PROFILE",
                 "AWS_CONFIG_FILE",
+                "_ROLEMAN_USER_AWS_CONFIG_FILE",
             ]
         );
         assert!(vars.contains(&EnvVar::new("AWS_ACCESS_KEY_ID", "AKIA123")));
@@ -870,6 +1115,51 @@ mod tests {
         assert!(vars.contains(&EnvVar::new("AWS_CONFIG_FILE", "/tmp/roleman-aws-config")));
     }
 
+    #[test]
+    fn credential_process_json_matches_version_1() {
+        let creds = AwsCredentials {
+            access_key_id: "AKIA123".into(),
+            secret_access_key: "secret".into(),
+            session_token: "token".into(),
+            expiration_ms: 1_700_000_000_000,
+            region: "us-east-1".into(),
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&creds.credential_process_json().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "Version": 1,
+                "AccessKeyId": "AKIA123",
+                "SecretAccessKey": "secret",
+                "SessionToken": "token",
+                "Expiration": "2023-11-14T22:13:20Z",
+            })
+        );
+    }
+
+    #[test]
+    fn container_credentials_json_matches_ecs_format() {
+        let creds = AwsCredentials {
+            access_key_id: "AKIA123".into(),
+            secret_access_key: "secret".into(),
+            session_token: "token".into(),
+            expiration_ms: 1_700_000_000_000,
+            region: "us-east-1".into(),
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&creds.container_credentials_json().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "AccessKeyId": "AKIA123",
+                "SecretAccessKey": "secret",
+                "Token": "token",
+                "Expiration": "2023-11-14T22:13:20Z",
+            })
+        );
+    }
+
     #[test]
     fn readonly_scope_suffixes_profile_name() {
         let choice = RoleChoice {
@@ -928,6 +1218,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn chained_sessions_are_named_after_the_sso_user() {
+        let arn = "arn:aws:sts::111111111111:assumed-role/AWSReservedSSO_Admin_abc/jane@corp.com";
+        assert_eq!(chained_session_name(Some(arn), "deployer"), "jane@corp.com");
+        assert_eq!(chained_session_name(None, "deployer"), "roleman-deployer");
+        let arn = "arn:aws:sts::111111111111:assumed-role/Admin/jane doe";
+        assert_eq!(chained_session_name(Some(arn), "deployer"), "jane-doe");
+        let long = format!(
+            "arn:aws:sts::111111111111:assumed-role/Admin/{}",
+            "a".repeat(100)
+        );
+        assert_eq!(chained_session_name(Some(&long), "deployer").len(), 64);
+    }
+
     #[test]
     fn readonly_role_name_bounds_long_callers() {
         let owner = "a".repeat(200);
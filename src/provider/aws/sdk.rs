@@ -6,9 +6,15 @@ use aws_smithy_types::error::metadata::ProvideErrorMetadata;
 use aws_types::SdkConfig;
 use aws_types::request_id::RequestId;
 
+use super::throttle::Throttle;
 use crate::error::{Error, Result};
 use crate::model::{Account, AwsRoleCredentials, Role};
 
+/// Attempts per throttled SSO call before giving up.
+const MAX_ATTEMPTS: u32 = 8;
+/// Longest pause between attempts of one call.
+const MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(8);
+
 pub async fn sdk_config(region: &str) -> Result<SdkConfig> {
     let region = Region::new(region.to_string());
     Ok(aws_config::defaults(aws_config::BehaviorVersion::latest())
@@ -28,7 +34,182 @@ async fn sso_client(region: &str) -> Result<aws_sdk_sso::Client> {
     Ok(aws_sdk_sso::Client::from_conf(builder.build()))
 }
 
-pub async fn list_accounts(access_token: &str, region: &str) -> Result<Vec<Account>> {
+async fn oidc_client(region: &str) -> Result<aws_sdk_ssooidc::Client> {
+    let config = sdk_config(region).await?;
+    let mut builder = aws_sdk_ssooidc::config::Builder::from(&config);
+    if let Ok(url) = std::env::var("ROLEMAN_OIDC_ENDPOINT")
+        && !url.is_empty()
+    {
+        builder = builder.endpoint_url(url);
+    }
+    Ok(aws_sdk_ssooidc::Client::from_conf(builder.build()))
+}
+
+/// OIDC scope granting access to the IAM Identity Center portal APIs.
+const SSO_ACCOUNT_ACCESS_SCOPE: &str = "sso:account:access";
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+const REFRESH_TOKEN_GRANT_TYPE: &str = "refresh_token";
+
+/// A public OIDC client registered with IAM Identity Center.
+#[derive(Debug, Clone)]
+pub struct ClientRegistration {
+    pub client_id: String,
+    pub client_secret: String,
+    /// Unix seconds after which the registration can no longer mint tokens.
+    pub expires_at: i64,
+}
+
+/// A pending device authorization the user approves in the browser.
+#[derive(Debug, Clone)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri_complete: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+/// An IAM Identity Center access token as issued by `CreateToken`.
+#[derive(Debug, Clone)]
+pub struct SsoToken {
+    pub access_token: String,
+    pub expires_in: u64,
+    pub refresh_token: Option<String>,
+}
+
+/// Outcome of one `CreateToken` poll during the device authorization flow.
+#[derive(Debug)]
+pub enum DeviceTokenPoll {
+    Ready(SsoToken),
+    /// The user has not approved the request yet.
+    Pending,
+    /// The service asked us to poll less often.
+    SlowDown,
+}
+
+pub async fn register_client(region: &str, client_name: &str) -> Result<ClientRegistration> {
+    let client = oidc_client(region).await?;
+    let output = client
+        .register_client()
+        .client_name(client_name)
+        .client_type("public")
+        .scopes(SSO_ACCOUNT_ACCESS_SCOPE)
+        .grant_types(DEVICE_CODE_GRANT_TYPE)
+        .grant_types(REFRESH_TOKEN_GRANT_TYPE)
+        .send()
+        .await
+        .map_err(|err| Error::AwsSdk(format_sdk_error(&err)))?;
+    Ok(ClientRegistration {
+        client_id: output
+            .client_id()
+            .ok_or_else(|| Error::AwsSdk("missing client_id".into()))?
+            .to_string(),
+        client_secret: output
+            .client_secret()
+            .ok_or_else(|| Error::AwsSdk("missing client_secret".into()))?
+            .to_string(),
+        expires_at: output.client_secret_expires_at(),
+    })
+}
+
+pub async fn start_device_authorization(
+    region: &str,
+    registration: &ClientRegistration,
+    start_url: &str,
+) -> Result<DeviceAuthorization> {
+    let client = oidc_client(region).await?;
+    let output = client
+        .start_device_authorization()
+        .client_id(&registration.client_id)
+        .client_secret(&registration.client_secret)
+        .start_url(start_url)
+        .send()
+        .await
+        .map_err(|err| Error::AwsSdk(format_sdk_error(&err)))?;
+    Ok(DeviceAuthorization {
+        device_code: output
+            .device_code()
+            .ok_or_else(|| Error::AwsSdk("missing device_code".into()))?
+            .to_string(),
+        user_code: output
+            .user_code()
+            .ok_or_else(|| Error::AwsSdk("missing user_code".into()))?
+            .to_string(),
+        verification_uri_complete: output
+            .verification_uri_complete()
+            .or(output.verification_uri())
+            .ok_or_else(|| Error::AwsSdk("missing verification_uri".into()))?
+            .to_string(),
+        expires_in: output.expires_in().max(0) as u64,
+        interval: output.interval().max(1) as u64,
+    })
+}
+
+/// Poll `CreateToken` once for a device authorization the user may not have approved yet.
+pub async fn create_token_for_device(
+    region: &str,
+    registration: &ClientRegistration,
+    device_code: &str,
+) -> Result<DeviceTokenPoll> {
+    let client = oidc_client(region).await?;
+    let result = client
+        .create_token()
+        .client_id(&registration.client_id)
+        .client_secret(&registration.client_secret)
+        .grant_type(DEVICE_CODE_GRANT_TYPE)
+        .device_code(device_code)
+        .send()
+        .await;
+    match result {
+        Ok(output) => Ok(DeviceTokenPoll::Ready(sso_token_from_sdk(&output)?)),
+        Err(err) if err.code() == Some("AuthorizationPendingException") => {
+            Ok(DeviceTokenPoll::Pending)
+        }
+        Err(err) if err.code() == Some("SlowDownException") => Ok(DeviceTokenPoll::SlowDown),
+        Err(err) => Err(Error::AwsSdk(format_sdk_error(&err))),
+    }
+}
+
+/// Exchange a refresh token for a new access token without user interaction.
+pub async fn create_token_with_refresh_token(
+    region: &str,
+    registration: &ClientRegistration,
+    refresh_token: &str,
+) -> Result<SsoToken> {
+    let client = oidc_client(region).await?;
+    let output = client
+        .create_token()
+        .client_id(&registration.client_id)
+        .client_secret(&registration.client_secret)
+        .grant_type(REFRESH_TOKEN_GRANT_TYPE)
+        .refresh_token(refresh_token)
+        .send()
+        .await
+        .map_err(|err| Error::AwsSdk(format_sdk_error(&err)))?;
+    sso_token_from_sdk(&output)
+}
+
+fn sso_token_from_sdk(
+    output: &aws_sdk_ssooidc::operation::create_token::CreateTokenOutput,
+) -> Result<SsoToken> {
+    Ok(SsoToken {
+        access_token: output
+            .access_token()
+            .ok_or_else(|| Error::AwsSdk("missing access_token".into()))?
+            .to_string(),
+        expires_in: output.expires_in().max(0) as u64,
+        refresh_token: output
+            .refresh_token()
+            .filter(|token| !token.is_empty())
+            .map(ToString::to_string),
+    })
+}
+
+pub async fn list_accounts(
+    access_token: &str,
+    region: &str,
+    throttle: &Throttle,
+) -> Result<Vec<Account>> {
     let client = sso_client(region).await?;
     let mut accounts = Vec::new();
     let mut next_token = None;
@@ -39,7 +220,7 @@ pub async fn list_accounts(access_token: &str, region: &str) -> Result<Vec<Accou
             request = request.next_token(token);
         }
         let output: aws_sdk_sso::operation::list_accounts::ListAccountsOutput =
-            retry_sdk(|| request.clone().send(), 5).await?;
+            retry_sdk(|| request.clone().send(), throttle).await?;
 
         accounts.extend(output.account_list().iter().filter_map(account_from_sdk));
 
@@ -56,6 +237,7 @@ pub async fn list_account_roles(
     access_token: &str,
     region: &str,
     account_id: &str,
+    throttle: &Throttle,
 ) -> Result<Vec<Role>> {
     let client = sso_client(region).await?;
     let mut roles = Vec::new();
@@ -70,7 +252,7 @@ pub async fn list_account_roles(
             request = request.next_token(token);
         }
         let output: aws_sdk_sso::operation::list_account_roles::ListAccountRolesOutput =
-            retry_sdk(|| request.clone().send(), 5).await?;
+            retry_sdk(|| request.clone().send(), throttle).await?;
 
         roles.extend(output.role_list().iter().filter_map(role_from_sdk));
 
@@ -83,7 +265,9 @@ pub async fn list_account_roles(
     Ok(roles)
 }
 
-async fn retry_sdk<F, Fut, T, E>(mut call: F, max_attempts: usize) -> Result<T>
+/// Send a request through `throttle`, retrying throttled attempts with exponential backoff.
+/// Each throttle also slows the shared bucket, so concurrent callers back off together.
+async fn retry_sdk<F, Fut, T, E>(mut call: F, throttle: &Throttle) -> Result<T>
 where
     F: FnMut() -> Fut,
     Fut: std::future::Future<Output = std::result::Result<T, SmithySdkError<E, SmithyResponse>>>,
@@ -91,16 +275,29 @@ where
 {
     let mut attempt = 1;
     loop {
+        throttle.acquire().await;
         match call().await {
-            Ok(output) => return Ok(output),
+            Ok(output) => {
+                throttle.succeeded();
+                return Ok(output);
+            }
             Err(err) => {
                 let message = format_sdk_error(&err);
-                if attempt >= max_attempts || !is_throttle_error(err.meta().code(), &message) {
+                if attempt >= MAX_ATTEMPTS || !is_throttle_error(err.meta().code(), &message) {
                     return Err(Error::AwsSdk(message));
                 }
-                let backoff_ms = 500_u64.saturating_mul(2_u64.pow((attempt - 1) as u32));
-                tracing::debug!(attempt, backoff_ms, "throttled by aws sdk, backing off");
-                tokio::time::sleep(std::time::Duration::from_millis(backoff_ms)).await;
+                throttle.throttled();
+                let backoff = std::time::Duration::from_millis(
+                    500_u64.saturating_mul(2_u64.pow(attempt - 1)),
+                )
+                .min(MAX_BACKOFF);
+                tracing::debug!(
+                    attempt,
+                    backoff_ms = backoff.as_millis() as u64,
+                    rate = throttle.rate(),
+                    "throttled by aws sdk, backing off"
+                );
+                tokio::time::sleep(backoff).await;
                 attempt += 1;
             }
         }
@@ -201,6 +398,17 @@ pub async fn get_caller_arn(region: &str, creds: &AwsRoleCredentials) -> Result<
         .ok_or_else(|| Error::AwsSdk("missing caller arn".into()))
 }
 
+/// Optional `AssumeRole` parameters beyond the role ARN and session name.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AssumeRoleOptions<'a> {
+    /// Managed policies applied as session policies (they can only restrict the role).
+    pub policy_arns: &'a [String],
+    /// Inline session policy document.
+    pub inline_policy: Option<&'a str>,
+    pub external_id: Option<&'a str>,
+    pub duration_seconds: Option<i32>,
+}
+
 /// Re-assume `role_arn` with a restrictive session policy, producing scoped-down credentials.
 ///
 /// A session policy can only *restrict* the effective permissions of the role, never expand
@@ -213,20 +421,44 @@ pub async fn assume_role_scoped(
     policy_arns: &[String],
     inline_policy: Option<&str>,
     session_name: &str,
+) -> Result<AwsRoleCredentials> {
+    assume_role(
+        region,
+        creds,
+        role_arn,
+        session_name,
+        AssumeRoleOptions {
+            policy_arns,
+            inline_policy,
+            ..AssumeRoleOptions::default()
+        },
+    )
+    .await
+}
+
+/// Assume `role_arn` with the given credentials (e.g. to chain from an SSO role).
+pub async fn assume_role(
+    region: &str,
+    creds: &AwsRoleCredentials,
+    role_arn: &str,
+    session_name: &str,
+    options: AssumeRoleOptions<'_>,
 ) -> Result<AwsRoleCredentials> {
     let client = sts_client(region, creds).await?;
     let mut request = client
         .assume_role()
         .role_arn(role_arn)
-        .role_session_name(session_name);
-    for arn in policy_arns {
+        .role_session_name(session_name)
+        .set_external_id(options.external_id.map(str::to_string))
+        .set_duration_seconds(options.duration_seconds);
+    for arn in options.policy_arns {
         request = request.policy_arns(
             aws_sdk_sts::types::PolicyDescriptorType::builder()
                 .arn(arn)
                 .build(),
         );
     }
-    if let Some(policy) = inline_policy {
+    if let Some(policy) = options.inline_policy {
         request = request.policy(policy);
     }
     let output = request
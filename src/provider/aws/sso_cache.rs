@@ -2,11 +2,37 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
 use crate::error::{Error, Result};
 use crate::model::CacheEntry;
 use crate::ui;
+use crate::util::{format_duration, write_atomic};
 use tracing::debug;
 
+/// A token file in `~/.aws/sso/cache`, in the layout the AWS CLI and SDKs share.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsoTokenFile {
+    #[serde(default)]
+    pub start_url: String,
+    pub region: String,
+    pub access_token: String,
+    pub expires_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_expires_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// Fields roleman doesn't read (such as the CLI's `scopes`), kept when the file is rewritten.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
 pub fn load_valid_cache(start_url: &str) -> Result<CacheEntry> {
     let aws_cache_dir = aws_sso_cache_dir()?;
     let entries = read_cache_entries_from_dir(&aws_cache_dir, start_url)?;
@@ -40,16 +66,122 @@ pub fn load_valid_cache(start_url: &str) -> Result<CacheEntry> {
     Err(Error::MissingCache)
 }
 
+/// Latest `expiresAt` (unix seconds) among the cached tokens for `start_url`, expired or not,
+/// without logging anything; `None` when no token is cached.
+pub fn latest_token_expiry(start_url: &str) -> Result<Option<u64>> {
+    let aws_cache_dir = aws_sso_cache_dir()?;
+    Ok(read_token_files_from_dir(&aws_cache_dir, start_url)
+        .iter()
+        .filter_map(|(_, token)| aws_time_to_epoch(&token.expires_at).ok())
+        .max())
+}
+
+/// Find the cached token for `start_url` that can be renewed with its refresh token,
+/// along with the file it was read from. Tokens whose client registration has expired
+/// are skipped, since `CreateToken` would reject them.
+pub fn load_refreshable_token(start_url: &str) -> Result<Option<(PathBuf, SsoTokenFile)>> {
+    let aws_cache_dir = aws_sso_cache_dir()?;
+    let mut best: Option<(PathBuf, SsoTokenFile, u64)> = None;
+    for (path, token) in read_token_files_from_dir(&aws_cache_dir, start_url) {
+        if token.refresh_token.is_none()
+            || token.client_id.is_none()
+            || token.client_secret.is_none()
+        {
+            continue;
+        }
+        if let Some(registration_expires_at) = &token.registration_expires_at
+            && is_expired(registration_expires_at).unwrap_or(true)
+        {
+            continue;
+        }
+        let expires_epoch = aws_time_to_epoch(&token.expires_at).unwrap_or_default();
+        let should_replace = match &best {
+            Some((_, _, best_epoch)) => expires_epoch > *best_epoch,
+            None => true,
+        };
+        if should_replace {
+            best = Some((path, token, expires_epoch));
+        }
+    }
+    Ok(best.map(|(path, token, _)| (path, token)))
+}
+
+/// Write a token for an `sso-session` to the file the AWS CLI would use for it
+/// (`sha1(session_name).json`), so roleman-minted tokens are shared with other tools.
+pub fn write_session_token(session_name: &str, token: &SsoTokenFile) -> Result<CacheEntry> {
+    let path = aws_sso_cache_dir()?.join(session_cache_filename(session_name));
+    write_token_file(&path, token)
+}
+
+/// Replace a token file, e.g. after refreshing the token it holds. The file holds the access
+/// and refresh tokens, so it is written 0600 and swapped in atomically.
+pub fn write_token_file(path: &Path, token: &SsoTokenFile) -> Result<CacheEntry> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| Error::Config(err.to_string()))?;
+    }
+    let data = serde_json::to_string(token).map_err(|_| Error::CacheParse {
+        path: path.to_path_buf(),
+    })?;
+    write_atomic(path, data.as_bytes(), true).map_err(|err| Error::Config(err.to_string()))?;
+    debug!(path = %path.display(), expires_at = %token.expires_at, "wrote sso token cache");
+    Ok(cache_entry(token))
+}
+
+/// Log how long a freshly refreshed token is valid for, like [`load_valid_cache`] does.
+pub fn report_refreshed(entry: &CacheEntry) {
+    let remaining = time_until_expiry(&entry.expires_at).unwrap_or_default();
+    eprintln!(
+        "{}",
+        ui::info(&format!(
+            "Refreshed cached SSO token (valid for {}).",
+            format_duration(remaining)
+        ))
+    );
+}
+
+/// RFC 3339 timestamp `seconds` from now, as stored in `expiresAt`.
+pub fn expires_at_from_now(seconds: u64) -> String {
+    let now = time::OffsetDateTime::now_utc().unix_timestamp();
+    aws_time_from_epoch(now.saturating_add(seconds as i64))
+}
+
+/// RFC 3339 timestamp for a unix-seconds value, as stored in `registrationExpiresAt`.
+pub fn aws_time_from_epoch(seconds: i64) -> String {
+    match time::OffsetDateTime::from_unix_timestamp(seconds) {
+        Ok(value) => format_aws_time(value),
+        Err(_) => seconds.to_string(),
+    }
+}
+
+fn format_aws_time(value: time::OffsetDateTime) -> String {
+    value
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_else(|_| value.unix_timestamp().to_string())
+}
+
+fn session_cache_filename(session_name: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(session_name.as_bytes());
+    format!("{:x}.json", hasher.finalize())
+}
+
 fn aws_sso_cache_dir() -> Result<PathBuf> {
     let home = std::env::var("HOME").map_err(|_| Error::MissingHome)?;
     Ok(Path::new(&home).join(".aws").join("sso").join("cache"))
 }
 
 fn read_cache_entries_from_dir(dir: &Path, start_url: &str) -> Result<Vec<CacheEntry>> {
-    let mut entries = Vec::new();
+    Ok(read_token_files_from_dir(dir, start_url)
+        .iter()
+        .map(|(_, token)| cache_entry(token))
+        .collect())
+}
+
+fn read_token_files_from_dir(dir: &Path, start_url: &str) -> Vec<(PathBuf, SsoTokenFile)> {
+    let mut tokens = Vec::new();
     let read_dir = match fs::read_dir(dir) {
         Ok(read_dir) => read_dir,
-        Err(_) => return Ok(entries),
+        Err(_) => return tokens,
     };
     for entry in read_dir.flatten() {
         let path = entry.path();
@@ -60,31 +192,25 @@ fn read_cache_entries_from_dir(dir: &Path, start_url: &str) -> Result<Vec<CacheE
             Ok(data) => data,
             Err(_) => continue,
         };
-        let value: serde_json::Value = match serde_json::from_str(&data) {
-            Ok(value) => value,
+        // Client registration files share this directory but carry no token.
+        let token: SsoTokenFile = match serde_json::from_str(&data) {
+            Ok(token) => token,
             Err(_) => continue,
         };
-        let start = value
-            .get("startUrl")
-            .and_then(|v| v.as_str())
-            .unwrap_or_default();
-        if start != start_url {
+        if token.start_url != start_url {
             continue;
         }
-        let access_token = value.get("accessToken").and_then(|v| v.as_str());
-        let region = value.get("region").and_then(|v| v.as_str());
-        let expires_at = value.get("expiresAt").and_then(|v| v.as_str());
-        if let (Some(access_token), Some(region), Some(expires_at)) =
-            (access_token, region, expires_at)
-        {
-            entries.push(CacheEntry {
-                access_token: access_token.to_string(),
-                expires_at: expires_at.to_string(),
-                region: region.to_string(),
-            });
-        }
+        tokens.push((path, token));
+    }
+    tokens
+}
+
+fn cache_entry(token: &SsoTokenFile) -> CacheEntry {
+    CacheEntry {
+        access_token: token.access_token.clone(),
+        expires_at: token.expires_at.clone(),
+        region: token.region.clone(),
     }
-    Ok(entries)
 }
 
 fn is_expired(expires_at: &str) -> Result<bool> {
@@ -117,20 +243,6 @@ fn time_until_expiry(expires_at: &str) -> Result<std::time::Duration> {
     Ok(std::time::Duration::from_secs(expires_epoch - now))
 }
 
-fn format_duration(duration: std::time::Duration) -> String {
-    let total = duration.as_secs();
-    let hours = total / 3600;
-    let minutes = (total % 3600) / 60;
-    let seconds = total % 60;
-    if hours > 0 {
-        format!("{hours}h {minutes}m")
-    } else if minutes > 0 {
-        format!("{minutes}m {seconds}s")
-    } else {
-        format!("{seconds}s")
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,4 +291,107 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn writes_session_token_where_the_aws_cli_looks() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("HOME").ok();
+        unsafe {
+            std::env::set_var("HOME", temp.path());
+        }
+
+        let token = SsoTokenFile {
+            start_url: "https://example.awsapps.com/start".into(),
+            region: "eu-west-1".into(),
+            access_token: "fresh-token".into(),
+            expires_at: expires_at_from_now(600),
+            client_id: Some("client".into()),
+            client_secret: Some("secret".into()),
+            registration_expires_at: Some(aws_time_from_epoch(4_102_444_800)),
+            refresh_token: None,
+            extra: serde_json::Map::new(),
+        };
+        write_session_token("roleman-work", &token).unwrap();
+
+        // Same name `aws sso login --sso-session roleman-work` would use.
+        let expected = aws_sso_cache_dir()
+            .unwrap()
+            .join("dfcc838110d6c2e58888a6a6179ca2b2a4b13627.json");
+        let raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&expected).unwrap()).unwrap();
+        assert_eq!(raw["accessToken"], "fresh-token");
+        assert_eq!(raw["registrationExpiresAt"], "2100-01-01T00:00:00Z");
+        assert!(raw.get("refreshToken").is_none());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&expected).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let loaded = load_valid_cache("https://example.awsapps.com/start").unwrap();
+        assert_eq!(loaded.access_token, "fresh-token");
+        assert_eq!(loaded.region, "eu-west-1");
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("HOME", value);
+            } else {
+                std::env::remove_var("HOME");
+            }
+        }
+    }
+
+    #[test]
+    fn finds_expired_token_that_can_be_refreshed() {
+        let _lock = crate::test_support::lock_env();
+        let temp = TempDir::new().unwrap();
+        let previous = std::env::var("HOME").ok();
+        unsafe {
+            std::env::set_var("HOME", temp.path());
+        }
+        let cache_dir = aws_sso_cache_dir().unwrap();
+        fs::create_dir_all(&cache_dir).unwrap();
+        let start_url = "https://example.awsapps.com/start";
+        let token = |registration_expires_at: &str| {
+            serde_json::json!({
+                "startUrl": start_url,
+                "region": "us-east-1",
+                "accessToken": "stale",
+                "expiresAt": "2000-01-01T00:00:00Z",
+                "clientId": "client",
+                "clientSecret": "secret",
+                "registrationExpiresAt": registration_expires_at,
+                "refreshToken": "refresh",
+            })
+        };
+        fs::write(
+            cache_dir.join("dead.json"),
+            token("2000-01-01T00:00:00Z").to_string(),
+        )
+        .unwrap();
+        assert!(load_refreshable_token(start_url).unwrap().is_none());
+        assert!(matches!(
+            load_valid_cache(start_url),
+            Err(Error::MissingCache)
+        ));
+
+        fs::write(
+            cache_dir.join("live.json"),
+            token("2099-01-01T00:00:00Z").to_string(),
+        )
+        .unwrap();
+        let (path, found) = load_refreshable_token(start_url).unwrap().unwrap();
+        assert_eq!(path, cache_dir.join("live.json"));
+        assert_eq!(found.refresh_token.as_deref(), Some("refresh"));
+
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("HOME", value);
+            } else {
+                std::env::remove_var("HOME");
+            }
+        }
+    }
 }
@@ -0,0 +1,164 @@
+//! Adaptive client-side rate limiting for SSO API calls.
+//!
+//! Listing roles fans out one `ListAccountRoles` call per account. Every call takes a token
+//! from one shared bucket, so when SSO answers `TooManyRequestsException` the whole fan-out
+//! slows down together instead of each task retrying into the same limit. The refill rate
+//! halves when a call is throttled and creeps back towards the configured maximum as calls
+//! succeed.
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::config::DEFAULT_MAX_REQUESTS_PER_SECOND;
+
+/// The rate never drops below this, so a heavily throttled listing still makes progress.
+const MIN_RATE: f64 = 0.5;
+/// Requests per second regained with each successful call.
+const RECOVERY_STEP: f64 = 0.25;
+/// Throttles within this long of the last slowdown are answers to requests sent before it,
+/// so they don't lower the rate again.
+const SLOWDOWN_WINDOW: Duration = Duration::from_secs(1);
+
+/// A token bucket whose rate adapts to throttling, shared by concurrent callers.
+pub struct Throttle {
+    max_rate: f64,
+    state: Mutex<Bucket>,
+}
+
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    slowed_at: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new(max_requests_per_second: u32) -> Self {
+        let max_rate = f64::from(max_requests_per_second.max(1));
+        Self {
+            max_rate,
+            state: Mutex::new(Bucket {
+                rate: max_rate,
+                tokens: max_rate,
+                refilled_at: Instant::now(),
+                slowed_at: None,
+            }),
+        }
+    }
+
+    /// Wait until a request may be sent.
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take a token, or say how long until one is available.
+    fn try_acquire(&self, now: Instant) -> Option<Duration> {
+        let mut bucket = self.lock();
+        bucket.refill(now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return None;
+        }
+        Some(Duration::from_secs_f64((1.0 - bucket.tokens) / bucket.rate))
+    }
+
+    /// The service throttled a call: halve the rate and drain the bucket.
+    pub fn throttled(&self) {
+        self.slow_down(Instant::now());
+    }
+
+    fn slow_down(&self, now: Instant) {
+        let mut bucket = self.lock();
+        if bucket
+            .slowed_at
+            .is_some_and(|slowed_at| now.saturating_duration_since(slowed_at) < SLOWDOWN_WINDOW)
+        {
+            return;
+        }
+        bucket.refill(now);
+        bucket.rate = (bucket.rate / 2.0).max(MIN_RATE);
+        bucket.tokens = 0.0;
+        bucket.slowed_at = Some(now);
+        tracing::debug!(rate = bucket.rate, "throttled, lowering request rate");
+    }
+
+    /// A call went through: recover towards the maximum rate.
+    pub fn succeeded(&self) {
+        let mut bucket = self.lock();
+        bucket.rate = (bucket.rate + RECOVERY_STEP).min(self.max_rate);
+    }
+
+    /// Current requests per second.
+    pub fn rate(&self) -> f64 {
+        self.lock().rate
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Bucket> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_REQUESTS_PER_SECOND)
+    }
+}
+
+impl Bucket {
+    /// Add the tokens earned since the last refill, holding at most a second's worth.
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.rate.max(1.0));
+        self.refilled_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paces_requests_once_the_burst_is_spent() {
+        let throttle = Throttle::new(2);
+        let now = Instant::now();
+        assert_eq!(throttle.try_acquire(now), None);
+        assert_eq!(throttle.try_acquire(now), None);
+        let wait = throttle.try_acquire(now).expect("bucket should be empty");
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+        assert_eq!(throttle.try_acquire(now + Duration::from_millis(500)), None);
+    }
+
+    #[test]
+    fn throttling_halves_the_rate_once_per_window() {
+        let throttle = Throttle::new(20);
+        let now = Instant::now();
+        throttle.slow_down(now);
+        throttle.slow_down(now + Duration::from_millis(50));
+        assert_eq!(throttle.rate(), 10.0);
+        assert!(
+            throttle
+                .try_acquire(now + Duration::from_millis(50))
+                .is_some()
+        );
+
+        throttle.slow_down(now + SLOWDOWN_WINDOW);
+        assert_eq!(throttle.rate(), 5.0);
+
+        for _ in 0..100 {
+            throttle.succeeded();
+        }
+        assert_eq!(throttle.rate(), 20.0);
+    }
+
+    #[test]
+    fn rate_has_a_floor() {
+        let throttle = Throttle::new(1);
+        let now = Instant::now();
+        for step in 0..5 {
+            throttle.slow_down(now + SLOWDOWN_WINDOW * step);
+        }
+        assert_eq!(throttle.rate(), MIN_RATE);
+    }
+}
@@ -0,0 +1,125 @@
+//! Application Default Credentials: the user's gcloud sign-in that roleman impersonates from.
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// File name gcloud writes under its config directory.
+const ADC_FILE_NAME: &str = "application_default_credentials.json";
+
+/// An `authorized_user` ADC file (written by `gcloud auth application-default login`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthorizedUser {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct AdcFile {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// Where ADC is read from: `GOOGLE_APPLICATION_CREDENTIALS`, then the gcloud config directory
+/// (`CLOUDSDK_CONFIG`, else `~/.config/gcloud`).
+pub fn adc_path() -> Result<PathBuf> {
+    if let Ok(path) = std::env::var("GOOGLE_APPLICATION_CREDENTIALS")
+        && !path.is_empty()
+    {
+        return Ok(PathBuf::from(path));
+    }
+    if let Ok(dir) = std::env::var("CLOUDSDK_CONFIG")
+        && !dir.is_empty()
+    {
+        return Ok(PathBuf::from(dir).join(ADC_FILE_NAME));
+    }
+    let home = std::env::var("HOME").map_err(|_| Error::MissingHome)?;
+    Ok(PathBuf::from(home)
+        .join(".config")
+        .join("gcloud")
+        .join(ADC_FILE_NAME))
+}
+
+/// Load user ADC, or `None` when no ADC file exists yet.
+pub fn load_authorized_user() -> Result<Option<AuthorizedUser>> {
+    let path = adc_path()?;
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(Error::Config(format!(
+                "failed to read {}: {err}",
+                path.display()
+            )));
+        }
+    };
+    let file: AdcFile =
+        serde_json::from_str(&contents).map_err(|_| Error::CacheParse { path: path.clone() })?;
+    if file.kind != "authorized_user" {
+        return Err(Error::Config(format!(
+            "{} holds `{}` credentials; roleman impersonates service accounts from a user \
+             sign-in (`gcloud auth application-default login`)",
+            path.display(),
+            file.kind
+        )));
+    }
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|_| Error::CacheParse { path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn with_adc_env<T>(path: &std::path::Path, f: impl FnOnce() -> T) -> T {
+        let _lock = crate::test_support::lock_env();
+        let previous = std::env::var("GOOGLE_APPLICATION_CREDENTIALS").ok();
+        unsafe {
+            std::env::set_var("GOOGLE_APPLICATION_CREDENTIALS", path);
+        }
+        let result = f();
+        unsafe {
+            if let Some(value) = previous {
+                std::env::set_var("GOOGLE_APPLICATION_CREDENTIALS", value);
+            } else {
+                std::env::remove_var("GOOGLE_APPLICATION_CREDENTIALS");
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn loads_authorized_user() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("adc.json");
+        std::fs::write(
+            &path,
+            r#"{"type":"authorized_user","client_id":"id","client_secret":"secret","refresh_token":"refresh"}"#,
+        )
+        .unwrap();
+        let user = with_adc_env(&path, load_authorized_user).unwrap().unwrap();
+        assert_eq!(user.client_id, "id");
+        assert_eq!(user.refresh_token, "refresh");
+    }
+
+    #[test]
+    fn missing_file_is_not_an_error() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("missing.json");
+        assert!(with_adc_env(&path, load_authorized_user).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_service_account_keys() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("key.json");
+        std::fs::write(&path, r#"{"type":"service_account","private_key":"..."}"#).unwrap();
+        let err = with_adc_env(&path, load_authorized_user).unwrap_err();
+        assert!(err.to_string().contains("`service_account` credentials"));
+    }
+}
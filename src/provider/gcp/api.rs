@@ -0,0 +1,334 @@
+//! Thin REST client for the Google APIs the GCP provider needs.
+//!
+//! Every service URL can be redirected with `ROLEMAN_GCP_ENDPOINT` (a base URL; the
+//! service name becomes the first path segment), so tests run against a local mock.
+
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::error::{Error, Result};
+
+/// OAuth scope requested for minted service-account tokens.
+pub const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+/// Lifetime requested for service-account tokens (the API maximum without org policy changes).
+const TOKEN_LIFETIME: &str = "3600s";
+const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// A project the caller can see.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub project_id: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+/// A service account the caller can list.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceAccount {
+    pub email: String,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+/// A bearer token and when it stops working.
+#[derive(Debug, Clone)]
+pub struct AccessToken {
+    pub token: String,
+    pub expiration_ms: u64,
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new)
+}
+
+/// Base URL for a Google service, honoring `ROLEMAN_GCP_ENDPOINT`.
+fn endpoint(service: &str) -> String {
+    match std::env::var("ROLEMAN_GCP_ENDPOINT") {
+        Ok(base) if !base.is_empty() => format!("{}/{service}", base.trim_end_matches('/')),
+        _ => format!("https://{service}.googleapis.com"),
+    }
+}
+
+/// Exchange an OAuth refresh token for a user access token.
+pub async fn refresh_access_token(
+    client_id: &str,
+    client_secret: &str,
+    refresh_token: &str,
+) -> Result<AccessToken> {
+    #[derive(Deserialize)]
+    struct Response {
+        access_token: String,
+        expires_in: u64,
+    }
+    let response: Response = send(
+        "refresh user token",
+        client()
+            .post(format!("{}/token", endpoint("oauth2")))
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", client_id),
+                ("client_secret", client_secret),
+                ("refresh_token", refresh_token),
+            ]),
+    )
+    .await?;
+    Ok(AccessToken {
+        token: response.access_token,
+        expiration_ms: now_ms() + response.expires_in * 1000,
+    })
+}
+
+/// List active projects visible to the caller.
+pub async fn list_projects(access_token: &str) -> Result<Vec<Project>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Page {
+        #[serde(default)]
+        projects: Vec<Project>,
+        next_page_token: Option<String>,
+    }
+    let url = format!("{}/v1/projects", endpoint("cloudresourcemanager"));
+    let mut projects = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut request = client()
+            .get(&url)
+            .bearer_auth(access_token)
+            .query(&[("filter", "lifecycleState:ACTIVE")]);
+        if let Some(token) = &page_token {
+            request = request.query(&[("pageToken", token)]);
+        }
+        let page: Page = send("list projects", request).await?;
+        projects.extend(page.projects);
+        match page.next_page_token.filter(|token| !token.is_empty()) {
+            Some(token) => page_token = Some(token),
+            None => break,
+        }
+    }
+    Ok(projects)
+}
+
+/// List the service accounts in a project; `None` when the caller may not see them (403 or
+/// 404), which is common for projects they can see but not manage.
+pub async fn list_service_accounts(
+    access_token: &str,
+    project_id: &str,
+) -> Result<Option<Vec<ServiceAccount>>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Page {
+        #[serde(default)]
+        accounts: Vec<ServiceAccount>,
+        next_page_token: Option<String>,
+    }
+    let url = format!(
+        "{}/v1/projects/{}/serviceAccounts",
+        endpoint("iam"),
+        urlencoding::encode(project_id)
+    );
+    let mut accounts = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut request = client().get(&url).bearer_auth(access_token);
+        if let Some(token) = &page_token {
+            request = request.query(&[("pageToken", token)]);
+        }
+        let Some(page) = send_unless_hidden::<Page>("list service accounts", request).await? else {
+            return Ok(None);
+        };
+        accounts.extend(page.accounts);
+        match page.next_page_token.filter(|token| !token.is_empty()) {
+            Some(token) => page_token = Some(token),
+            None => break,
+        }
+    }
+    Ok(Some(accounts))
+}
+
+/// Mint a short-lived access token for a service account by impersonating it.
+pub async fn generate_access_token(
+    access_token: &str,
+    service_account: &str,
+) -> Result<AccessToken> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Response {
+        access_token: String,
+        expire_time: String,
+    }
+    let url = format!(
+        "{}/v1/projects/-/serviceAccounts/{}:generateAccessToken",
+        endpoint("iamcredentials"),
+        urlencoding::encode(service_account)
+    );
+    let response: Response = send(
+        "generate access token",
+        client()
+            .post(url)
+            .bearer_auth(access_token)
+            .json(&serde_json::json!({
+                "scope": [CLOUD_PLATFORM_SCOPE],
+                "lifetime": TOKEN_LIFETIME,
+            })),
+    )
+    .await?;
+    Ok(AccessToken {
+        token: response.access_token,
+        expiration_ms: parse_rfc3339_ms(&response.expire_time)?,
+    })
+}
+
+/// Exchange a token for a downscoped one restricted by a Credential Access Boundary.
+///
+/// `boundary` is the JSON `{"accessBoundary": ...}` document. The downscoped token never
+/// outlives the source token.
+pub async fn downscope_token(source: &AccessToken, boundary: &str) -> Result<AccessToken> {
+    #[derive(Deserialize)]
+    struct Response {
+        access_token: String,
+        expires_in: Option<u64>,
+    }
+    let response: Response = send(
+        "downscope token",
+        client()
+            .post(format!("{}/v1/token", endpoint("sts")))
+            .form(&[
+                ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+                ("subject_token_type", ACCESS_TOKEN_TYPE),
+                ("requested_token_type", ACCESS_TOKEN_TYPE),
+                ("subject_token", source.token.as_str()),
+                ("options", boundary),
+            ]),
+    )
+    .await?;
+    let expiration_ms = response
+        .expires_in
+        .map(|seconds| (now_ms() + seconds * 1000).min(source.expiration_ms))
+        .unwrap_or(source.expiration_ms);
+    Ok(AccessToken {
+        token: response.access_token,
+        expiration_ms,
+    })
+}
+
+async fn send<T: DeserializeOwned>(action: &str, request: reqwest::RequestBuilder) -> Result<T> {
+    let (status, body) = fetch(action, request).await?;
+    parse_response(action, status, &body)
+}
+
+/// Like [`send`], but `None` when the API answers 403 or 404. Other failures, such as 5xx,
+/// 429 or network errors, are still errors.
+async fn send_unless_hidden<T: DeserializeOwned>(
+    action: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<Option<T>> {
+    let (status, body) = fetch(action, request).await?;
+    if matches!(
+        status,
+        reqwest::StatusCode::FORBIDDEN | reqwest::StatusCode::NOT_FOUND
+    ) {
+        return Ok(None);
+    }
+    parse_response(action, status, &body).map(Some)
+}
+
+async fn fetch(
+    action: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<(reqwest::StatusCode, String)> {
+    let response = request
+        .send()
+        .await
+        .map_err(|err| Error::Gcp(format!("{action}: {err}")))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|err| Error::Gcp(format!("{action}: {err}")))?;
+    Ok((status, body))
+}
+
+fn parse_response<T: DeserializeOwned>(
+    action: &str,
+    status: reqwest::StatusCode,
+    body: &str,
+) -> Result<T> {
+    if !status.is_success() {
+        return Err(Error::Gcp(format!(
+            "{action} failed ({status}): {}",
+            error_message(body)
+        )));
+    }
+    serde_json::from_str(body)
+        .map_err(|err| Error::Gcp(format!("{action}: unexpected response: {err}")))
+}
+
+/// The `error.message` of a Google error body (or `error_description` for OAuth), else the body.
+fn error_message(body: &str) -> String {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(body) else {
+        return body.trim().to_string();
+    };
+    value
+        .pointer("/error/message")
+        .or_else(|| value.get("error_description"))
+        .and_then(|message| message.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+fn parse_rfc3339_ms(value: &str) -> Result<u64> {
+    let parsed = time::OffsetDateTime::parse(value, &time::format_description::well_known::Rfc3339)
+        .map_err(|err| Error::Gcp(format!("invalid expireTime {value}: {err}")))?;
+    Ok((parsed.unix_timestamp_nanos() / 1_000_000).max(0) as u64)
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_google_and_oauth_error_messages() {
+        assert_eq!(
+            error_message(
+                r#"{"error":{"code":403,"message":"Permission denied","status":"PERMISSION_DENIED"}}"#
+            ),
+            "Permission denied"
+        );
+        assert_eq!(
+            error_message(
+                r#"{"error":"invalid_grant","error_description":"Token has been expired or revoked."}"#
+            ),
+            "Token has been expired or revoked."
+        );
+        assert_eq!(
+            error_message("upstream connect error"),
+            "upstream connect error"
+        );
+    }
+
+    #[test]
+    fn parses_expire_time() {
+        assert_eq!(
+            parse_rfc3339_ms("2024-01-01T00:00:00Z").unwrap(),
+            1_704_067_200_000
+        );
+        assert_eq!(
+            parse_rfc3339_ms("2024-01-01T00:00:00.250Z").unwrap(),
+            1_704_067_200_250
+        );
+        assert!(parse_rfc3339_ms("soon").is_err());
+    }
+}
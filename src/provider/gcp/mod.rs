@@ -0,0 +1,458 @@
+//! Google Cloud implementation of [`CloudProvider`].
+//!
+//! Targets are service accounts: the "account" is the project and the "role" is the service
+//! account email. Credentials are short-lived access tokens minted by impersonating the
+//! service account from the user's Application Default Credentials; `--readonly` exchanges
+//! that token for one restricted by a Credential Access Boundary.
+
+pub mod adc;
+pub mod api;
+
+use std::any::Any;
+use std::io::IsTerminal;
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::config::{ReadonlyPolicy, SsoIdentity};
+use crate::error::{Error, Result};
+use crate::model::{ListingFailure, RoleChoice, RoleListing};
+use crate::provider::{
+    AccessScope, ActiveMarker, CloudProvider, EnvVar, PostLoginActions, ProfileBinding,
+    ProviderCredentials, ProviderSession, active_marker,
+};
+use crate::ui;
+
+/// Placeholder in an inline `readonly_policy` replaced with the selected project ID.
+const PROJECT_PLACEHOLDER: &str = "{project}";
+
+/// Variables [`GcpCredentials::env_vars`] exports; the region pair only when a region is set.
+pub const CREDENTIAL_ENV_VARS: &[&str] = &[
+    "CLOUDSDK_AUTH_ACCESS_TOKEN",
+    "GOOGLE_OAUTH_ACCESS_TOKEN",
+    "CLOUDSDK_CORE_ACCOUNT",
+    "CLOUDSDK_CORE_PROJECT",
+    "GOOGLE_CLOUD_PROJECT",
+    "CLOUDSDK_COMPUTE_REGION",
+    "GOOGLE_CLOUD_REGION",
+];
+
+/// GCP provider bound to a single configured identity.
+pub struct GcpProvider {
+    identity: SsoIdentity,
+}
+
+impl GcpProvider {
+    pub fn new(identity: SsoIdentity) -> Self {
+        Self { identity }
+    }
+}
+
+/// GCP session state: a user access token obtained from ADC.
+pub struct GcpSession {
+    token: api::AccessToken,
+}
+
+impl ProviderSession for GcpSession {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+fn session_of(session: &dyn ProviderSession) -> Result<&GcpSession> {
+    session
+        .as_any()
+        .downcast_ref::<GcpSession>()
+        .ok_or_else(|| Error::Gcp("session/provider mismatch".into()))
+}
+
+/// A service-account access token ready for export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcpCredentials {
+    access_token: String,
+    expiration_ms: u64,
+    project_id: String,
+    service_account: String,
+    #[serde(default)]
+    region: Option<String>,
+}
+
+impl ProviderCredentials for GcpCredentials {
+    fn env_vars(&self, _binding: &ProfileBinding) -> Vec<EnvVar> {
+        let mut vars = vec![
+            EnvVar::new("CLOUDSDK_AUTH_ACCESS_TOKEN", self.access_token.clone()),
+            EnvVar::new("GOOGLE_OAUTH_ACCESS_TOKEN", self.access_token.clone()),
+            EnvVar::new("CLOUDSDK_CORE_ACCOUNT", self.service_account.clone()),
+            EnvVar::new("CLOUDSDK_CORE_PROJECT", self.project_id.clone()),
+            EnvVar::new("GOOGLE_CLOUD_PROJECT", self.project_id.clone()),
+        ];
+        if let Some(region) = &self.region {
+            vars.push(EnvVar::new("CLOUDSDK_COMPUTE_REGION", region.clone()));
+            vars.push(EnvVar::new("GOOGLE_CLOUD_REGION", region.clone()));
+        }
+        vars
+    }
+
+    fn expiration_ms(&self) -> u64 {
+        self.expiration_ms
+    }
+
+    fn to_cache_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(|err| Error::Gcp(err.to_string()))
+    }
+}
+
+#[async_trait::async_trait]
+impl CloudProvider for GcpProvider {
+    async fn ensure_session(
+        &self,
+        _ignore_cache: bool,
+        _post_login: PostLoginActions,
+    ) -> Result<Box<dyn ProviderSession>> {
+        if let Some(session) = self.resume_session().await? {
+            return Ok(session);
+        }
+        application_default_login()?;
+        self.resume_session().await?.ok_or_else(|| {
+            Error::Config("gcloud login did not write application default credentials".into())
+        })
+    }
+
+    async fn resume_session(&self) -> Result<Option<Box<dyn ProviderSession>>> {
+        let Some(user) = adc::load_authorized_user()? else {
+            return Ok(None);
+        };
+        let token =
+            api::refresh_access_token(&user.client_id, &user.client_secret, &user.refresh_token)
+                .await
+                .map_err(|err| {
+                    Error::Gcp(format!(
+                        "{err}. Run `gcloud auth application-default login` to sign in again"
+                    ))
+                })?;
+        Ok(Some(Box::new(GcpSession { token })))
+    }
+
+    async fn list_choices(&self, session: &dyn ProviderSession) -> Result<RoleListing> {
+        let token = &session_of(session)?.token.token;
+
+        let projects_spinner = ui::spinner("Fetching GCP projects...");
+        let mut projects = match api::list_projects(token).await {
+            Ok(projects) => projects,
+            Err(err) => {
+                projects_spinner.finish_and_clear();
+                return Err(err);
+            }
+        };
+        projects_spinner.finish_with_message(ui::success("Fetched GCP projects"));
+        projects.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+
+        let accounts_spinner = ui::spinner("Fetching service accounts for all projects...");
+        let accounts_by_project = futures::stream::iter(projects)
+            .map(|project| async move {
+                let accounts = api::list_service_accounts(token, &project.project_id).await;
+                (project, accounts)
+            })
+            .buffer_unordered(self.identity.list_concurrency())
+            .collect::<Vec<_>>()
+            .await;
+
+        let total = accounts_by_project.len();
+        let mut listing = RoleListing::default();
+        for (project, accounts) in accounts_by_project {
+            let account_name = if project.name.is_empty() {
+                project.project_id.clone()
+            } else {
+                project.name.clone()
+            };
+            // Seeing a project doesn't imply seeing its service accounts; skip those quietly.
+            // Anything else (outages, throttling) leaves the listing incomplete.
+            let accounts = match accounts {
+                Ok(Some(accounts)) => accounts,
+                Ok(None) => {
+                    debug!(project_id = %project.project_id, "no access to service accounts");
+                    continue;
+                }
+                Err(err) => {
+                    debug!(project_id = %project.project_id, error = %err, "listing failed");
+                    listing.failures.push(ListingFailure {
+                        account_id: project.project_id,
+                        account_name,
+                        error: err.to_string(),
+                        cached_roles: 0,
+                    });
+                    continue;
+                }
+            };
+            for account in accounts.into_iter().filter(|account| !account.disabled) {
+                listing.choices.push(RoleChoice {
+                    account_id: project.project_id.clone(),
+                    account_name: account_name.clone(),
+                    role_name: account.email,
+                });
+            }
+        }
+        if total > 0 && listing.failures.len() == total {
+            accounts_spinner.finish_and_clear();
+            return Err(Error::Gcp(format!(
+                "couldn't list service accounts for any project: {}",
+                listing.failures[0].error
+            )));
+        }
+        if listing.failures.is_empty() {
+            accounts_spinner.finish_with_message(ui::success("Fetched service accounts"));
+        } else {
+            accounts_spinner.finish_with_message(ui::warn(&format!(
+                "Fetched service accounts; {} of the projects failed",
+                listing.failures.len()
+            )));
+        }
+        Ok(listing)
+    }
+
+    async fn fetch_credentials(
+        &self,
+        session: &dyn ProviderSession,
+        choice: &RoleChoice,
+        scope: AccessScope,
+        region: Option<&str>,
+        _may_create: bool,
+    ) -> Result<Box<dyn ProviderCredentials>> {
+        let session = session_of(session)?;
+        let base = api::generate_access_token(&session.token.token, &choice.role_name).await?;
+        let token = match scope {
+            AccessScope::Full => base,
+            AccessScope::ReadOnly => {
+                let boundary = readonly_boundary(&self.identity, &choice.account_id)?;
+                api::downscope_token(&base, &boundary)
+                    .await
+                    .map_err(|err| Error::PermissionDrop(err.to_string()))?
+            }
+        };
+        Ok(Box::new(GcpCredentials {
+            access_token: token.token,
+            expiration_ms: token.expiration_ms,
+            project_id: choice.account_id.clone(),
+            service_account: choice.role_name.clone(),
+            region: region.map(str::to_string),
+        }))
+    }
+
+    fn credentials_from_cache_json(&self, json: &str) -> Result<Box<dyn ProviderCredentials>> {
+        let creds: GcpCredentials =
+            serde_json::from_str(json).map_err(|err| Error::Gcp(err.to_string()))?;
+        Ok(Box::new(creds))
+    }
+
+    fn ensure_profile(
+        &self,
+        _session: &dyn ProviderSession,
+        choice: &RoleChoice,
+        scope: AccessScope,
+        _region: Option<&str>,
+        _omit_role_name: bool,
+    ) -> Result<ProfileBinding> {
+        // Everything gcloud and the client libraries need travels in env vars; no config file.
+        Ok(ProfileBinding {
+            profile_name: self.profile_name(choice, scope, false),
+            config_file: None,
+            saved_config_file: None,
+        })
+    }
+
+    /// Read-only needs a configured Credential Access Boundary; see [`readonly_boundary`].
+    fn supports_scope(&self, scope: AccessScope) -> bool {
+        match scope {
+            AccessScope::Full => true,
+            AccessScope::ReadOnly => {
+                matches!(
+                    self.identity.readonly_policy,
+                    Some(ReadonlyPolicy::Inline(_))
+                )
+            }
+        }
+    }
+
+    fn profile_name(
+        &self,
+        choice: &RoleChoice,
+        scope: AccessScope,
+        _omit_role_name: bool,
+    ) -> String {
+        format!(
+            "{}/{}{}",
+            choice.account_id,
+            choice.role_name,
+            scope.profile_suffix()
+        )
+    }
+
+    fn console_url(&self, choice: &RoleChoice) -> String {
+        format!(
+            "https://console.cloud.google.com/home/dashboard?project={}",
+            urlencoding::encode(&choice.account_id)
+        )
+    }
+
+    fn cache_namespace(&self) -> String {
+        format!("gcp:{}", self.identity.name)
+    }
+
+    fn active_markers(
+        &self,
+        choices: &[RoleChoice],
+        scope: AccessScope,
+        min_lifetime: Duration,
+    ) -> Vec<ActiveMarker> {
+        let namespace = self.cache_namespace();
+        let current_account = std::env::var("CLOUDSDK_CORE_ACCOUNT").ok();
+        let current_project = std::env::var("CLOUDSDK_CORE_PROJECT").ok();
+        choices
+            .iter()
+            .map(|choice| {
+                if current_account.as_deref() != Some(choice.role_name.as_str())
+                    || current_project.as_deref() != Some(choice.account_id.as_str())
+                {
+                    return ActiveMarker::Inactive;
+                }
+                active_marker(
+                    &namespace,
+                    &self.identity,
+                    choice,
+                    scope,
+                    "CLOUDSDK_COMPUTE_REGION",
+                    min_lifetime,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Run `gcloud auth application-default login` when there is no ADC yet and a user is present.
+fn application_default_login() -> Result<()> {
+    let hint = "no application default credentials found; run \
+                `gcloud auth application-default login`";
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::Config(hint.to_string()));
+    }
+    eprintln!(
+        "{}",
+        ui::action("Signing in with `gcloud auth application-default login`...")
+    );
+    let status = std::process::Command::new("gcloud")
+        .args(["auth", "application-default", "login"])
+        .status()
+        .map_err(|err| Error::Config(format!("{hint} (failed to run gcloud: {err})")))?;
+    if !status.success() {
+        return Err(Error::Config(format!(
+            "{hint} (gcloud exited with {status})"
+        )));
+    }
+    Ok(())
+}
+
+/// The Credential Access Boundary for `--readonly`: the configured inline document, with
+/// `{project}` substituted.
+///
+/// There is no default: boundaries only restrict Cloud Storage, so a generic "viewer on the
+/// project" rule would be rejected or promise a read-only token it can't deliver.
+fn readonly_boundary(identity: &SsoIdentity, project_id: &str) -> Result<String> {
+    match &identity.readonly_policy {
+        Some(ReadonlyPolicy::Inline(document)) => {
+            Ok(document.replace(PROJECT_PLACEHOLDER, project_id))
+        }
+        Some(ReadonlyPolicy::PolicyArns(_)) => Err(Error::PermissionDrop(
+            "policy ARNs only apply to AWS identities; set an inline Credential Access \
+             Boundary as `readonly_policy` for GCP"
+                .to_string(),
+        )),
+        None => Err(Error::PermissionDrop(format!(
+            "--readonly for GCP identity `{}` needs a Credential Access Boundary; set \
+             `readonly_policy = {{ inline = '...' }}` with rules for the Cloud Storage buckets \
+             it may read",
+            identity.name
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderKind;
+
+    fn identity(readonly_policy: Option<ReadonlyPolicy>) -> SsoIdentity {
+        SsoIdentity {
+            name: "gcp".into(),
+            start_url: String::new(),
+            sso_region: String::new(),
+            provider: ProviderKind::Gcp,
+            accounts: Vec::new(),
+            ignore_roles: Vec::new(),
+            readonly_policy,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
+        }
+    }
+
+    #[test]
+    fn readonly_requires_an_explicit_boundary() {
+        assert!(readonly_boundary(&identity(None), "acme-prod").is_err());
+        let provider = GcpProvider::new(identity(None));
+        assert!(!provider.supports_scope(AccessScope::ReadOnly));
+        let policy = ReadonlyPolicy::Inline("{}".into());
+        assert!(GcpProvider::new(identity(Some(policy))).supports_scope(AccessScope::ReadOnly));
+    }
+
+    #[test]
+    fn inline_readonly_boundary_substitutes_project() {
+        let policy = ReadonlyPolicy::Inline(
+            r#"{"accessBoundary":{"accessBoundaryRules":[{"availableResource":"//storage.googleapis.com/projects/_/buckets/{project}-data","availablePermissions":["inRole:roles/storage.objectViewer"]}]}}"#.into(),
+        );
+        let boundary = readonly_boundary(&identity(Some(policy)), "acme-prod").unwrap();
+        assert!(boundary.contains("buckets/acme-prod-data"));
+        assert!(
+            readonly_boundary(
+                &identity(Some(ReadonlyPolicy::PolicyArns(vec!["arn:aws:x".into()]))),
+                "p"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn exports_gcloud_and_client_library_variables() {
+        let creds = GcpCredentials {
+            access_token: "ya29.token".into(),
+            expiration_ms: 0,
+            project_id: "acme-prod".into(),
+            service_account: "deploy@acme-prod.iam.gserviceaccount.com".into(),
+            region: Some("europe-west1".into()),
+        };
+        let binding = ProfileBinding {
+            profile_name: "ignored".into(),
+            config_file: None,
+            saved_config_file: None,
+        };
+        let vars = creds.env_vars(&binding);
+        assert!(vars.contains(&EnvVar::new("CLOUDSDK_AUTH_ACCESS_TOKEN", "ya29.token")));
+        assert!(vars.contains(&EnvVar::new("GOOGLE_OAUTH_ACCESS_TOKEN", "ya29.token")));
+        assert!(vars.contains(&EnvVar::new("CLOUDSDK_CORE_PROJECT", "acme-prod")));
+        assert!(vars.contains(&EnvVar::new("GOOGLE_CLOUD_PROJECT", "acme-prod")));
+        assert!(vars.contains(&EnvVar::new("CLOUDSDK_COMPUTE_REGION", "europe-west1")));
+
+        let json = creds.to_cache_json().unwrap();
+        let provider = GcpProvider::new(identity(None));
+        let restored = provider.credentials_from_cache_json(&json).unwrap();
+        assert_eq!(restored.env_vars(&binding), vars);
+    }
+}
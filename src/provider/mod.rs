@@ -5,19 +5,23 @@
 //! how to list targets, how to mint and render credentials, how to drop privileges —
 //! lives behind [`CloudProvider`] so the orchestration in `lib.rs` stays generic.
 //!
-//! AWS is implemented in [`aws`]. A GCP implementation (service-account impersonation
-//! with Credential Access Boundaries for `--readonly`) can be added as a sibling module
-//! without touching the generic layer.
+//! AWS is implemented in [`aws`]; GCP (service-account impersonation with Credential
+//! Access Boundaries for `--readonly`) in [`gcp`].
 
 pub mod aws;
+pub mod gcp;
 
 use std::any::Any;
+use std::borrow::Cow;
+use std::time::Duration;
 
 use crate::config::{ProviderKind, SsoIdentity};
+use crate::credentials_cache::{self, CachedCredentialsStatus};
 use crate::error::{Error, Result};
-use crate::model::RoleChoice;
+use crate::model::{RoleChoice, RoleListing};
+use crate::renew;
 
-pub use aws::cli::PostLoginActions;
+pub use aws::login::PostLoginActions;
 
 /// The privilege level to mint credentials at.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -58,10 +62,61 @@ pub enum ActiveMarker {
     ActiveStale,
 }
 
+/// The marker for a choice the shell has active, judged by its cached credentials. They are
+/// looked up under the region `_ROLEMAN_TARGET` records when it names this target; otherwise
+/// under both the configured default and the region the shell exports in `region_var`.
+pub(crate) fn active_marker(
+    namespace: &str,
+    identity: &SsoIdentity,
+    choice: &RoleChoice,
+    scope: AccessScope,
+    region_var: &str,
+    min_lifetime: Duration,
+) -> ActiveMarker {
+    let target = std::env::var(renew::TARGET_VAR)
+        .ok()
+        .and_then(|value| renew::RenewTarget::parse(&value).ok())
+        .filter(|target| {
+            target.identity == identity.name
+                && target.account_id == choice.account_id
+                && target.role_name == choice.role_name
+                && target.scope == scope
+        });
+    let regions = match target {
+        Some(target) => vec![target.region],
+        None => vec![
+            identity
+                .region_for(&choice.account_id, &choice.role_name)
+                .map(str::to_string),
+            std::env::var(region_var)
+                .ok()
+                .filter(|region| !region.is_empty()),
+        ],
+    };
+    for region in regions {
+        match credentials_cache::cached_credentials_status(
+            namespace,
+            &choice.account_id,
+            &identity.credentials_cache_role(&choice.account_id, &choice.role_name),
+            scope,
+            region.as_deref(),
+            min_lifetime,
+        ) {
+            Ok(CachedCredentialsStatus::Valid) => return ActiveMarker::ActiveValid,
+            Ok(_) => {}
+            Err(err) => tracing::debug!(error = %err, "failed to check cached credentials"),
+        }
+    }
+    ActiveMarker::ActiveStale
+}
+
 /// The provider-side profile binding for a selected target (AWS: a `~/.aws/config` profile).
 pub struct ProfileBinding {
     pub profile_name: String,
     pub config_file: Option<String>,
+    /// The user's own config file to remember while `config_file` replaces it; an empty
+    /// value forgets it.
+    pub saved_config_file: Option<String>,
 }
 
 /// Opaque, provider-owned session/token state threaded back into later calls.
@@ -93,14 +148,28 @@ pub trait ProviderCredentials: Send + Sync {
     fn expiration_ms(&self) -> u64;
     /// Serialize for the on-disk credentials cache.
     fn to_cache_json(&self) -> Result<String>;
-}
-
-/// Render environment variables as shell `export NAME=value` lines.
-pub fn export_lines(vars: &[EnvVar]) -> String {
-    vars.iter()
-        .map(|var| format!("export {}={}", var.name, var.value))
-        .collect::<Vec<_>>()
-        .join("\n")
+    /// Render as the JSON document an external-process credential hook prints to stdout
+    /// (AWS: the `credential_process` Version 1 format).
+    fn credential_process_json(&self) -> Result<String> {
+        Err(Error::Config(
+            "this provider does not support credential_process".to_string(),
+        ))
+    }
+    /// Write into a roleman-managed `profile_name` section of the provider's shared credentials
+    /// file (AWS: `~/.aws/credentials`), for tools that only read static keys from there.
+    fn write_shared_credentials(&self, profile_name: &str) -> Result<()> {
+        let _ = profile_name;
+        Err(Error::Config(
+            "this provider does not support a shared credentials file".to_string(),
+        ))
+    }
+    /// Render as the JSON document a container-credentials endpoint serves
+    /// (AWS: the ECS `AWS_CONTAINER_CREDENTIALS_FULL_URI` format).
+    fn container_credentials_json(&self) -> Result<String> {
+        Err(Error::Config(
+            "this provider does not support serving credentials".to_string(),
+        ))
+    }
 }
 
 #[async_trait::async_trait]
@@ -112,12 +181,18 @@ pub trait CloudProvider: Send + Sync {
         post_login: PostLoginActions,
     ) -> Result<Box<dyn ProviderSession>>;
 
-    /// List selectable account/role targets for this session.
-    async fn list_choices(&self, session: &dyn ProviderSession) -> Result<Vec<RoleChoice>>;
+    /// Load a still-valid session without signing in, or `None` when the user has to log in
+    /// again. Used where no interactive flow is allowed (the shell hook's renewal).
+    async fn resume_session(&self) -> Result<Option<Box<dyn ProviderSession>>>;
+
+    /// List selectable account/role targets for this session. Accounts whose roles fail to
+    /// list are reported in [`RoleListing::failures`] rather than failing the whole listing.
+    async fn list_choices(&self, session: &dyn ProviderSession) -> Result<RoleListing>;
 
     /// Mint credentials for a selected target at the requested scope.
     ///
-    /// `may_create` authorizes the provider to create cloud resources it needs (e.g. an AWS
+    /// `region` overrides the region the credentials target (AWS: the exported
+    /// `AWS_REGION`); `None` uses the provider default. `may_create` authorizes the provider to create cloud resources it needs (e.g. an AWS
     /// read-only role for [`AccessScope::ReadOnly`]). When `false` and a resource is missing,
     /// the provider returns [`Error::NeedsResourceCreation`] instead of creating it, so the
     /// caller can obtain consent and retry.
@@ -126,6 +201,7 @@ pub trait CloudProvider: Send + Sync {
         session: &dyn ProviderSession,
         choice: &RoleChoice,
         scope: AccessScope,
+        region: Option<&str>,
         may_create: bool,
     ) -> Result<Box<dyn ProviderCredentials>>;
 
@@ -138,17 +214,54 @@ pub trait CloudProvider: Send + Sync {
         session: &dyn ProviderSession,
         choice: &RoleChoice,
         scope: AccessScope,
+        region: Option<&str>,
         omit_role_name: bool,
     ) -> Result<ProfileBinding>;
 
+    /// Name of the profile [`CloudProvider::ensure_profile`] binds `choice` to.
+    fn profile_name(&self, choice: &RoleChoice, scope: AccessScope, omit_role_name: bool)
+    -> String;
+
+    /// Profiles roleman generated for this identity (AWS: roleman-managed `[profile ...]`
+    /// sections), for `roleman profiles`.
+    fn generated_profiles(&self) -> Result<Vec<String>> {
+        Err(Error::Config(
+            "this provider does not write profile config".to_string(),
+        ))
+    }
+
+    /// Remove generated profiles by name, skipping any roleman didn't generate.
+    fn remove_generated_profiles(&self, _names: &[String]) -> Result<()> {
+        Err(Error::Config(
+            "this provider does not write profile config".to_string(),
+        ))
+    }
+
     /// Build the web/console URL for the Open action.
     fn console_url(&self, choice: &RoleChoice) -> String;
 
     /// Provider-agnostic cache namespace for this identity (identity name + provider).
     fn cache_namespace(&self) -> String;
 
-    /// Resolve the selector active-marker for each choice, aligned to the input slice.
-    fn active_markers(&self, choices: &[RoleChoice], scope: AccessScope) -> Vec<ActiveMarker>;
+    /// Role name a choice's credentials are cached under within [`Self::cache_namespace`].
+    fn cache_role_name<'a>(&self, choice: &'a RoleChoice) -> Cow<'a, str> {
+        Cow::Borrowed(&choice.role_name)
+    }
+
+    /// Resolve the selector active-marker for each choice, aligned to the input slice. Cached
+    /// credentials that don't outlast `min_lifetime` count as stale.
+    fn active_markers(
+        &self,
+        choices: &[RoleChoice],
+        scope: AccessScope,
+        min_lifetime: Duration,
+    ) -> Vec<ActiveMarker>;
+
+    /// When the cached sign-in expires (unix-ms), for status displays. `None` when nothing is
+    /// cached or the provider's sign-in doesn't expire (GCP refresh tokens).
+    fn session_expiration_ms(&self) -> Option<u64> {
+        None
+    }
 
     /// Whether this provider can enforce the requested scope.
     fn supports_scope(&self, _scope: AccessScope) -> bool {
@@ -228,13 +341,20 @@ pub struct AccountCleanup {
     pub error: Option<String>,
 }
 
+/// Credential variables any provider's `set` exports, for `unset` and for clearing what
+/// another provider left behind.
+pub fn managed_env_vars() -> impl Iterator<Item = &'static str> {
+    aws::CREDENTIAL_ENV_VARS
+        .iter()
+        .chain(gcp::CREDENTIAL_ENV_VARS)
+        .copied()
+}
+
 /// Construct the provider for an identity based on its configured [`ProviderKind`].
 pub fn for_identity(identity: &SsoIdentity) -> Result<Box<dyn CloudProvider>> {
     match identity.provider {
         ProviderKind::Aws => Ok(Box::new(aws::AwsProvider::new(identity.clone()))),
-        ProviderKind::Gcp => Err(Error::Config(
-            "GCP provider is not implemented yet".to_string(),
-        )),
+        ProviderKind::Gcp => Ok(Box::new(gcp::GcpProvider::new(identity.clone()))),
     }
 }
 
@@ -249,6 +369,16 @@ mod tests {
             sso_region: "us-east-1".into(),
             provider,
             readonly_policy: None,
+            credential_process: false,
+            shared_credentials_file: false,
+            isolated_aws_config: false,
+            config_file: None,
+            shortcuts: Vec::new(),
+            chained_roles: Vec::new(),
+            min_credential_lifetime_seconds: None,
+            roles_cache_ttl_seconds: None,
+            list_concurrency: None,
+            max_requests_per_second: None,
             accounts: Vec::new(),
             ignore_roles: Vec::new(),
         }
@@ -260,8 +390,85 @@ mod tests {
     }
 
     #[test]
-    fn for_identity_rejects_unimplemented_gcp() {
-        assert!(for_identity(&identity(ProviderKind::Gcp)).is_err());
+    fn for_identity_dispatches_gcp() {
+        let provider = for_identity(&identity(ProviderKind::Gcp)).unwrap();
+        assert_eq!(provider.cache_namespace(), "gcp:work");
+    }
+
+    #[test]
+    fn active_marker_finds_credentials_minted_for_another_region() {
+        let _lock = crate::test_support::lock_env();
+        let temp = tempfile::TempDir::new().unwrap();
+        let saved = ["XDG_CACHE_HOME", "AWS_REGION", renew::TARGET_VAR]
+            .map(|name| (name, std::env::var(name).ok()));
+        unsafe {
+            std::env::set_var("XDG_CACHE_HOME", temp.path());
+            std::env::remove_var("AWS_REGION");
+            std::env::remove_var(renew::TARGET_VAR);
+        }
+        let identity = identity(ProviderKind::Aws);
+        let choice = RoleChoice {
+            account_id: "1111".into(),
+            account_name: "Production".into(),
+            role_name: "Admin".into(),
+        };
+        let expiration_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64
+            + 3_600_000;
+        credentials_cache::CredentialsCache::open(
+            crate::config::CredentialCacheBackend::File,
+            "aws:work".into(),
+        )
+        .unwrap()
+        .save(
+            "1111",
+            "Admin",
+            AccessScope::Full,
+            Some("eu-west-1"),
+            expiration_ms,
+            "{}",
+        )
+        .unwrap();
+        let marker = || {
+            active_marker(
+                "aws:work",
+                &identity,
+                &choice,
+                AccessScope::Full,
+                "AWS_REGION",
+                crate::config::DEFAULT_MIN_CREDENTIAL_LIFETIME,
+            )
+        };
+
+        // `set --region eu-west-1` without a configured region: only the export tells.
+        assert_eq!(marker(), ActiveMarker::ActiveStale);
+        unsafe { std::env::set_var("AWS_REGION", "eu-west-1") };
+        assert_eq!(marker(), ActiveMarker::ActiveValid);
+
+        // The hook's target wins over whatever region the shell exports.
+        unsafe { std::env::set_var("AWS_REGION", "us-east-1") };
+        let target = renew::RenewTarget {
+            identity: "work".into(),
+            account_id: "1111".into(),
+            account_name: "Production".into(),
+            role_name: "Admin".into(),
+            scope: AccessScope::Full,
+            region: Some("eu-west-1".into()),
+            omit_role_name: false,
+        };
+        unsafe { std::env::set_var(renew::TARGET_VAR, target.encode()) };
+        assert_eq!(marker(), ActiveMarker::ActiveValid);
+
+        unsafe {
+            for (name, value) in saved {
+                match value {
+                    Some(value) => std::env::set_var(name, value),
+                    None => std::env::remove_var(name),
+                }
+            }
+        }
     }
 
     // A minimal non-AWS provider proving the generic layer is provider-agnostic:
@@ -301,18 +508,25 @@ mod tests {
         ) -> Result<Box<dyn ProviderSession>> {
             Ok(Box::new(FakeSession))
         }
-        async fn list_choices(&self, _session: &dyn ProviderSession) -> Result<Vec<RoleChoice>> {
-            Ok(vec![RoleChoice {
-                account_id: "1".into(),
-                account_name: "Acme".into(),
-                role_name: "Admin".into(),
-            }])
+        async fn resume_session(&self) -> Result<Option<Box<dyn ProviderSession>>> {
+            Ok(Some(Box::new(FakeSession)))
+        }
+        async fn list_choices(&self, _session: &dyn ProviderSession) -> Result<RoleListing> {
+            Ok(RoleListing {
+                choices: vec![RoleChoice {
+                    account_id: "1".into(),
+                    account_name: "Acme".into(),
+                    role_name: "Admin".into(),
+                }],
+                failures: Vec::new(),
+            })
         }
         async fn fetch_credentials(
             &self,
             _session: &dyn ProviderSession,
             _choice: &RoleChoice,
             scope: AccessScope,
+            _region: Option<&str>,
             may_create: bool,
         ) -> Result<Box<dyn ProviderCredentials>> {
             // Mirror the AWS provider: ReadOnly needs a resource that requires consent.
@@ -331,20 +545,35 @@ mod tests {
             _session: &dyn ProviderSession,
             _choice: &RoleChoice,
             scope: AccessScope,
+            _region: Option<&str>,
             _omit_role_name: bool,
         ) -> Result<ProfileBinding> {
             Ok(ProfileBinding {
                 profile_name: format!("acme{}", scope.profile_suffix()),
                 config_file: None,
+                saved_config_file: None,
             })
         }
+        fn profile_name(
+            &self,
+            _choice: &RoleChoice,
+            scope: AccessScope,
+            _omit_role_name: bool,
+        ) -> String {
+            format!("acme{}", scope.profile_suffix())
+        }
         fn console_url(&self, _choice: &RoleChoice) -> String {
             "https://example.test".into()
         }
         fn cache_namespace(&self) -> String {
             "fake".into()
         }
-        fn active_markers(&self, choices: &[RoleChoice], _scope: AccessScope) -> Vec<ActiveMarker> {
+        fn active_markers(
+            &self,
+            choices: &[RoleChoice],
+            _scope: AccessScope,
+            _min_lifetime: Duration,
+        ) -> Vec<ActiveMarker> {
             vec![ActiveMarker::Inactive; choices.len()]
         }
     }
@@ -356,29 +585,48 @@ mod tests {
             .ensure_session(false, PostLoginActions::default())
             .await
             .unwrap();
-        let choices = provider.list_choices(session.as_ref()).await.unwrap();
-        assert_eq!(choices.len(), 1);
+        let listing = provider.list_choices(session.as_ref()).await.unwrap();
+        assert_eq!(listing.choices.len(), 1);
 
         // Without consent, ReadOnly asks the caller to authorize resource creation.
         let needs = provider
-            .fetch_credentials(session.as_ref(), &choices[0], AccessScope::ReadOnly, false)
+            .fetch_credentials(
+                session.as_ref(),
+                &listing.choices[0],
+                AccessScope::ReadOnly,
+                None,
+                false,
+            )
             .await;
         assert!(matches!(needs, Err(Error::NeedsResourceCreation(_))));
 
         // With consent (may_create=true), it succeeds.
         let creds = provider
-            .fetch_credentials(session.as_ref(), &choices[0], AccessScope::ReadOnly, true)
+            .fetch_credentials(
+                session.as_ref(),
+                &listing.choices[0],
+                AccessScope::ReadOnly,
+                None,
+                true,
+            )
             .await
             .unwrap();
         let binding = provider
-            .ensure_profile(session.as_ref(), &choices[0], AccessScope::ReadOnly, true)
+            .ensure_profile(
+                session.as_ref(),
+                &listing.choices[0],
+                AccessScope::ReadOnly,
+                None,
+                true,
+            )
             .unwrap();
         let vars = creds.env_vars(&binding);
         // The ReadOnly scope flows end-to-end without the generic layer knowing the provider.
         assert!(vars.contains(&EnvVar::new("FAKE_TOKEN", "readonly")));
         assert!(vars.contains(&EnvVar::new("FAKE_PROFILE", "acme@readonly")));
         // The generic formatter turns pairs into shell exports.
-        let lines = export_lines(&vars);
+        let bash = crate::shell::shell_for_name("bash").unwrap();
+        let lines = bash.render_exports(&vars).unwrap();
         assert!(lines.contains("export FAKE_TOKEN=readonly"));
     }
 }
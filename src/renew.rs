@@ -0,0 +1,214 @@
+//! Background credential renewal driven by the shell hook.
+//!
+//! `set` exports two extra variables alongside the credentials: `_ROLEMAN_TARGET`, which names
+//! the resolved target, and `_ROLEMAN_RENEW_AT`, the unix time after which the hook should call
+//! `roleman renew`. The hook only compares two integers per prompt; everything else happens
+//! here, without a selector or sign-in flow.
+//!
+//! The same target encoding lets `set` hand a cached credential that is well into its lifetime
+//! to a detached `roleman refresh-credentials`, which re-mints it for the next `set` or renewal.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::model::RoleChoice;
+use crate::provider::{AccessScope, EnvVar};
+
+/// Unix time (seconds) after which the hook runs `roleman renew`; empty disables renewal.
+pub(crate) const RENEW_AT_VAR: &str = "_ROLEMAN_RENEW_AT";
+/// The target `roleman renew` re-mints credentials for.
+pub(crate) const TARGET_VAR: &str = "_ROLEMAN_TARGET";
+/// Renew this long before the exported credentials expire.
+pub(crate) const RENEW_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// Wait this long before retrying a renewal that failed for a transient reason.
+pub(crate) const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Everything needed to re-mint the exported credentials without the selector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RenewTarget {
+    pub identity: String,
+    pub account_id: String,
+    pub account_name: String,
+    pub role_name: String,
+    pub scope: AccessScope,
+    pub region: Option<String>,
+    pub omit_role_name: bool,
+}
+
+impl RenewTarget {
+    /// Encode as a single shell-safe word: percent-encoded fields joined by `:`.
+    pub fn encode(&self) -> String {
+        [
+            self.identity.as_str(),
+            self.account_id.as_str(),
+            self.account_name.as_str(),
+            self.role_name.as_str(),
+            self.scope.cache_tag(),
+            self.region.as_deref().unwrap_or_default(),
+            if self.omit_role_name { "1" } else { "0" },
+        ]
+        .iter()
+        .map(|field| urlencoding::encode(field).into_owned())
+        .collect::<Vec<_>>()
+        .join(":")
+    }
+
+    pub fn choice(&self) -> RoleChoice {
+        RoleChoice {
+            account_id: self.account_id.clone(),
+            account_name: self.account_name.clone(),
+            role_name: self.role_name.clone(),
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || Error::Config(format!("invalid {TARGET_VAR} value: {value}"));
+        let fields = value
+            .split(':')
+            .map(|field| urlencoding::decode(field).map(|field| field.into_owned()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let [
+            identity,
+            account_id,
+            account_name,
+            role_name,
+            scope,
+            region,
+            omit,
+        ] = <[String; 7]>::try_from(fields).map_err(|_| invalid())?;
+        let scope = match scope.as_str() {
+            "full" => AccessScope::Full,
+            "readonly" => AccessScope::ReadOnly,
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            identity,
+            account_id,
+            account_name,
+            role_name,
+            scope,
+            region: (!region.is_empty()).then_some(region),
+            omit_role_name: omit == "1",
+        })
+    }
+}
+
+/// Variables that arm renewal for credentials expiring at `expiration_ms`.
+pub(crate) fn renew_vars(target: &RenewTarget, expiration_ms: u64, now_ms: u64) -> Vec<EnvVar> {
+    vec![
+        EnvVar::new(TARGET_VAR, target.encode()),
+        renew_at_var(expiration_ms, now_ms),
+    ]
+}
+
+/// When to renew credentials expiring at `expiration_ms`: [`RENEW_MARGIN`] ahead of expiry,
+/// but never sooner than [`RETRY_DELAY`] from now, so short-lived credentials can't make
+/// every prompt spawn roleman.
+pub(crate) fn renew_at_var(expiration_ms: u64, now_ms: u64) -> EnvVar {
+    let due_ms = expiration_ms
+        .saturating_sub(RENEW_MARGIN.as_millis() as u64)
+        .max(now_ms + RETRY_DELAY.as_millis() as u64);
+    EnvVar::new(RENEW_AT_VAR, (due_ms / 1000).to_string())
+}
+
+/// Try again after [`RETRY_DELAY`], after a renewal failed for a transient reason.
+pub(crate) fn retry_var(now_ms: u64) -> EnvVar {
+    renew_at_var(0, now_ms)
+}
+
+/// Stop renewing until the next `set` (the target is gone or needs an interactive sign-in).
+pub(crate) fn disable_var() -> EnvVar {
+    EnvVar::new(RENEW_AT_VAR, "")
+}
+
+/// Start a detached `roleman refresh-credentials` for `target` and return without waiting.
+pub(crate) fn spawn_background_refresh(
+    target: &RenewTarget,
+    config_path: Option<&Path>,
+) -> Result<()> {
+    let exe = std::env::current_exe().map_err(|err| Error::Command(err.to_string()))?;
+    let mut command = Command::new(&exe);
+    command.arg("refresh-credentials");
+    if let Some(path) = config_path {
+        command.arg("--config").arg(path);
+    }
+    command
+        .arg(target.encode())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Its own process group, so Ctrl-C at the prompt doesn't interrupt the refresh.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    command
+        .spawn()
+        .map_err(|err| Error::Command(format!("{}: {err}", exe.display())))?;
+    Ok(())
+}
+
+/// Whether credentials expiring at `expiration_ms` are fresh enough to hand out on renewal.
+pub(crate) fn outlasts_margin(expiration_ms: u64, now_ms: u64) -> bool {
+    expiration_ms > now_ms + RENEW_MARGIN.as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_roundtrips_through_encoding() {
+        let target = RenewTarget {
+            identity: "work: main".to_string(),
+            account_id: "123456789012".to_string(),
+            account_name: "Platform Prod".to_string(),
+            role_name: "Admin".to_string(),
+            scope: AccessScope::ReadOnly,
+            region: Some("eu-west-1".to_string()),
+            omit_role_name: true,
+        };
+        let encoded = target.encode();
+        assert!(!encoded.contains(' '));
+        assert_eq!(RenewTarget::parse(&encoded).unwrap(), target);
+
+        let default_region = RenewTarget {
+            region: None,
+            scope: AccessScope::Full,
+            ..target
+        };
+        assert_eq!(
+            RenewTarget::parse(&default_region.encode()).unwrap(),
+            default_region
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_targets() {
+        assert!(RenewTarget::parse("").is_err());
+        assert!(RenewTarget::parse("work:123:Acme:Admin:full:").is_err());
+        assert!(RenewTarget::parse("work:123:Acme:Admin:admin::0").is_err());
+    }
+
+    #[test]
+    fn renews_ahead_of_expiry_but_not_in_a_hot_loop() {
+        let now = 1_000_000_000;
+        let hour = 60 * 60 * 1000;
+        assert_eq!(
+            renew_at_var(now + hour, now).value,
+            ((now + hour - 5 * 60 * 1000) / 1000).to_string()
+        );
+        assert_eq!(
+            renew_at_var(now + 1000, now).value,
+            ((now + 60_000) / 1000).to_string()
+        );
+    }
+
+    #[test]
+    fn credentials_inside_the_margin_are_not_reused() {
+        let now = 1_000_000_000;
+        assert!(outlasts_margin(now + 10 * 60 * 1000, now));
+        assert!(!outlasts_margin(now + 2 * 60 * 1000, now));
+    }
+}
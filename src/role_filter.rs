@@ -0,0 +1,216 @@
+//! Non-interactive role selection from `--account-id`, `--account-name` and `--role`.
+//!
+//! Scripts and CI resolve a role deterministically against the visible choices instead of
+//! going through the selector; anything other than exactly one match is an error.
+
+use crate::error::{Error, Result};
+use crate::model::RoleChoice;
+
+/// How many near misses to list when nothing matches.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Criteria a choice must satisfy; unset fields match anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoleFilter {
+    pub account_id: Option<String>,
+    /// Account name (or configured alias), compared case-insensitively.
+    pub account_name: Option<String>,
+    /// Role name, compared case-insensitively.
+    pub role_name: Option<String>,
+}
+
+impl RoleFilter {
+    /// Whether no criteria are set (the selector should be used instead).
+    pub fn is_empty(&self) -> bool {
+        self.account_id.is_none() && self.account_name.is_none() && self.role_name.is_none()
+    }
+
+    pub fn matches(&self, choice: &RoleChoice) -> bool {
+        self.account_id
+            .as_deref()
+            .is_none_or(|id| id.trim() == choice.account_id)
+            && self
+                .account_name
+                .as_deref()
+                .is_none_or(|name| name.trim().eq_ignore_ascii_case(&choice.account_name))
+            && self
+                .role_name
+                .as_deref()
+                .is_none_or(|role| role.trim().eq_ignore_ascii_case(&choice.role_name))
+    }
+
+    /// Resolve the single choice matching every criterion.
+    pub fn resolve(&self, choices: &[RoleChoice]) -> Result<RoleChoice> {
+        let matched: Vec<&RoleChoice> = choices.iter().filter(|c| self.matches(c)).collect();
+        match matched.as_slice() {
+            [choice] => Ok((*choice).clone()),
+            [] => {
+                let mut message = format!("no role matches {}", self.describe());
+                let suggestions = self.closest(choices);
+                if !suggestions.is_empty() {
+                    message.push_str("; close matches:");
+                    for choice in suggestions {
+                        message.push_str("\n  ");
+                        message.push_str(&choice.label());
+                    }
+                }
+                Err(Error::RoleSelection(message))
+            }
+            several => {
+                let mut message = format!(
+                    "{} roles match {}; narrow it down with --account-id/--role:",
+                    several.len(),
+                    self.describe()
+                );
+                for choice in several {
+                    message.push_str("\n  ");
+                    message.push_str(&choice.label());
+                }
+                Err(Error::RoleSelection(message))
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(id) = &self.account_id {
+            parts.push(format!("account ID {id}"));
+        }
+        if let Some(name) = &self.account_name {
+            parts.push(format!("account name {name}"));
+        }
+        if let Some(role) = &self.role_name {
+            parts.push(format!("role {role}"));
+        }
+        parts.join(", ")
+    }
+
+    /// Choices ranked by total edit distance over the criteria that were given.
+    fn closest<'a>(&self, choices: &'a [RoleChoice]) -> Vec<&'a RoleChoice> {
+        let mut scored: Vec<(usize, &RoleChoice)> = choices
+            .iter()
+            .map(|choice| {
+                let mut score = 0;
+                if let Some(id) = &self.account_id {
+                    score += edit_distance(id.trim(), &choice.account_id);
+                }
+                if let Some(name) = &self.account_name {
+                    score += edit_distance(
+                        &name.trim().to_lowercase(),
+                        &choice.account_name.to_lowercase(),
+                    );
+                }
+                if let Some(role) = &self.role_name {
+                    score += edit_distance(
+                        &role.trim().to_lowercase(),
+                        &choice.role_name.to_lowercase(),
+                    );
+                }
+                (score, choice)
+            })
+            .collect();
+        scored.sort_by_key(|(score, choice)| (*score, choice.label()));
+        scored
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, choice)| choice)
+            .collect()
+    }
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choices() -> Vec<RoleChoice> {
+        vec![
+            RoleChoice {
+                account_id: "111111111111".into(),
+                account_name: "Platform".into(),
+                role_name: "Admin".into(),
+            },
+            RoleChoice {
+                account_id: "111111111111".into(),
+                account_name: "Platform".into(),
+                role_name: "ReadOnly".into(),
+            },
+            RoleChoice {
+                account_id: "222222222222".into(),
+                account_name: "Sandbox".into(),
+                role_name: "Admin".into(),
+            },
+        ]
+    }
+
+    #[test]
+    fn resolves_by_account_id_and_role() {
+        let filter = RoleFilter {
+            account_id: Some("111111111111".into()),
+            role_name: Some("admin".into()),
+            ..RoleFilter::default()
+        };
+        let choice = filter.resolve(&choices()).unwrap();
+        assert_eq!(choice.account_name, "Platform");
+        assert_eq!(choice.role_name, "Admin");
+    }
+
+    #[test]
+    fn resolves_by_account_name_alone_when_unique() {
+        let filter = RoleFilter {
+            account_name: Some("sandbox".into()),
+            ..RoleFilter::default()
+        };
+        assert_eq!(
+            filter.resolve(&choices()).unwrap().account_id,
+            "222222222222"
+        );
+    }
+
+    #[test]
+    fn ambiguous_filter_lists_candidates() {
+        let filter = RoleFilter {
+            role_name: Some("Admin".into()),
+            ..RoleFilter::default()
+        };
+        let err = filter.resolve(&choices()).unwrap_err().to_string();
+        assert!(err.starts_with("2 roles match role Admin"));
+        assert!(err.contains("Platform (111111111111) — Admin"));
+        assert!(err.contains("Sandbox (222222222222) — Admin"));
+    }
+
+    #[test]
+    fn missing_role_suggests_close_matches() {
+        let filter = RoleFilter {
+            account_id: Some("222222222222".into()),
+            role_name: Some("Admn".into()),
+            ..RoleFilter::default()
+        };
+        let err = filter.resolve(&choices()).unwrap_err().to_string();
+        assert!(err.starts_with("no role matches account ID 222222222222, role Admn"));
+        let first_suggestion = err.lines().nth(1).unwrap();
+        assert_eq!(first_suggestion.trim(), "Sandbox (222222222222) — Admin");
+    }
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("admin", "admin"), 0);
+        assert_eq!(edit_distance("admn", "admin"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
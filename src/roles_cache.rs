@@ -1,4 +1,5 @@
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -7,15 +8,40 @@ use sha1::{Digest, Sha1};
 
 use crate::error::{Error, Result};
 use crate::model::RoleChoice;
+use crate::util::write_atomic;
 
-const ROLES_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// File names of role lists start with this; the rest is a hash of the namespace.
+pub(crate) const FILE_PREFIX: &str = "roles-";
 
 #[derive(Debug, Serialize, Deserialize)]
-struct CachedRoles {
-    fetched_at: u64,
+pub(crate) struct CachedRoles {
+    /// The provider namespace, so `roleman cache` can name the identity; absent in lists
+    /// written by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub fetched_at: u64,
     roles: Vec<CachedRole>,
 }
 
+impl CachedRoles {
+    /// Read a role list file, or `None` if it isn't one.
+    pub(crate) fn read(path: &Path) -> Option<Self> {
+        let data = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    pub(crate) fn choices(&self) -> Vec<RoleChoice> {
+        self.roles
+            .iter()
+            .map(|entry| RoleChoice {
+                account_id: entry.account_id.clone(),
+                account_name: entry.account_name.clone(),
+                role_name: entry.role_name.clone(),
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct CachedRole {
     account_id: String,
@@ -23,16 +49,6 @@ struct CachedRole {
     role_name: String,
 }
 
-pub fn load_cached_roles(namespace: &str) -> Result<Option<(Vec<RoleChoice>, Duration)>> {
-    let cached = load_cached_roles_with_age(namespace)?;
-    if let Some((choices, age)) = cached
-        && age <= ROLES_CACHE_TTL
-    {
-        return Ok(Some((choices, age)));
-    }
-    Ok(None)
-}
-
 pub fn load_cached_roles_with_age(namespace: &str) -> Result<Option<(Vec<RoleChoice>, Duration)>> {
     let cache_dir = roleman_cache_dir()?;
     let path = cache_dir.join(cache_filename(namespace));
@@ -40,13 +56,8 @@ pub fn load_cached_roles_with_age(namespace: &str) -> Result<Option<(Vec<RoleCho
         return Ok(None);
     }
 
-    let data = match fs::read_to_string(&path) {
-        Ok(data) => data,
-        Err(_) => return Ok(None),
-    };
-    let cached: CachedRoles = match serde_json::from_str(&data) {
-        Ok(cached) => cached,
-        Err(_) => return Ok(None),
+    let Some(cached) = CachedRoles::read(&path) else {
+        return Ok(None);
     };
 
     let now = SystemTime::now()
@@ -54,23 +65,16 @@ pub fn load_cached_roles_with_age(namespace: &str) -> Result<Option<(Vec<RoleCho
         .unwrap_or_default()
         .as_secs();
     let age = Duration::from_secs(now.saturating_sub(cached.fetched_at));
-    let choices = cached
-        .roles
-        .into_iter()
-        .map(|entry| RoleChoice {
-            account_id: entry.account_id,
-            account_name: entry.account_name,
-            role_name: entry.role_name,
-        })
-        .collect();
+    let choices = cached.choices();
     Ok(Some((choices, age)))
 }
 
 pub fn save_cached_roles(namespace: &str, choices: &[RoleChoice]) -> Result<()> {
     let cache_dir = roleman_cache_dir()?;
-    fs::create_dir_all(&cache_dir).map_err(|_| Error::MissingCache)?;
+    create_private_dir(&cache_dir).map_err(|_| Error::MissingCache)?;
     let path = cache_dir.join(cache_filename(namespace));
     let cached = CachedRoles {
+        namespace: Some(namespace.to_string()),
         fetched_at: SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
@@ -86,24 +90,10 @@ pub fn save_cached_roles(namespace: &str, choices: &[RoleChoice]) -> Result<()>
     };
     let data =
         serde_json::to_string(&cached).map_err(|_| Error::CacheParse { path: path.clone() })?;
-    fs::write(&path, data).map_err(|_| Error::CacheParse { path })?;
+    write_private(&path, data.as_bytes()).map_err(|_| Error::CacheParse { path })?;
     Ok(())
 }
 
-pub fn format_age(age: Duration) -> String {
-    let total = age.as_secs();
-    let hours = total / 3600;
-    let minutes = (total % 3600) / 60;
-    let seconds = total % 60;
-    if hours > 0 {
-        format!("{hours}h {minutes}m")
-    } else if minutes > 0 {
-        format!("{minutes}m {seconds}s")
-    } else {
-        format!("{seconds}s")
-    }
-}
-
 pub fn roleman_cache_dir() -> Result<PathBuf> {
     if let Ok(dir) = std::env::var("XDG_CACHE_HOME") {
         Ok(PathBuf::from(dir).join("roleman"))
@@ -113,11 +103,29 @@ pub fn roleman_cache_dir() -> Result<PathBuf> {
     }
 }
 
+/// Create a cache directory that only the current user can enter (0700 on Unix), tightening
+/// it if an older version created it with default permissions.
+pub(crate) fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// Write a cache file that only the current user can read (0600 on Unix), replacing it
+/// atomically so concurrent readers never see it half-written.
+pub(crate) fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    write_atomic(path, data, true)
+}
+
 fn cache_filename(namespace: &str) -> String {
     let mut hasher = Sha1::new();
     hasher.update(namespace.as_bytes());
     let digest = hasher.finalize();
-    format!("roles-{:x}.json", digest)
+    format!("{FILE_PREFIX}{digest:x}.json")
 }
 
 #[cfg(test)]
@@ -163,7 +171,7 @@ mod tests {
     }
 
     #[test]
-    fn stale_cache_is_ignored_by_default_loader() {
+    fn stale_cache_reports_its_age() {
         let _lock = crate::test_support::lock_env();
         let temp = TempDir::new().unwrap();
         let previous = std::env::var("XDG_CACHE_HOME").ok();
@@ -175,8 +183,9 @@ mod tests {
         fs::create_dir_all(&cache_dir).unwrap();
         let path = cache_dir.join(cache_filename("https://example.awsapps.com/start"));
         let stale = CachedRoles {
+            namespace: None,
             fetched_at: SystemTime::now()
-                .checked_sub(ROLES_CACHE_TTL + Duration::from_secs(60))
+                .checked_sub(Duration::from_secs(2 * 60 * 60))
                 .unwrap()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
@@ -190,10 +199,11 @@ mod tests {
         let data = serde_json::to_string(&stale).unwrap();
         fs::write(&path, data).unwrap();
 
-        let fresh = load_cached_roles("https://example.awsapps.com/start").unwrap();
-        assert!(fresh.is_none());
-        let with_age = load_cached_roles_with_age("https://example.awsapps.com/start").unwrap();
-        assert!(with_age.is_some());
+        let (roles, age) = load_cached_roles_with_age("https://example.awsapps.com/start")
+            .unwrap()
+            .unwrap();
+        assert_eq!(roles.len(), 1);
+        assert!(age >= Duration::from_secs(2 * 60 * 60));
 
         unsafe {
             if let Some(value) = previous {
@@ -203,11 +213,4 @@ mod tests {
             }
         }
     }
-
-    #[test]
-    fn format_age_outputs_compact_string() {
-        assert_eq!(format_age(Duration::from_secs(5)), "5s");
-        assert_eq!(format_age(Duration::from_secs(70)), "1m 10s");
-        assert_eq!(format_age(Duration::from_secs(3_650)), "1h 0m");
-    }
 }
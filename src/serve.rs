@@ -0,0 +1,256 @@
+//! Loopback credentials endpoint for `roleman serve`.
+//!
+//! Serves the selected role's credentials in the format the AWS SDKs' container credentials
+//! provider reads (`AWS_CONTAINER_CREDENTIALS_FULL_URI` + `AWS_CONTAINER_AUTHORIZATION_TOKEN`),
+//! so SDK clients and containers pick up credentials that roleman keeps refreshed.
+//!
+//! The endpoint only listens on 127.0.0.1: the SDKs refuse plain-HTTP credential URIs on
+//! other hosts, so containers reach it through the host network (`docker run --network host`).
+//! Once the served credentials expire (a refresh kept failing), requests get a 503 instead.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+
+use crate::error::{Error, Result};
+use crate::provider::EnvVar;
+
+/// Path the credentials document is served under.
+const CREDENTIALS_PATH: &str = "/credentials";
+/// Refresh this long before the served credentials expire.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// Never refresh more often than this, even when credentials are already near expiry.
+const MIN_REFRESH_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+struct ServeState {
+    token: Arc<str>,
+    document: Arc<RwLock<ServedDocument>>,
+}
+
+/// The credentials document and when the credentials in it expire.
+struct ServedDocument {
+    json: String,
+    expiration_ms: u64,
+}
+
+/// A bound credentials endpoint; the served document can be swapped while it runs.
+pub(crate) struct CredentialServer {
+    listener: tokio::net::TcpListener,
+    addr: SocketAddr,
+    state: ServeState,
+}
+
+impl CredentialServer {
+    /// Bind to `127.0.0.1:port` (0 picks a free port) with a fresh authorization token.
+    pub(crate) async fn bind(port: u16, document: String, expiration_ms: u64) -> Result<Self> {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(|err| Error::Config(format!("failed to bind 127.0.0.1:{port}: {err}")))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|err| Error::Config(err.to_string()))?;
+        Ok(Self {
+            listener,
+            addr,
+            state: ServeState {
+                token: generate_token()?.into(),
+                document: Arc::new(RwLock::new(ServedDocument {
+                    json: document,
+                    expiration_ms,
+                })),
+            },
+        })
+    }
+
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}{CREDENTIALS_PATH}", self.addr)
+    }
+
+    /// Environment variables that point an AWS SDK at this endpoint.
+    pub(crate) fn env_vars(&self) -> Vec<EnvVar> {
+        vec![
+            EnvVar::new("AWS_CONTAINER_CREDENTIALS_FULL_URI", self.url()),
+            EnvVar::new(
+                "AWS_CONTAINER_AUTHORIZATION_TOKEN",
+                self.state.token.as_ref(),
+            ),
+        ]
+    }
+
+    /// Handle for replacing the served document after a refresh.
+    pub(crate) fn updater(&self) -> DocumentUpdater {
+        DocumentUpdater(self.state.document.clone())
+    }
+
+    /// Serve requests until the future is dropped.
+    pub(crate) async fn run(self) -> Result<()> {
+        axum::serve(self.listener, router(self.state))
+            .await
+            .map_err(|err| Error::Config(format!("credentials server failed: {err}")))
+    }
+}
+
+/// Replaces the credentials document served by a [`CredentialServer`].
+pub(crate) struct DocumentUpdater(Arc<RwLock<ServedDocument>>);
+
+impl DocumentUpdater {
+    pub(crate) fn set(&self, document: String, expiration_ms: u64) {
+        let document = ServedDocument {
+            json: document,
+            expiration_ms,
+        };
+        match self.0.write() {
+            Ok(mut guard) => *guard = document,
+            Err(poisoned) => *poisoned.into_inner() = document,
+        }
+    }
+}
+
+fn router(state: ServeState) -> Router {
+    Router::new()
+        .route(CREDENTIALS_PATH, get(handle_credentials))
+        .with_state(state)
+}
+
+async fn handle_credentials(State(state): State<ServeState>, headers: HeaderMap) -> Response {
+    let authorized = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| tokens_match(value.as_bytes(), state.token.as_bytes()));
+    if !authorized {
+        tracing::debug!("rejected credentials request with missing or wrong token");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let (document, expiration_ms) = match state.document.read() {
+        Ok(guard) => (guard.json.clone(), guard.expiration_ms),
+        Err(poisoned) => {
+            let guard = poisoned.into_inner();
+            (guard.json.clone(), guard.expiration_ms)
+        }
+    };
+    if expiration_ms <= crate::now_ms() {
+        tracing::debug!("refusing to serve expired credentials");
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "credentials expired and roleman could not refresh them",
+        )
+            .into_response();
+    }
+    ([(header::CONTENT_TYPE, "application/json")], document).into_response()
+}
+
+/// Compare tokens in time that depends only on their length, so response times don't reveal
+/// how much of a guess matched.
+fn tokens_match(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len()
+        && std::hint::black_box(
+            given
+                .iter()
+                .zip(expected)
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b)),
+        ) == 0
+}
+
+/// How long to wait before refreshing credentials that expire at `expiration_ms`.
+pub(crate) fn refresh_delay(expiration_ms: u64, now_ms: u64) -> Duration {
+    let remaining = Duration::from_millis(expiration_ms.saturating_sub(now_ms));
+    remaining
+        .saturating_sub(REFRESH_MARGIN)
+        .max(MIN_REFRESH_DELAY)
+}
+
+fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes)
+        .map_err(|err| Error::Config(format!("failed to generate token: {err}")))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn get(addr: SocketAddr, token: Option<&str>) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let auth = token
+            .map(|token| format!("Authorization: {token}\r\n"))
+            .unwrap_or_default();
+        let request = format!(
+            "GET {CREDENTIALS_PATH} HTTP/1.1\r\nHost: {addr}\r\n{auth}Connection: close\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_document_only_with_token() {
+        let valid_until = crate::now_ms() + 60 * 60 * 1000;
+        let server = CredentialServer::bind(0, "{\"AccessKeyId\":\"first\"}".into(), valid_until)
+            .await
+            .unwrap();
+        let addr = server.addr;
+        let token = server.state.token.to_string();
+        let updater = server.updater();
+        let task = tokio::spawn(server.run());
+
+        let denied = get(addr, None).await;
+        assert!(denied.starts_with("HTTP/1.1 401"));
+        let wrong = get(addr, Some("nope")).await;
+        assert!(wrong.starts_with("HTTP/1.1 401"));
+
+        let ok = get(addr, Some(&token)).await;
+        assert!(ok.starts_with("HTTP/1.1 200"));
+        assert!(ok.ends_with("{\"AccessKeyId\":\"first\"}"));
+
+        updater.set("{\"AccessKeyId\":\"second\"}".into(), valid_until);
+        let refreshed = get(addr, Some(&token)).await;
+        assert!(refreshed.ends_with("{\"AccessKeyId\":\"second\"}"));
+
+        // Expired credentials (a refresh that kept failing) are never handed out.
+        updater.set("{\"AccessKeyId\":\"second\"}".into(), crate::now_ms() - 1);
+        let expired = get(addr, Some(&token)).await;
+        assert!(expired.starts_with("HTTP/1.1 503"));
+        assert!(!expired.contains("AccessKeyId"));
+
+        task.abort();
+    }
+
+    #[test]
+    fn refreshes_ahead_of_expiry() {
+        let now = 1_000_000;
+        assert_eq!(
+            refresh_delay(now + 60 * 60 * 1000, now),
+            Duration::from_secs(55 * 60)
+        );
+        // Already inside the margin (or expired): retry soon, but not in a hot loop.
+        assert_eq!(refresh_delay(now + 60_000, now), MIN_REFRESH_DELAY);
+        assert_eq!(refresh_delay(now - 60_000, now), MIN_REFRESH_DELAY);
+    }
+
+    #[test]
+    fn tokens_match_only_exactly() {
+        assert!(tokens_match(b"abc123", b"abc123"));
+        assert!(!tokens_match(b"abc124", b"abc123"));
+        assert!(!tokens_match(b"abc12", b"abc123"));
+        assert!(!tokens_match(b"", b"abc123"));
+    }
+
+    #[test]
+    fn tokens_are_random_hex() {
+        let a = generate_token().unwrap();
+        let b = generate_token().unwrap();
+        assert_eq!(a.len(), 64);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+}
@@ -14,11 +14,16 @@ impl Shell for BashShell {
 
     fn hook_snippet(&self) -> &'static str {
         r##"export _ROLEMAN_HOOK_ENV="${XDG_STATE_HOME:-$HOME/.local/state}/roleman/env-${TTY//\//_}"
-export _ROLEMAN_HOOK_VERSION=1
+export _ROLEMAN_HOOK_VERSION=3
+export _ROLEMAN_HOOK_SHELL=bash
 roleman() {
   command roleman --env-file "$_ROLEMAN_HOOK_ENV" "$@"
 }
 _roleman_prompt_command() {
+  if [[ -n "${_ROLEMAN_RENEW_AT:-}" && ! -f "$_ROLEMAN_HOOK_ENV" ]] \
+    && (( ${EPOCHSECONDS:-$(date +%s)} >= _ROLEMAN_RENEW_AT )); then
+    command roleman --env-file "$_ROLEMAN_HOOK_ENV" renew
+  fi
   if [[ -f "$_ROLEMAN_HOOK_ENV" ]]; then
     source "$_ROLEMAN_HOOK_ENV"
     rm -f "$_ROLEMAN_HOOK_ENV"
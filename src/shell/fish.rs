@@ -1,6 +1,6 @@
 use std::path::{Path, PathBuf};
 
-use super::Shell;
+use super::{Shell, is_plain_word};
 
 #[derive(Clone, Copy, Debug)]
 pub struct FishShell;
@@ -18,11 +18,16 @@ impl Shell for FishShell {
 else
   set -gx _ROLEMAN_HOOK_ENV "$HOME/.local/state/roleman/env-(string replace -a '/' '_' (tty))"
 end
-set -gx _ROLEMAN_HOOK_VERSION 1
+set -gx _ROLEMAN_HOOK_VERSION 3
+set -gx _ROLEMAN_HOOK_SHELL fish
 function roleman
   command roleman --env-file "$_ROLEMAN_HOOK_ENV" $argv
 end
 function __roleman_prompt --on-event fish_prompt
+  if test -n "$_ROLEMAN_RENEW_AT"; and not test -f "$_ROLEMAN_HOOK_ENV"
+    and test (date +%s) -ge "$_ROLEMAN_RENEW_AT"
+    command roleman --env-file "$_ROLEMAN_HOOK_ENV" renew
+  end
   if test -f "$_ROLEMAN_HOOK_ENV"
     source "$_ROLEMAN_HOOK_ENV"
     rm -f "$_ROLEMAN_HOOK_ENV"
@@ -50,4 +55,24 @@ end"##
     fn alias_line(&self) -> &'static str {
         "alias rl roleman"
     }
+
+    /// fish single quotes treat `\\` and `\'` as escapes, unlike POSIX ones.
+    fn quote(&self, value: &str) -> String {
+        if is_plain_word(value) {
+            return value.to_string();
+        }
+        format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+    }
+
+    fn export_command(&self, name: &str, value: &str) -> String {
+        format!("set -gx {name} {}", self.quote(value))
+    }
+
+    fn unset_command(&self, names: &[&str]) -> String {
+        names
+            .iter()
+            .map(|name| format!("set -e {name}"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
 }
@@ -1,3 +1,5 @@
+//! Shell hook snippets and the per-shell syntax for the exports the hook sources.
+
 use std::path::{Path, PathBuf};
 
 mod bash;
@@ -8,10 +10,23 @@ use bash::BASH_SHELL;
 use fish::FISH_SHELL;
 use zsh::ZSH_SHELL;
 
-pub trait Shell {
+use crate::error::{Error, Result};
+use crate::provider::EnvVar;
+
+/// Version of the hook protocol the snippets implement, exported as `_ROLEMAN_HOOK_VERSION`.
+///
+/// Bump it whenever the snippets change so shells running an older hook are told to reload.
+/// Version 2 renews credentials from the prompt hook shortly before they expire. Version 3
+/// names the shell in `_ROLEMAN_HOOK_SHELL`, so the env file is written in its syntax.
+pub const HOOK_VERSION: &str = "3";
+
+/// Names the shell the hook runs in, so roleman writes the env file in its syntax.
+pub const HOOK_SHELL_VAR: &str = "_ROLEMAN_HOOK_SHELL";
+
+pub trait Shell: Sync {
     fn name(&self) -> &'static str;
     fn hook_snippet(&self) -> &'static str;
-    fn rc_path(&self) -> Result<PathBuf, String>;
+    fn rc_path(&self) -> std::result::Result<PathBuf, String>;
 
     fn install_line(&self) -> String {
         format!("eval \"$(roleman hook {})\"", self.name())
@@ -24,6 +39,34 @@ pub trait Shell {
     fn reload_command(&self, rc_path: &Path) -> String {
         format!("source {}", rc_path.display())
     }
+
+    /// Quote `value` as one word this shell reads back verbatim, expanding nothing.
+    fn quote(&self, value: &str) -> String {
+        posix_quote(value)
+    }
+
+    /// A command exporting `name`, which callers have already validated.
+    fn export_command(&self, name: &str, value: &str) -> String {
+        format!("export {name}={}", self.quote(value))
+    }
+
+    /// A command removing `names` from the environment.
+    fn unset_command(&self, names: &[&str]) -> String {
+        format!("unset {}", names.join(" "))
+    }
+
+    /// Render `vars` as export commands, one per line, refusing anything the shell could
+    /// read back differently.
+    fn render_exports(&self, vars: &[EnvVar]) -> Result<String> {
+        let lines = vars
+            .iter()
+            .map(|var| {
+                check_env_var(var)?;
+                Ok(self.export_command(&var.name, &var.value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(lines.join("\n"))
+    }
 }
 
 pub fn shell_for_name(name: &str) -> Option<&'static dyn Shell> {
@@ -41,9 +84,95 @@ pub fn detect_shell_from_env() -> Option<&'static dyn Shell> {
     shell_for_name(name)
 }
 
+/// The shell whose hook will source our output: the one named in [`HOOK_SHELL_VAR`], else a
+/// POSIX shell, which is what `eval "$(roleman)"` and older hooks expect.
+pub fn hook_shell() -> &'static dyn Shell {
+    std::env::var(HOOK_SHELL_VAR)
+        .ok()
+        .and_then(|name| shell_for_name(&name))
+        .unwrap_or(&BASH_SHELL)
+}
+
+/// The fish shell, for rendering `set --format fish` outside the hook.
+pub(crate) fn fish() -> &'static dyn Shell {
+    &FISH_SHELL
+}
+
+/// Whether `name` is a portable environment variable name: `[A-Za-z_][A-Za-z0-9_]*`.
+pub fn is_valid_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Refuse names that aren't plain identifiers (they'd be spliced into code unquoted) and values
+/// no environment can hold.
+pub(crate) fn check_env_var(var: &EnvVar) -> Result<()> {
+    if !is_valid_var_name(&var.name) {
+        return Err(Error::InvalidEnvVar(format!(
+            "{:?} is not a valid variable name",
+            var.name
+        )));
+    }
+    if var.value.contains('\0') {
+        return Err(Error::InvalidEnvVar(format!(
+            "the value of {} contains a NUL byte",
+            var.name
+        )));
+    }
+    Ok(())
+}
+
+/// Whether `value` is a non-empty word no shell or dotenv loader treats specially. A leading
+/// `=` (zsh's `EQUALS` expansion) or `~` (tilde expansion) needs quoting.
+pub(crate) fn is_plain_word(value: &str) -> bool {
+    !value.is_empty() && !value.starts_with(['=', '~']) && value.chars().all(is_plain_char)
+}
+
+/// Characters that are literal anywhere but the start of a word.
+fn is_plain_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric()
+        || matches!(
+            ch,
+            '_' | '-' | '.' | '/' | ':' | '+' | ',' | '@' | '%' | '='
+        )
+}
+
+/// Quote for a POSIX shell, leaving plain words (keys, ARNs, base64 tokens) bare.
+pub(crate) fn posix_quote(value: &str) -> String {
+    if is_plain_word(value) {
+        value.to_string()
+    } else {
+        posix_quote_always(value)
+    }
+}
+
+/// Single-quote for a POSIX shell. Single quotes can't be escaped inside single quotes, so each
+/// `'` is written as `\'` between quoted runs; no run is ever empty, which keeps zsh's
+/// `RC_QUOTES` from reading `''` as a literal quote.
+pub(crate) fn posix_quote_always(value: &str) -> String {
+    if value.is_empty() {
+        return "''".to_string();
+    }
+    value
+        .split('\'')
+        .map(|run| {
+            if run.is_empty() {
+                String::new()
+            } else {
+                format!("'{run}'")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\\'")
+}
+
 #[cfg(test)]
 mod tests {
-    use super::shell_for_name;
+    use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn resolves_supported_shells() {
@@ -52,9 +181,227 @@ mod tests {
         assert!(shell_for_name("fish").is_some());
     }
 
+    #[test]
+    fn snippets_export_the_current_hook_version() {
+        for name in ["bash", "zsh", "fish"] {
+            let snippet = shell_for_name(name).unwrap().hook_snippet();
+            let exported = snippet
+                .lines()
+                .find(|line| line.contains("_ROLEMAN_HOOK_VERSION"))
+                .expect("snippet exports its version");
+            assert!(
+                exported.ends_with(&format!(" {HOOK_VERSION}"))
+                    || exported.ends_with(&format!("={HOOK_VERSION}")),
+                "{name}: {exported}"
+            );
+            assert!(snippet.contains("roleman --env-file \"$_ROLEMAN_HOOK_ENV\" renew"));
+        }
+    }
+
     #[test]
     fn fish_uses_fish_specific_install_line() {
         let fish = shell_for_name("fish").expect("fish shell should be supported");
         assert_eq!(fish.install_line(), "roleman hook fish | source");
     }
+
+    #[test]
+    fn quotes_only_values_that_need_it() {
+        let bash = shell_for_name("bash").unwrap();
+        let vars = [
+            EnvVar::new("AWS_SESSION_TOKEN", "IQo/b3JpZ2lu+ZWM="),
+            EnvVar::new("AWS_PROFILE", "it's $HOME"),
+            EnvVar::new("_ROLEMAN_RENEW_AT", ""),
+        ];
+        assert_eq!(
+            bash.render_exports(&vars).unwrap(),
+            "export AWS_SESSION_TOKEN=IQo/b3JpZ2lu+ZWM=\n\
+             export AWS_PROFILE='it'\\''s $HOME'\n\
+             export _ROLEMAN_RENEW_AT=''"
+        );
+        assert_eq!(
+            shell_for_name("fish")
+                .unwrap()
+                .render_exports(&vars)
+                .unwrap(),
+            "set -gx AWS_SESSION_TOKEN IQo/b3JpZ2lu+ZWM=\n\
+             set -gx AWS_PROFILE 'it\\'s $HOME'\n\
+             set -gx _ROLEMAN_RENEW_AT ''"
+        );
+        // Expansions that only fire at the start of a word.
+        assert_eq!(posix_quote("=ls"), "'=ls'");
+        assert_eq!(posix_quote("~/.aws"), "'~/.aws'");
+        assert_eq!(posix_quote("a=~b"), "'a=~b'");
+        assert_eq!(posix_quote_always("'a''"), "\\''a'\\'\\'");
+    }
+
+    #[test]
+    fn unsets_in_each_shells_syntax() {
+        let names = ["AWS_PROFILE", "AWS_REGION"];
+        assert_eq!(
+            shell_for_name("zsh").unwrap().unset_command(&names),
+            "unset AWS_PROFILE AWS_REGION"
+        );
+        assert_eq!(
+            shell_for_name("fish").unwrap().unset_command(&names),
+            "set -e AWS_PROFILE; set -e AWS_REGION"
+        );
+    }
+
+    #[test]
+    fn follows_the_hook_shell() {
+        let _lock = crate::test_support::lock_env();
+        unsafe { std::env::set_var(HOOK_SHELL_VAR, "fish") };
+        assert_eq!(hook_shell().name(), "fish");
+        unsafe { std::env::set_var(HOOK_SHELL_VAR, "tcsh") };
+        assert_eq!(hook_shell().name(), "bash");
+        unsafe { std::env::remove_var(HOOK_SHELL_VAR) };
+        assert_eq!(hook_shell().name(), "bash");
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_identifiers() {
+        for name in [
+            "",
+            "1AWS",
+            "AWS-PROFILE",
+            "A B",
+            "X=1",
+            "A;B",
+            "$(id)",
+            "ÄWS",
+        ] {
+            assert!(!is_valid_var_name(name), "{name:?}");
+            assert!(check_env_var(&EnvVar::new(name, "value")).is_err());
+        }
+        assert!(is_valid_var_name("_ROLEMAN_TARGET"));
+        assert!(check_env_var(&EnvVar::new("AWS_PROFILE", "a\0b")).is_err());
+    }
+
+    /// Read back one word of POSIX shell output: single-quoted runs, `\'` and bare characters.
+    fn parse_posix_word(word: &str) -> String {
+        let mut out = String::new();
+        let mut chars = word.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\'' => out.extend(chars.by_ref().take_while(|&ch| ch != '\'')),
+                '\\' => out.push(chars.next().expect("dangling backslash")),
+                _ => {
+                    assert!(is_plain_char(ch), "bare {ch:?} in {word:?}");
+                    out.push(ch);
+                }
+            }
+        }
+        out
+    }
+
+    /// Read back one word of fish output, where `\\` and `\'` are escapes inside single quotes.
+    fn parse_fish_word(word: &str) -> String {
+        let Some(quoted) = word.strip_prefix('\'') else {
+            assert!(is_plain_word(word), "bare {word:?}");
+            return word.to_string();
+        };
+        let quoted = quoted.strip_suffix('\'').expect("unterminated quote");
+        let mut out = String::new();
+        let mut chars = quoted.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => match chars.next() {
+                    Some(next @ ('\\' | '\'')) => out.push(next),
+                    Some(next) => out.extend(['\\', next]),
+                    None => out.push('\\'),
+                },
+                '\'' => panic!("unescaped quote in {word:?}"),
+                _ => out.push(ch),
+            }
+        }
+        out
+    }
+
+    /// Account aliases built from the pieces most likely to break out of a quoted word.
+    fn hostile_alias() -> impl Strategy<Value = String> {
+        let piece = prop_oneof![
+            Just("'".to_string()),
+            Just("''".to_string()),
+            Just("\"".to_string()),
+            Just("\\".to_string()),
+            Just("\\'".to_string()),
+            Just("$(touch pwned)".to_string()),
+            Just("`touch pwned`".to_string()),
+            Just("${HOME}".to_string()),
+            Just("; touch pwned #".to_string()),
+            Just("\n".to_string()),
+            Just("\t".to_string()),
+            Just("{a,b}".to_string()),
+            Just("*".to_string()),
+            Just("~".to_string()),
+            Just("=ls".to_string()),
+            Just("!!".to_string()),
+            "[a-zA-Z0-9 ]{0,4}",
+            "[^\\x00]{0,3}",
+        ];
+        prop::collection::vec(piece, 0..10).prop_map(|pieces| pieces.concat())
+    }
+
+    proptest! {
+        #[test]
+        fn posix_quoting_roundtrips(value in hostile_alias()) {
+            prop_assert_eq!(parse_posix_word(&posix_quote(&value)), value.clone());
+            prop_assert!(!posix_quote_always(&value).contains("'''"));
+        }
+
+        #[test]
+        fn fish_quoting_roundtrips(value in hostile_alias()) {
+            let fish = shell_for_name("fish").unwrap();
+            prop_assert_eq!(parse_fish_word(&fish.quote(&value)), value);
+        }
+
+        #[test]
+        fn rendered_exports_keep_one_command_per_line(value in "[^\\x00\\n\\r]*") {
+            let vars = [EnvVar::new("ROLEMAN_ACCOUNT", value)];
+            for name in ["bash", "fish"] {
+                let rendered = shell_for_name(name).unwrap().render_exports(&vars).unwrap();
+                prop_assert_eq!(rendered.lines().count(), 1);
+            }
+        }
+    }
+
+    /// Source rendered exports in `shell` and print the value back.
+    fn read_back(shell: &str, exports: &str, setup: &str) -> String {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("env");
+        std::fs::write(&path, exports).unwrap();
+        let script = format!("{setup}source \"$1\"; printf '%s' \"$ROLEMAN_ACCOUNT\"");
+        let output = std::process::Command::new(shell)
+            .args(["-c", &script, "roleman", path.to_str().unwrap()])
+            .current_dir(dir.path())
+            .env_remove("ENV")
+            .env_remove("BASH_ENV")
+            .output()
+            .unwrap_or_else(|err| panic!("failed to run {shell}: {err}"));
+        assert!(output.status.success(), "{shell} failed on {exports:?}");
+        assert!(
+            !dir.path().join("pwned").exists(),
+            "{shell} ran {exports:?}"
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(48))]
+
+        #[test]
+        fn bash_reads_hostile_aliases_back_verbatim(alias in hostile_alias()) {
+            let vars = [EnvVar::new("ROLEMAN_ACCOUNT", alias.clone())];
+            let exports = shell_for_name("bash").unwrap().render_exports(&vars).unwrap();
+            prop_assert_eq!(read_back("bash", &exports, ""), alias);
+        }
+
+        #[test]
+        #[ignore = "needs zsh; CI installs it and runs ignored tests"]
+        fn zsh_reads_hostile_aliases_back_verbatim(alias in hostile_alias()) {
+            let vars = [EnvVar::new("ROLEMAN_ACCOUNT", alias.clone())];
+            let exports = shell_for_name("zsh").unwrap().render_exports(&vars).unwrap();
+            prop_assert_eq!(read_back("zsh", &exports, "setopt rc_quotes; "), alias);
+        }
+    }
 }
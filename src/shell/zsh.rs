@@ -14,16 +14,22 @@ impl Shell for ZshShell {
 
     fn hook_snippet(&self) -> &'static str {
         r##"export _ROLEMAN_HOOK_ENV="${XDG_STATE_HOME:-$HOME/.local/state}/roleman/env-${TTY//\//_}"
-export _ROLEMAN_HOOK_VERSION=1
+export _ROLEMAN_HOOK_VERSION=3
+export _ROLEMAN_HOOK_SHELL=zsh
 roleman() {
   command roleman --env-file "$_ROLEMAN_HOOK_ENV" "$@"
 }
 _roleman_precmd() {
+  if [[ -n "${_ROLEMAN_RENEW_AT:-}" && ! -f "$_ROLEMAN_HOOK_ENV" ]] \
+    && (( ${EPOCHSECONDS:-$(date +%s)} >= _ROLEMAN_RENEW_AT )); then
+    command roleman --env-file "$_ROLEMAN_HOOK_ENV" renew
+  fi
   if [[ -f "$_ROLEMAN_HOOK_ENV" ]]; then
     source "$_ROLEMAN_HOOK_ENV"
     rm -f "$_ROLEMAN_HOOK_ENV"
   fi
 }
+zmodload zsh/datetime 2>/dev/null
 autoload -Uz add-zsh-hook
 add-zsh-hook precmd _roleman_precmd"##
     }
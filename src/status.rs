@@ -0,0 +1,309 @@
+//! What the current shell is using, for `roleman status` and `roleman prompt`.
+//!
+//! Everything comes from the environment the hook exported and from local caches, never the
+//! network, so the prompt segment is cheap enough to render on every prompt.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::provider::{self, ActiveMarker};
+use crate::renew::{RenewTarget, TARGET_VAR};
+use crate::util::format_duration;
+
+/// Template `roleman prompt` renders when neither `--template` nor `prompt_template` is set.
+pub const DEFAULT_PROMPT_TEMPLATE: &str = "{profile}{marker} {expires_in}";
+
+/// Whether the shell's credentials are usable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CredentialState {
+    /// Credentials are exported and unexpired.
+    Valid,
+    /// Credentials are exported but expired (or roleman's cached copy is).
+    Stale,
+    /// Nothing roleman recognizes is exported.
+    None,
+}
+
+/// The identity, target and remaining lifetimes of the shell's active credentials.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveStatus {
+    pub state: CredentialState,
+    pub identity: Option<String>,
+    pub account_id: Option<String>,
+    pub account_name: Option<String>,
+    pub role_name: Option<String>,
+    /// `full` or `readonly`.
+    pub scope: Option<&'static str>,
+    pub region: Option<String>,
+    pub profile: Option<String>,
+    /// `AWS_CREDENTIAL_EXPIRATION` as exported.
+    pub credentials_expiration: Option<String>,
+    pub credentials_remaining_seconds: Option<u64>,
+    /// Remaining lifetime of the identity's cached sign-in (AWS: the SSO token).
+    pub session_remaining_seconds: Option<u64>,
+}
+
+impl ActiveStatus {
+    /// Render a prompt template. Unknown placeholders are left as written, and an inactive
+    /// shell renders nothing so the prompt segment disappears.
+    pub fn render(&self, template: &str) -> String {
+        if self.state == CredentialState::None {
+            return String::new();
+        }
+        let marker = match self.state {
+            CredentialState::Valid => "*",
+            CredentialState::Stale => "!",
+            CredentialState::None => "",
+        };
+        let state = match self.state {
+            CredentialState::Valid => "valid",
+            CredentialState::Stale => "stale",
+            CredentialState::None => "",
+        };
+        let readonly = if self.scope == Some("readonly") {
+            "readonly"
+        } else {
+            ""
+        };
+        let account = self.account_name.as_deref().or(self.account_id.as_deref());
+        let placeholders = [
+            ("marker", Some(marker.to_string())),
+            ("state", Some(state.to_string())),
+            ("identity", self.identity.clone()),
+            ("account", account.map(str::to_string)),
+            ("account_id", self.account_id.clone()),
+            ("role", self.role_name.clone()),
+            ("scope", self.scope.map(str::to_string)),
+            ("readonly", Some(readonly.to_string())),
+            ("region", self.region.clone()),
+            ("profile", self.profile.clone()),
+            (
+                "expires_in",
+                self.credentials_remaining_seconds.map(format_remaining),
+            ),
+            (
+                "session_expires_in",
+                self.session_remaining_seconds.map(format_remaining),
+            ),
+        ];
+        let mut rendered = template.to_string();
+        for (name, value) in placeholders {
+            rendered = rendered.replace(&format!("{{{name}}}"), value.as_deref().unwrap_or(""));
+        }
+        rendered.trim().to_string()
+    }
+}
+
+/// Collect the active status. `include_session` also reads the provider's sign-in cache, which
+/// the prompt skips unless its template asks for it.
+pub(crate) fn collect(config: &Config, include_session: bool, now_ms: u64) -> ActiveStatus {
+    let profile = env_nonempty("AWS_PROFILE");
+    let credentials_expiration = env_nonempty("AWS_CREDENTIAL_EXPIRATION");
+    let expiration_ms = credentials_expiration.as_deref().and_then(parse_rfc3339_ms);
+    let region = env_nonempty("AWS_REGION").or_else(|| env_nonempty("CLOUDSDK_COMPUTE_REGION"));
+    let expired = expiration_ms.is_some_and(|expiration| expiration <= now_ms);
+
+    let mut status = ActiveStatus {
+        state: if profile.is_none() {
+            CredentialState::None
+        } else if expired {
+            CredentialState::Stale
+        } else {
+            CredentialState::Valid
+        },
+        identity: None,
+        account_id: None,
+        account_name: None,
+        role_name: None,
+        scope: None,
+        region,
+        profile,
+        credentials_expiration,
+        credentials_remaining_seconds: expiration_ms
+            .map(|expiration| expiration.saturating_sub(now_ms) / 1000),
+        session_remaining_seconds: None,
+    };
+
+    let Some(target) = env_nonempty(TARGET_VAR).and_then(|value| RenewTarget::parse(&value).ok())
+    else {
+        return status;
+    };
+    let Some(identity) = config
+        .identities
+        .iter()
+        .find(|identity| identity.name == target.identity)
+    else {
+        return status;
+    };
+    let provider = match provider::for_identity(identity) {
+        Ok(provider) => provider,
+        Err(err) => {
+            debug!(error = %err, "failed to build provider for status");
+            return status;
+        }
+    };
+    let marker = provider
+        .active_markers(
+            &[target.choice()],
+            target.scope,
+            config.min_credential_lifetime(identity),
+        )
+        .first()
+        .copied()
+        .unwrap_or(ActiveMarker::Inactive);
+    if marker == ActiveMarker::Inactive {
+        // The shell switched away from the target roleman last exported.
+        return status;
+    }
+
+    status.state = if marker == ActiveMarker::ActiveStale || expired {
+        CredentialState::Stale
+    } else {
+        CredentialState::Valid
+    };
+    status.identity = Some(target.identity.clone());
+    status.account_id = Some(target.account_id.clone());
+    status.account_name = Some(target.account_name.clone()).filter(|name| !name.is_empty());
+    status.role_name = Some(target.role_name.clone());
+    status.scope = Some(target.scope.cache_tag());
+    if status.region.is_none() {
+        status.region = target.region.clone();
+    }
+    if status.profile.is_none() {
+        status.profile = Some(format!(
+            "{}/{}{}",
+            target.account_id,
+            target.role_name,
+            target.scope.profile_suffix()
+        ));
+    }
+    if include_session {
+        status.session_remaining_seconds = provider
+            .session_expiration_ms()
+            .map(|expiration| expiration.saturating_sub(now_ms) / 1000);
+    }
+    status
+}
+
+fn format_remaining(seconds: u64) -> String {
+    if seconds == 0 {
+        "expired".to_string()
+    } else {
+        format_duration(Duration::from_secs(seconds))
+    }
+}
+
+fn env_nonempty(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn parse_rfc3339_ms(value: &str) -> Option<u64> {
+    let parsed =
+        time::OffsetDateTime::parse(value, &time::format_description::well_known::Rfc3339).ok()?;
+    Some((parsed.unix_timestamp_nanos() / 1_000_000).max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn active() -> ActiveStatus {
+        ActiveStatus {
+            state: CredentialState::Valid,
+            identity: Some("work".to_string()),
+            account_id: Some("123456789012".to_string()),
+            account_name: Some("Platform".to_string()),
+            role_name: Some("Admin".to_string()),
+            scope: Some("readonly"),
+            region: Some("eu-west-1".to_string()),
+            profile: Some("Platform/Admin@readonly".to_string()),
+            credentials_expiration: Some("2030-01-01T00:00:00Z".to_string()),
+            credentials_remaining_seconds: Some(42 * 60 + 10),
+            session_remaining_seconds: Some(7 * 3600 + 3 * 60),
+        }
+    }
+
+    #[test]
+    fn renders_default_template() {
+        assert_eq!(
+            active().render(DEFAULT_PROMPT_TEMPLATE),
+            "Platform/Admin@readonly* 42m 10s"
+        );
+        let stale = ActiveStatus {
+            state: CredentialState::Stale,
+            credentials_remaining_seconds: Some(0),
+            ..active()
+        };
+        assert_eq!(
+            stale.render(DEFAULT_PROMPT_TEMPLATE),
+            "Platform/Admin@readonly! expired"
+        );
+    }
+
+    #[test]
+    fn renders_custom_placeholders() {
+        assert_eq!(
+            active()
+                .render("{account}:{role} [{readonly}] {region} sso={session_expires_in} {nope}"),
+            "Platform:Admin [readonly] eu-west-1 sso=7h 3m {nope}"
+        );
+        let unnamed = ActiveStatus {
+            account_name: None,
+            ..active()
+        };
+        assert_eq!(unnamed.render("{account}"), "123456789012");
+    }
+
+    #[test]
+    fn inactive_shell_renders_nothing() {
+        let inactive = ActiveStatus {
+            state: CredentialState::None,
+            ..active()
+        };
+        assert_eq!(inactive.render(DEFAULT_PROMPT_TEMPLATE), "");
+    }
+
+    #[test]
+    fn collects_foreign_profile_from_environment() {
+        let _lock = crate::test_support::lock_env();
+        let names = [
+            "AWS_PROFILE",
+            "AWS_CREDENTIAL_EXPIRATION",
+            "AWS_REGION",
+            "CLOUDSDK_COMPUTE_REGION",
+            TARGET_VAR,
+        ];
+        let previous: Vec<_> = names.iter().map(|name| std::env::var(name).ok()).collect();
+        unsafe {
+            for name in names {
+                std::env::remove_var(name);
+            }
+            std::env::set_var("AWS_PROFILE", "legacy-admin");
+            std::env::set_var("AWS_CREDENTIAL_EXPIRATION", "2024-01-01T00:10:00Z");
+            std::env::set_var("AWS_REGION", "us-west-2");
+        }
+        let now = 1_704_067_200_000; // 2024-01-01T00:00:00Z
+        let status = collect(&Config::default(), true, now);
+        let later = collect(&Config::default(), false, now + 11 * 60 * 1000);
+        unsafe {
+            for (name, value) in names.iter().zip(previous) {
+                match value {
+                    Some(value) => std::env::set_var(name, value),
+                    None => std::env::remove_var(name),
+                }
+            }
+        }
+
+        assert_eq!(status.state, CredentialState::Valid);
+        assert_eq!(status.profile.as_deref(), Some("legacy-admin"));
+        assert_eq!(status.region.as_deref(), Some("us-west-2"));
+        assert_eq!(status.credentials_remaining_seconds, Some(600));
+        assert!(status.identity.is_none());
+        assert_eq!(later.state, CredentialState::Stale);
+        assert_eq!(later.credentials_remaining_seconds, Some(0));
+    }
+}
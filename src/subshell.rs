@@ -0,0 +1,103 @@
+//! Child processes for `roleman exec` and `roleman shell`.
+//!
+//! Credentials go into the child's environment only, so the calling shell never sees them.
+
+use std::process::{Command, ExitStatus};
+
+use crate::error::{Error, Result};
+use crate::provider::EnvVar;
+
+/// Marker set in the child environment, naming the active profile.
+pub(crate) const ACTIVE_MARKER_VAR: &str = "ROLEMAN_ACTIVE";
+
+/// The user's login shell, for `roleman shell`.
+pub(crate) fn default_shell() -> String {
+    if let Ok(shell) = std::env::var("SHELL")
+        && !shell.trim().is_empty()
+    {
+        return shell;
+    }
+    if cfg!(windows) {
+        std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+    } else {
+        "/bin/sh".to_string()
+    }
+}
+
+/// Run `command` with `vars` added to the inherited environment and wait for it.
+///
+/// Returns the child's exit code as computed by [`exit_code`]. Ctrl-C is left to the child
+/// (it shares our process group), so interrupting a command or an interactive subshell
+/// doesn't kill roleman underneath it.
+pub(crate) async fn run(command: &[String], vars: &[EnvVar]) -> Result<i32> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| Error::Command("no command given".to_string()))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .envs(vars.iter().map(|var| (&var.name, &var.value)))
+        .spawn()
+        .map_err(|err| Error::Command(format!("{program}: {err}")))?;
+
+    let wait = tokio::task::spawn_blocking(move || child.wait());
+    tokio::pin!(wait);
+    let status = loop {
+        tokio::select! {
+            result = &mut wait => break result,
+            // Listening for Ctrl-C replaces the default "terminate" action for roleman.
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    };
+    let status = status
+        .map_err(|err| Error::Command(err.to_string()))?
+        .map_err(|err| Error::Command(format!("{program}: {err}")))?;
+    Ok(exit_code(status))
+}
+
+/// Exit code to report for the child, as shells do: a child killed by a signal exits
+/// `128 + signal`.
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn passes_env_and_exit_code() {
+        let command = vec![
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            "test \"$ROLEMAN_ACTIVE\" = Acme/Admin && exit 7".to_string(),
+        ];
+        let vars = vec![EnvVar::new(ACTIVE_MARKER_VAR, "Acme/Admin")];
+        assert_eq!(run(&command, &vars).await.unwrap(), 7);
+        // Without the marker the test fails and the shell exits 1.
+        assert_eq!(run(&command, &[]).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_signals_like_a_shell() {
+        let command = vec![
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            "kill -TERM $$".to_string(),
+        ];
+        assert_eq!(run(&command, &[]).await.unwrap(), 128 + 15);
+    }
+
+    #[tokio::test]
+    async fn reports_missing_programs() {
+        let command = vec!["roleman-definitely-missing-binary".to_string()];
+        let err = run(&command, &[]).await.unwrap_err();
+        assert!(err.to_string().contains("failed to run"));
+    }
+}
@@ -1,6 +1,10 @@
 use std::borrow::Cow;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex, RwLock};
 
+use ratatui::style::{Color, Modifier};
+use ratatui::text::Line;
 use skim::matcher::Matcher;
 use skim::prelude::*;
 use skim::tui::event::Action;
@@ -22,21 +26,164 @@ pub struct TuiSelection {
     pub auto_selected: bool,
 }
 
+/// A refreshed role list, delivered while the selector is already open.
+pub struct RoleListUpdate {
+    pub choices: Vec<RoleChoice>,
+    pub markers: Vec<ActiveMarker>,
+    pub guarded: Vec<bool>,
+}
+
+/// Labels of listed roles that a refresh no longer returned. Skim can't drop items it has
+/// been sent, so they stay listed but crossed out.
+type RemovedLabels = Arc<RwLock<HashSet<String>>>;
+
 struct ChoiceItem {
     label: String,
+    /// Account marked `confirm = true`; rendered red so it stands out from sandboxes.
+    guarded: bool,
+    removed: RemovedLabels,
 }
 
 impl SkimItem for ChoiceItem {
     fn text(&self) -> Cow<'_, str> {
         Cow::Borrowed(&self.label)
     }
+
+    fn display<'a>(&'a self, mut context: DisplayContext) -> Line<'a> {
+        let removed = self
+            .removed
+            .read()
+            .is_ok_and(|removed| removed.contains(&self.label));
+        if removed {
+            context.base_style = context
+                .base_style
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::CROSSED_OUT);
+        } else if self.guarded {
+            context.base_style = context.base_style.fg(Color::Red);
+        }
+        context.to_line(self.text())
+    }
 }
 
+/// What the selector is showing, shared with the thread that applies a [`RoleListUpdate`].
+struct SkimChoices {
+    /// Label → choice, for every item sent to skim.
+    lookup: Mutex<HashMap<String, RoleChoice>>,
+    removed: RemovedLabels,
+    /// Whether labels carry a marker column.
+    any_active: bool,
+}
+
+impl SkimChoices {
+    fn new(any_active: bool) -> Self {
+        Self {
+            lookup: Mutex::new(HashMap::new()),
+            removed: RemovedLabels::default(),
+            any_active,
+        }
+    }
+
+    /// Register choices and build their skim items.
+    fn items<'a>(
+        &self,
+        entries: impl Iterator<Item = (&'a RoleChoice, ActiveMarker, bool)>,
+    ) -> Vec<Arc<dyn SkimItem>> {
+        let mut lookup = self.lookup.lock().unwrap_or_else(|err| err.into_inner());
+        entries
+            .map(|(choice, marker, guarded)| {
+                let label = choice_label(choice, marker, self.any_active);
+                lookup.insert(label.clone(), choice.clone());
+                Arc::new(ChoiceItem {
+                    label,
+                    guarded,
+                    removed: self.removed.clone(),
+                }) as Arc<dyn SkimItem>
+            })
+            .collect()
+    }
+
+    /// Cross out listed roles missing from `update` and return items for the new ones.
+    fn apply(&self, update: &RoleListUpdate) -> Vec<Arc<dyn SkimItem>> {
+        let key = |choice: &RoleChoice| (choice.account_id.clone(), choice.role_name.clone());
+        let refreshed: HashSet<_> = update.choices.iter().map(key).collect();
+        let listed: HashSet<_> = {
+            let lookup = self.lookup.lock().unwrap_or_else(|err| err.into_inner());
+            let mut removed = self.removed.write().unwrap_or_else(|err| err.into_inner());
+            for (label, choice) in lookup.iter() {
+                if !refreshed.contains(&key(choice)) {
+                    removed.insert(label.clone());
+                }
+            }
+            lookup.values().map(key).collect()
+        };
+        let added = self.items(
+            annotated(&update.choices, &update.markers, &update.guarded)
+                .filter(|(choice, _, _)| !listed.contains(&key(choice))),
+        );
+        debug!(added = added.len(), "applied refreshed role list");
+        added
+    }
+
+    /// The choice behind a selected label; an error if a refresh found it gone.
+    fn resolve(&self, label: &str) -> Result<Option<RoleChoice>> {
+        let removed = self
+            .removed
+            .read()
+            .is_ok_and(|removed| removed.contains(label));
+        let lookup = self.lookup.lock().unwrap_or_else(|err| err.into_inner());
+        match lookup.get(label) {
+            Some(choice) if removed => Err(Error::RoleSelection(format!(
+                "{} is no longer available; pick again from the refreshed list",
+                choice.label()
+            ))),
+            choice => Ok(choice.cloned()),
+        }
+    }
+}
+
+/// Pair each choice with its marker and guard flag.
+fn annotated<'a>(
+    choices: &'a [RoleChoice],
+    markers: &'a [ActiveMarker],
+    guarded: &'a [bool],
+) -> impl Iterator<Item = (&'a RoleChoice, ActiveMarker, bool)> {
+    choices.iter().enumerate().map(|(index, choice)| {
+        (
+            choice,
+            markers
+                .get(index)
+                .copied()
+                .unwrap_or(ActiveMarker::Inactive),
+            guarded.get(index).copied().unwrap_or(false),
+        )
+    })
+}
+
+/// The label skim shows and matches for `choice`.
+fn choice_label(choice: &RoleChoice, marker: ActiveMarker, any_active: bool) -> String {
+    if !any_active {
+        return choice.label();
+    }
+    // The provider resolves which choice is active and whether its cached
+    // credentials are fresh; the TUI just renders the marker prefix.
+    let prefix = match marker {
+        ActiveMarker::ActiveValid => "* ",
+        ActiveMarker::ActiveStale => "! ",
+        ActiveMarker::Inactive => "  ",
+    };
+    format!("{}{}", prefix, choice.label())
+}
+
+/// Run the selector over `choices`. When `updates` is given, the first refreshed list it
+/// delivers is merged into the running selector.
 pub fn select_role(
     prompt: &str,
     choices: &[RoleChoice],
     markers: &[ActiveMarker],
+    guarded: &[bool],
     initial_query: Option<&str>,
+    updates: Option<Receiver<RoleListUpdate>>,
 ) -> Result<Option<TuiSelection>> {
     if choices.is_empty() {
         return Ok(None);
@@ -92,7 +239,7 @@ pub fn select_role(
         crate::ui::hint("Type to filter, ↑/↓ to navigate, ⏎ selects, ^o opens in browser.")
     );
 
-    let (selected, open_in_browser) = run_skim(options, &ordered, markers)?;
+    let (selected, open_in_browser) = run_skim(options, &ordered, markers, guarded, updates)?;
 
     if selected.is_empty() {
         debug!("no role selected");
@@ -129,6 +276,8 @@ fn find_single_query_match(
     let mut matches = choices.iter().filter(|choice| {
         let item: Arc<dyn SkimItem> = Arc::new(ChoiceItem {
             label: choice.label(),
+            guarded: false,
+            removed: RemovedLabels::default(),
         });
         engine.match_item(item).is_some()
     });
@@ -143,39 +292,35 @@ fn run_skim(
     options: SkimOptions,
     choices: &[RoleChoice],
     markers: &[ActiveMarker],
+    guarded: &[bool],
+    updates: Option<Receiver<RoleListUpdate>>,
 ) -> Result<(Vec<RoleChoice>, bool)> {
     trace!(count = choices.len(), "preparing skim items");
     let any_active = markers
         .iter()
         .any(|marker| *marker != ActiveMarker::Inactive);
-    let mut lookup = std::collections::HashMap::new();
-    let mut items = Vec::with_capacity(choices.len());
-    for (index, choice) in choices.iter().enumerate() {
-        // The provider resolves which choice is active and whether its cached
-        // credentials are fresh; the TUI just renders the marker prefix.
-        let label = if any_active {
-            let prefix = match markers
-                .get(index)
-                .copied()
-                .unwrap_or(ActiveMarker::Inactive)
-            {
-                ActiveMarker::ActiveValid => "* ",
-                ActiveMarker::ActiveStale => "! ",
-                ActiveMarker::Inactive => "  ",
-            };
-            format!("{}{}", prefix, choice.label())
-        } else {
-            choice.label()
-        };
-        lookup.insert(label.clone(), choice.clone());
-        items.push(Arc::new(ChoiceItem { label }) as Arc<dyn SkimItem>);
-    }
+    let skim_choices = Arc::new(SkimChoices::new(any_active));
+    let items = skim_choices.items(annotated(choices, markers, guarded));
 
     let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
     if tx.send(items).is_err() {
         return Ok((Vec::new(), false));
     }
-    drop(tx);
+    if let Some(updates) = updates {
+        let skim_choices = skim_choices.clone();
+        // Skim keeps reading while this sender is alive; the thread ends once the refresh
+        // delivers its list or is dropped.
+        std::thread::spawn(move || {
+            if let Ok(update) = updates.recv() {
+                let added = skim_choices.apply(&update);
+                if !added.is_empty() {
+                    let _ = tx.send(added);
+                }
+            }
+        });
+    } else {
+        drop(tx);
+    }
 
     let (selected, open_in_browser) = match Skim::run_with(options, Some(rx)) {
         Ok(out) => {
@@ -202,8 +347,8 @@ fn run_skim(
     let mut result = Vec::new();
     for item in selected {
         let key = item.text();
-        if let Some(choice) = lookup.get(key.as_ref()) {
-            result.push(choice.clone());
+        if let Some(choice) = skim_choices.resolve(key.as_ref())? {
+            result.push(choice);
         } else {
             debug!(value = %key, "missing selection lookup");
         }
@@ -285,6 +430,41 @@ mod tests {
         assert_eq!(ordered[1].account_name, "Sandbox");
     }
 
+    #[test]
+    fn merges_refreshed_role_list() {
+        let choice = |account_id: &str, role_name: &str| RoleChoice {
+            account_id: account_id.into(),
+            account_name: "Platform".into(),
+            role_name: role_name.into(),
+        };
+        let skim_choices = SkimChoices::new(false);
+        let listed = [choice("111", "Admin"), choice("111", "ReadOnly")];
+        assert_eq!(skim_choices.items(annotated(&listed, &[], &[])).len(), 2);
+
+        let added = skim_choices.apply(&RoleListUpdate {
+            choices: vec![choice("111", "ReadOnly"), choice("222", "Deploy")],
+            markers: Vec::new(),
+            guarded: Vec::new(),
+        });
+
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].text(), choice("222", "Deploy").label());
+        assert!(skim_choices.resolve(&listed[0].label()).is_err());
+        assert_eq!(
+            skim_choices
+                .resolve(&listed[1].label())
+                .unwrap()
+                .map(|choice| choice.role_name),
+            Some("ReadOnly".to_string())
+        );
+        assert!(
+            skim_choices
+                .resolve(&choice("222", "Deploy").label())
+                .unwrap()
+                .is_some()
+        );
+    }
+
     #[test]
     fn normalizes_initial_query() {
         assert_eq!(normalize_initial_query(None), None);
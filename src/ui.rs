@@ -1,9 +1,30 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use indicatif::{ProgressBar, ProgressStyle};
 use owo_colors::OwoColorize;
 
+/// Set while the selector owns the terminal, so background work doesn't draw over it.
+static SPINNERS_HIDDEN: AtomicBool = AtomicBool::new(false);
+
+/// Keeps spinners hidden until dropped.
+pub struct HiddenSpinners(());
+
+impl Drop for HiddenSpinners {
+    fn drop(&mut self) {
+        SPINNERS_HIDDEN.store(false, Ordering::Relaxed);
+    }
+}
+
+pub fn hide_spinners() -> HiddenSpinners {
+    SPINNERS_HIDDEN.store(true, Ordering::Relaxed);
+    HiddenSpinners(())
+}
+
 pub fn spinner(message: &str) -> ProgressBar {
+    if SPINNERS_HIDDEN.load(Ordering::Relaxed) {
+        return ProgressBar::hidden();
+    }
     let style = ProgressStyle::with_template("{spinner} {msg}")
         .unwrap()
         .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]);
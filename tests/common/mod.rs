@@ -7,7 +7,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use axum::body::Bytes;
 use axum::extract::State;
-use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::http::{HeaderMap, StatusCode, Uri, header};
 use axum::response::IntoResponse;
 use axum::routing::any;
 use axum::{Json, Router};
@@ -18,6 +18,9 @@ use tracing::info;
 
 static ENV_LOCK: Mutex<()> = Mutex::new(());
 
+/// Session name of the SSO user behind every mock STS caller.
+pub const MOCK_SSO_USER: &str = "mock.user@example.com";
+
 pub fn lock_env() -> MutexGuard<'static, ()> {
     ENV_LOCK.lock().expect("failed to lock env mutex")
 }
@@ -114,6 +117,12 @@ async fn handle_root(
     uri: Uri,
     body: Bytes,
 ) -> impl IntoResponse {
+    if let Some(path) = uri.path().strip_prefix("/gcp/") {
+        return handle_gcp(path, &headers, &body);
+    }
+    if uri.path().starts_with("/sts") {
+        return handle_sts(&body);
+    }
     let target = headers
         .get("x-amz-target")
         .and_then(|value| value.to_str().ok())
@@ -214,6 +223,158 @@ async fn handle_root(
     }
 }
 
+/// Google API stand-in, reached through `ROLEMAN_GCP_ENDPOINT=<base>/gcp`.
+///
+/// `mock-data` lists no service accounts (403), like projects a user can see but not manage;
+/// `mock-outage` fails with a 503.
+fn handle_gcp(path: &str, headers: &HeaderMap, body: &Bytes) -> axum::response::Response {
+    let form = parse_form(body);
+    let authorized = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value == "Bearer mock-gcp-user-token");
+    let forbidden = || {
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": { "code": 403, "message": "Permission denied", "status": "PERMISSION_DENIED" } })),
+        )
+            .into_response()
+    };
+
+    match path {
+        "oauth2/token" => {
+            if form.get("refresh_token").map(String::as_str) != Some("mock-refresh-token") {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": "invalid_grant", "error_description": "Bad refresh token" })),
+                )
+                    .into_response();
+            }
+            Json(json!({
+                "access_token": "mock-gcp-user-token",
+                "expires_in": 3599,
+                "token_type": "Bearer",
+            }))
+            .into_response()
+        }
+        "cloudresourcemanager/v1/projects" if authorized => Json(json!({
+            "projects": [
+                { "projectId": "mock-platform", "name": "Mock Platform", "lifecycleState": "ACTIVE" },
+                { "projectId": "mock-data", "name": "Mock Data", "lifecycleState": "ACTIVE" },
+                { "projectId": "mock-outage", "name": "Mock Outage", "lifecycleState": "ACTIVE" },
+            ]
+        }))
+        .into_response(),
+        "iam/v1/projects/mock-platform/serviceAccounts" if authorized => Json(json!({
+            "accounts": [
+                { "email": "deploy@mock-platform.iam.gserviceaccount.com" },
+                { "email": "old@mock-platform.iam.gserviceaccount.com", "disabled": true },
+            ]
+        }))
+        .into_response(),
+        "iam/v1/projects/mock-outage/serviceAccounts" if authorized => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": { "code": 503, "message": "The service is currently unavailable.", "status": "UNAVAILABLE" } })),
+        )
+            .into_response(),
+        path if authorized
+            && path.starts_with("iamcredentials/v1/projects/-/serviceAccounts/")
+            && path.ends_with(":generateAccessToken") =>
+        {
+            let expire_time = (time::OffsetDateTime::now_utc() + time::Duration::hours(1))
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default();
+            Json(json!({
+                "accessToken": "mock-gcp-sa-token",
+                "expireTime": expire_time,
+            }))
+            .into_response()
+        }
+        "sts/v1/token" => {
+            let bounded = form
+                .get("options")
+                .is_some_and(|options| options.contains("accessBoundary"));
+            if form.get("subject_token").map(String::as_str) != Some("mock-gcp-sa-token")
+                || !bounded
+            {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": "invalid_request", "error_description": "Bad token exchange" })),
+                )
+                    .into_response();
+            }
+            Json(json!({
+                "access_token": "mock-gcp-downscoped-token",
+                "issued_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "token_type": "Bearer",
+                "expires_in": 3600,
+            }))
+            .into_response()
+        }
+        _ => forbidden(),
+    }
+}
+
+/// STS stand-in, reached through `ROLEMAN_STS_ENDPOINT=<base>/sts`.
+///
+/// Callers are always the mock SSO user; `AssumeRole` echoes the session name in the session
+/// token so tests can check what roleman asked for.
+fn handle_sts(body: &Bytes) -> axum::response::Response {
+    let form = parse_form(body);
+    let xml = |body: String| ([(header::CONTENT_TYPE, "text/xml")], body).into_response();
+    match form.get("Action").map(String::as_str) {
+        Some("GetCallerIdentity") => xml(format!(
+            r#"<GetCallerIdentityResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <GetCallerIdentityResult>
+    <Arn>arn:aws:sts::111111111111:assumed-role/AWSReservedSSO_Admin_abc/{MOCK_SSO_USER}</Arn>
+    <UserId>AROAMOCK:{MOCK_SSO_USER}</UserId>
+    <Account>111111111111</Account>
+  </GetCallerIdentityResult>
+  <ResponseMetadata><RequestId>mock</RequestId></ResponseMetadata>
+</GetCallerIdentityResponse>"#
+        )),
+        Some("AssumeRole") => {
+            let session = form.get("RoleSessionName").cloned().unwrap_or_default();
+            let expiration = (time::OffsetDateTime::now_utc() + time::Duration::hours(1))
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default();
+            xml(format!(
+                r#"<AssumeRoleResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleResult>
+    <Credentials>
+      <AccessKeyId>ASIAMOCKCHAINED</AccessKeyId>
+      <SecretAccessKey>mock-chained-secret</SecretAccessKey>
+      <SessionToken>mock-chained-token:{session}</SessionToken>
+      <Expiration>{expiration}</Expiration>
+    </Credentials>
+    <AssumedRoleUser>
+      <Arn>arn:aws:sts::222222222222:assumed-role/Deployer/{session}</Arn>
+      <AssumedRoleId>AROAMOCKCHAINED:{session}</AssumedRoleId>
+    </AssumedRoleUser>
+  </AssumeRoleResult>
+  <ResponseMetadata><RequestId>mock</RequestId></ResponseMetadata>
+</AssumeRoleResponse>"#
+            ))
+        }
+        _ => (StatusCode::BAD_REQUEST, "unknown STS action").into_response(),
+    }
+}
+
+fn parse_form(bytes: &Bytes) -> HashMap<String, String> {
+    String::from_utf8_lossy(bytes)
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| {
+            let decode = |raw: &str| {
+                urlencoding::decode(&raw.replace('+', " "))
+                    .map(|value| value.into_owned())
+                    .unwrap_or_default()
+            };
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
 async fn parse_json(bytes: Bytes) -> Result<Value, String> {
     if bytes.is_empty() {
         return Ok(json!({}));
@@ -1,7 +1,13 @@
+// The env lock is held across awaits on purpose: it serializes env mutation between tests.
+#![allow(clippy::await_holding_lock)]
+
 mod common;
 
-use common::{MockServerOptions, lock_env, start_mock_server};
-use roleman::provider::aws::sdk as aws_sdk;
+use common::{MOCK_SSO_USER, MockServerOptions, lock_env, start_mock_server};
+use roleman::config::SsoIdentity;
+use roleman::provider::aws::{login, sdk as aws_sdk, sso_cache, throttle::Throttle};
+use roleman::provider::{EnvVar, PostLoginActions, for_identity};
+use roleman::{AccessScope, RoleChoice};
 
 #[tokio::test]
 async fn e2e_sso_flow_uses_mock_endpoints() {
@@ -23,7 +29,8 @@ async fn e2e_sso_flow_uses_mock_endpoints() {
     let region = "us-east-1";
     let token = "mock-access-token";
 
-    let accounts = aws_sdk::list_accounts(token, region)
+    let throttle = Throttle::default();
+    let accounts = aws_sdk::list_accounts(token, region, &throttle)
         .await
         .expect("list_accounts failed");
     assert!(!accounts.is_empty());
@@ -32,7 +39,7 @@ async fn e2e_sso_flow_uses_mock_endpoints() {
         .iter()
         .find(|entry| entry.id == "111111111111")
         .expect("expected mock account");
-    let roles = aws_sdk::list_account_roles(token, region, &account.id)
+    let roles = aws_sdk::list_account_roles(token, region, &account.id, &throttle)
         .await
         .expect("list_account_roles failed");
     assert!(roles.iter().any(|role| role.name == "Admin"));
@@ -56,3 +63,353 @@ async fn e2e_sso_flow_uses_mock_endpoints() {
     }
     server.shutdown().await.expect("mock server shutdown");
 }
+
+#[tokio::test]
+async fn e2e_native_sso_login_writes_aws_token_cache() {
+    let _lock = lock_env();
+    let server = start_mock_server(MockServerOptions {
+        host: "127.0.0.1".to_string(),
+        port: 0,
+    })
+    .await
+    .expect("failed to start mock server");
+    let base = format!("http://{}", server.addr());
+    let home = tempfile::TempDir::new().expect("temp home");
+    let previous_oidc = std::env::var("ROLEMAN_OIDC_ENDPOINT").ok();
+    let previous_imds = std::env::var("AWS_EC2_METADATA_DISABLED").ok();
+    let previous_home = std::env::var("HOME").ok();
+    unsafe {
+        std::env::set_var("ROLEMAN_OIDC_ENDPOINT", format!("{}/oidc", base));
+        std::env::set_var("AWS_EC2_METADATA_DISABLED", "true");
+        std::env::set_var("HOME", home.path());
+    }
+
+    let start_url = "https://mock.awsapps.com/start";
+    let entry = login::sso_login(
+        "roleman-mock",
+        start_url,
+        "us-east-1",
+        false,
+        PostLoginActions::default(),
+    )
+    .await
+    .expect("sso_login failed");
+    assert_eq!(entry.access_token, "mock-access-token");
+    assert_eq!(entry.region, "us-east-1");
+
+    let cached = sso_cache::load_valid_cache(start_url).expect("token written to sso cache");
+    assert_eq!(cached.access_token, "mock-access-token");
+    assert_eq!(cached.expires_at, entry.expires_at);
+
+    unsafe {
+        if let Some(value) = previous_oidc {
+            std::env::set_var("ROLEMAN_OIDC_ENDPOINT", value);
+        } else {
+            std::env::remove_var("ROLEMAN_OIDC_ENDPOINT");
+        }
+        if let Some(value) = previous_imds {
+            std::env::set_var("AWS_EC2_METADATA_DISABLED", value);
+        } else {
+            std::env::remove_var("AWS_EC2_METADATA_DISABLED");
+        }
+        if let Some(value) = previous_home {
+            std::env::set_var("HOME", value);
+        } else {
+            std::env::remove_var("HOME");
+        }
+    }
+    server.shutdown().await.expect("mock server shutdown");
+}
+
+#[tokio::test]
+async fn e2e_refreshes_expired_sso_token_with_refresh_token() {
+    let _lock = lock_env();
+    let server = start_mock_server(MockServerOptions {
+        host: "127.0.0.1".to_string(),
+        port: 0,
+    })
+    .await
+    .expect("failed to start mock server");
+    let base = format!("http://{}", server.addr());
+    let home = tempfile::TempDir::new().expect("temp home");
+    let previous_oidc = std::env::var("ROLEMAN_OIDC_ENDPOINT").ok();
+    let previous_imds = std::env::var("AWS_EC2_METADATA_DISABLED").ok();
+    let previous_home = std::env::var("HOME").ok();
+    unsafe {
+        std::env::set_var("ROLEMAN_OIDC_ENDPOINT", format!("{}/oidc", base));
+        std::env::set_var("AWS_EC2_METADATA_DISABLED", "true");
+        std::env::set_var("HOME", home.path());
+    }
+
+    let start_url = "https://mock.awsapps.com/start";
+    let cache_dir = home.path().join(".aws").join("sso").join("cache");
+    std::fs::create_dir_all(&cache_dir).expect("create sso cache dir");
+    let token_path = cache_dir.join("expired.json");
+    std::fs::write(
+        &token_path,
+        serde_json::json!({
+            "startUrl": start_url,
+            "region": "us-east-1",
+            "accessToken": "expired-access-token",
+            "expiresAt": "2000-01-01T00:00:00Z",
+            "clientId": "mock-client",
+            "clientSecret": "mock-secret",
+            "registrationExpiresAt": "2099-01-01T00:00:00Z",
+            "refreshToken": "mock-refresh-token",
+            "scopes": ["sso:account:access"],
+        })
+        .to_string(),
+    )
+    .expect("write expired token");
+
+    let entry = login::refresh_sso_token(start_url)
+        .await
+        .expect("refresh produced a token");
+    assert_eq!(entry.access_token, "mock-access-token");
+
+    let cached = sso_cache::load_valid_cache(start_url).expect("refreshed token is valid");
+    assert_eq!(cached.access_token, "mock-access-token");
+    let rewritten: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&token_path).expect("read token file"))
+            .expect("parse token file");
+    // The mock does not rotate refresh tokens, so the original one must be kept.
+    assert_eq!(rewritten["refreshToken"], "mock-refresh-token");
+    assert_eq!(rewritten["clientId"], "mock-client");
+    // Fields roleman doesn't know about survive the rewrite.
+    assert_eq!(
+        rewritten["scopes"],
+        serde_json::json!(["sso:account:access"])
+    );
+
+    unsafe {
+        if let Some(value) = previous_oidc {
+            std::env::set_var("ROLEMAN_OIDC_ENDPOINT", value);
+        } else {
+            std::env::remove_var("ROLEMAN_OIDC_ENDPOINT");
+        }
+        if let Some(value) = previous_imds {
+            std::env::set_var("AWS_EC2_METADATA_DISABLED", value);
+        } else {
+            std::env::remove_var("AWS_EC2_METADATA_DISABLED");
+        }
+        if let Some(value) = previous_home {
+            std::env::set_var("HOME", value);
+        } else {
+            std::env::remove_var("HOME");
+        }
+    }
+    server.shutdown().await.expect("mock server shutdown");
+}
+
+#[tokio::test]
+async fn e2e_chained_roles_assume_the_target_as_the_sso_user() {
+    let _lock = lock_env();
+    let server = start_mock_server(MockServerOptions {
+        host: "127.0.0.1".to_string(),
+        port: 0,
+    })
+    .await
+    .expect("failed to start mock server");
+    let base = format!("http://{}", server.addr());
+    let home = tempfile::TempDir::new().expect("temp home");
+    let previous_sso = std::env::var("ROLEMAN_SSO_ENDPOINT").ok();
+    let previous_sts = std::env::var("ROLEMAN_STS_ENDPOINT").ok();
+    let previous_imds = std::env::var("AWS_EC2_METADATA_DISABLED").ok();
+    let previous_home = std::env::var("HOME").ok();
+    unsafe {
+        std::env::set_var("ROLEMAN_SSO_ENDPOINT", format!("{}/sso", base));
+        std::env::set_var("ROLEMAN_STS_ENDPOINT", format!("{}/sts", base));
+        std::env::set_var("AWS_EC2_METADATA_DISABLED", "true");
+        std::env::set_var("HOME", home.path());
+    }
+
+    let start_url = "https://mock.awsapps.com/start";
+    let cache_dir = home.path().join(".aws").join("sso").join("cache");
+    std::fs::create_dir_all(&cache_dir).expect("create sso cache dir");
+    std::fs::write(
+        cache_dir.join("token.json"),
+        serde_json::json!({
+            "startUrl": start_url,
+            "region": "us-east-1",
+            "accessToken": "mock-access-token",
+            "expiresAt": "2099-01-01T00:00:00Z",
+        })
+        .to_string(),
+    )
+    .expect("write sso token");
+
+    let identity: SsoIdentity = toml::from_str(&format!(
+        r#"
+        name = "mock"
+        start_url = "{start_url}"
+        sso_region = "us-east-1"
+
+        [[chained_roles]]
+        name = "deployer"
+        source_account_id = "111111111111"
+        source_role = "Admin"
+        role_arn = "arn:aws:iam::222222222222:role/Deployer"
+        "#
+    ))
+    .expect("parse aws identity");
+    let provider = for_identity(&identity).expect("aws provider");
+    let session = provider
+        .ensure_session(false, PostLoginActions::default())
+        .await
+        .expect("ensure_session failed");
+    let choice = RoleChoice {
+        account_id: "222222222222".to_string(),
+        account_name: "Mock Data".to_string(),
+        role_name: "deployer".to_string(),
+    };
+    // Cached apart from any SSO role that happens to share the name.
+    assert_eq!(provider.cache_role_name(&choice), "chained:deployer");
+
+    let creds = provider
+        .fetch_credentials(session.as_ref(), &choice, AccessScope::Full, None, false)
+        .await
+        .expect("fetch_credentials failed");
+    let document: serde_json::Value =
+        serde_json::from_str(&creds.credential_process_json().expect("render credentials"))
+            .expect("parse credentials");
+    assert_eq!(document["AccessKeyId"], "ASIAMOCKCHAINED");
+    assert_eq!(
+        document["SessionToken"],
+        format!("mock-chained-token:{MOCK_SSO_USER}")
+    );
+
+    unsafe {
+        if let Some(value) = previous_sso {
+            std::env::set_var("ROLEMAN_SSO_ENDPOINT", value);
+        } else {
+            std::env::remove_var("ROLEMAN_SSO_ENDPOINT");
+        }
+        if let Some(value) = previous_sts {
+            std::env::set_var("ROLEMAN_STS_ENDPOINT", value);
+        } else {
+            std::env::remove_var("ROLEMAN_STS_ENDPOINT");
+        }
+        if let Some(value) = previous_imds {
+            std::env::set_var("AWS_EC2_METADATA_DISABLED", value);
+        } else {
+            std::env::remove_var("AWS_EC2_METADATA_DISABLED");
+        }
+        if let Some(value) = previous_home {
+            std::env::set_var("HOME", value);
+        } else {
+            std::env::remove_var("HOME");
+        }
+    }
+    server.shutdown().await.expect("mock server shutdown");
+}
+
+#[tokio::test]
+async fn e2e_gcp_provider_impersonates_service_accounts() {
+    let _lock = lock_env();
+    let server = start_mock_server(MockServerOptions {
+        host: "127.0.0.1".to_string(),
+        port: 0,
+    })
+    .await
+    .expect("failed to start mock server");
+    let base = format!("http://{}", server.addr());
+    let temp = tempfile::TempDir::new().expect("temp dir");
+    let adc_path = temp.path().join("application_default_credentials.json");
+    std::fs::write(
+        &adc_path,
+        serde_json::json!({
+            "type": "authorized_user",
+            "client_id": "mock-client",
+            "client_secret": "mock-secret",
+            "refresh_token": "mock-refresh-token",
+        })
+        .to_string(),
+    )
+    .expect("write adc");
+    let previous_endpoint = std::env::var("ROLEMAN_GCP_ENDPOINT").ok();
+    let previous_adc = std::env::var("GOOGLE_APPLICATION_CREDENTIALS").ok();
+    unsafe {
+        std::env::set_var("ROLEMAN_GCP_ENDPOINT", format!("{}/gcp", base));
+        std::env::set_var("GOOGLE_APPLICATION_CREDENTIALS", &adc_path);
+    }
+
+    let identity: SsoIdentity = toml::from_str(
+        r#"
+        name = "gcp"
+        provider = "gcp"
+        readonly_policy = { inline = '{"accessBoundary":{"accessBoundaryRules":[{"availableResource":"//storage.googleapis.com/projects/_/buckets/{project}-data","availablePermissions":["inRole:roles/storage.objectViewer"]}]}}' }
+        "#,
+    )
+    .expect("parse gcp identity");
+    let provider = for_identity(&identity).expect("gcp provider");
+    let session = provider
+        .ensure_session(false, PostLoginActions::default())
+        .await
+        .expect("ensure_session failed");
+
+    let listing = provider
+        .list_choices(session.as_ref())
+        .await
+        .expect("list_choices failed");
+    // Disabled accounts and projects whose service accounts the user may not see are skipped;
+    // projects that fail for other reasons are reported.
+    let failed = listing
+        .failures
+        .iter()
+        .map(|failure| failure.account_id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(failed, ["mock-outage"]);
+    let choices = listing.choices;
+    assert_eq!(choices.len(), 1);
+    let choice = &choices[0];
+    assert_eq!(choice.account_id, "mock-platform");
+    assert_eq!(choice.account_name, "Mock Platform");
+    assert_eq!(
+        choice.role_name,
+        "deploy@mock-platform.iam.gserviceaccount.com"
+    );
+
+    let binding = provider
+        .ensure_profile(session.as_ref(), choice, AccessScope::Full, None, true)
+        .expect("ensure_profile failed");
+    let creds = provider
+        .fetch_credentials(
+            session.as_ref(),
+            choice,
+            AccessScope::Full,
+            Some("europe-west1"),
+            false,
+        )
+        .await
+        .expect("fetch_credentials failed");
+    let vars = creds.env_vars(&binding);
+    assert!(vars.contains(&EnvVar::new(
+        "CLOUDSDK_AUTH_ACCESS_TOKEN",
+        "mock-gcp-sa-token"
+    )));
+    assert!(vars.contains(&EnvVar::new("CLOUDSDK_CORE_PROJECT", "mock-platform")));
+    assert!(vars.contains(&EnvVar::new("CLOUDSDK_COMPUTE_REGION", "europe-west1")));
+
+    let readonly = provider
+        .fetch_credentials(session.as_ref(), choice, AccessScope::ReadOnly, None, false)
+        .await
+        .expect("readonly fetch_credentials failed");
+    assert!(readonly.env_vars(&binding).contains(&EnvVar::new(
+        "GOOGLE_OAUTH_ACCESS_TOKEN",
+        "mock-gcp-downscoped-token"
+    )));
+
+    unsafe {
+        if let Some(value) = previous_endpoint {
+            std::env::set_var("ROLEMAN_GCP_ENDPOINT", value);
+        } else {
+            std::env::remove_var("ROLEMAN_GCP_ENDPOINT");
+        }
+        if let Some(value) = previous_adc {
+            std::env::set_var("GOOGLE_APPLICATION_CREDENTIALS", value);
+        } else {
+            std::env::remove_var("GOOGLE_APPLICATION_CREDENTIALS");
+        }
+    }
+    server.shutdown().await.expect("mock server shutdown");
+}